@@ -0,0 +1,32 @@
+//! Linux capture backend selection by desktop session type
+
+/// Which mechanism serves screen capture in the current Linux session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinuxBackend {
+    /// Direct X server access (Xorg sessions)
+    X11,
+    /// xdg-desktop-portal over D-Bus (Wayland sessions, where X11 grabbing only sees XWayland)
+    Portal,
+}
+
+impl LinuxBackend {
+    /// Pick a backend from `$XDG_SESSION_TYPE` / `$WAYLAND_DISPLAY`.
+    /// `SCREENCAPTURE_BACKEND=x11|portal` overrides detection.
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        match var("SCREENCAPTURE_BACKEND").as_deref() {
+            Some("x11") => return LinuxBackend::X11,
+            Some("portal") => return LinuxBackend::Portal,
+            Some(other) => log::warn!("Unknown SCREENCAPTURE_BACKEND '{}', detecting", other),
+            None => {}
+        }
+
+        match var("XDG_SESSION_TYPE").as_deref() {
+            Some("wayland") => LinuxBackend::Portal,
+            Some("x11") => LinuxBackend::X11,
+            _ if var("WAYLAND_DISPLAY").is_some() => LinuxBackend::Portal,
+            _ => LinuxBackend::X11,
+        }
+    }
+}
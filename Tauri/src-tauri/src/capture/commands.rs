@@ -1,4 +1,8 @@
 use crate::capture::config::*;
+use crate::capture::recording::audio::AudioDevice;
+use crate::capture::recording::encoder::format::FormatSupport;
+use crate::capture::recording::markers::RecordingMarker;
+use crate::capture::recording::replay::ReplayStatus;
 use crate::capture::recording::RecordingSessionState;
 use crate::error::CaptureError;
 use crate::state::app_state::AppState;
@@ -54,6 +58,8 @@ pub async fn capture_area(
     save_screenshot(&data, &format, &state)
 }
 
+/// On Wayland, `window_id` 0 lets the user pick the window in the portal's dialog;
+/// other ids are refused because the compositor can't capture them directly.
 #[tauri::command]
 pub async fn capture_window(
     window_id: u32,
@@ -77,27 +83,65 @@ pub async fn list_windows() -> Result<Vec<WindowInfo>, CaptureError> {
     provider.get_windows().await
 }
 
+#[tauri::command]
+pub async fn list_audio_devices() -> Result<Vec<AudioDevice>, CaptureError> {
+    tokio::task::spawn_blocking(crate::capture::recording::audio::list_devices)
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))?
+}
+
+/// Recording formats and whether the installed ffmpeg can write them
+#[tauri::command]
+pub async fn list_output_formats() -> Result<Vec<FormatSupport>, CaptureError> {
+    tokio::task::spawn_blocking(crate::capture::recording::encoder::format::supported_formats)
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))
+}
+
 #[tauri::command]
 pub async fn start_recording(
     target: RecordingTarget,
     config: RecordingConfig,
-    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
 ) -> Result<(), CaptureError> {
-    crate::capture::recording::start_recording(target, config, &state).await
+    crate::capture::recording::start_recording(target, config, &app).await
 }
 
 #[tauri::command]
 pub async fn stop_recording(
-    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
 ) -> Result<CaptureItem, CaptureError> {
-    crate::capture::recording::stop_recording(&state).await
+    crate::capture::recording::stop_recording(&app).await
+}
+
+#[tauri::command]
+pub fn pause_recording(
+    app: tauri::AppHandle,
+) -> Result<(), CaptureError> {
+    crate::capture::recording::pause_recording(&app)
+}
+
+#[tauri::command]
+pub fn resume_recording(
+    app: tauri::AppHandle,
+) -> Result<(), CaptureError> {
+    crate::capture::recording::resume_recording(&app)
 }
 
 #[tauri::command]
 pub async fn cancel_recording(
-    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
 ) -> Result<(), CaptureError> {
-    crate::capture::recording::cancel_recording(&state).await
+    crate::capture::recording::cancel_recording(&app).await
+}
+
+/// Drop a marker at the current point of the active recording; saved as a chapter
+#[tauri::command]
+pub fn add_recording_marker(
+    name: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<RecordingMarker, CaptureError> {
+    crate::capture::recording::add_marker(&app, name)
 }
 
 #[tauri::command]
@@ -106,3 +150,29 @@ pub fn get_recording_state(
 ) -> RecordingSessionState {
     crate::capture::recording::get_state(&state)
 }
+
+/// Start buffering the most recent `duration_seconds` of a display in the background
+#[tauri::command]
+pub async fn start_replay_buffer(
+    config: Option<ReplayConfig>,
+    app: tauri::AppHandle,
+) -> Result<ReplayStatus, CaptureError> {
+    crate::capture::recording::replay::start(&app, config.unwrap_or_default()).await
+}
+
+/// Stop the replay buffer, discarding what it holds. Returns false if it wasn't running.
+#[tauri::command]
+pub async fn stop_replay_buffer(app: tauri::AppHandle) -> Result<bool, CaptureError> {
+    crate::capture::recording::replay::stop(&app).await
+}
+
+/// Save the buffered replay as a new recording; the buffer keeps running
+#[tauri::command]
+pub async fn save_replay(app: tauri::AppHandle) -> Result<CaptureItem, CaptureError> {
+    crate::capture::recording::replay::save(&app).await
+}
+
+#[tauri::command]
+pub fn get_replay_status(app: tauri::AppHandle) -> Option<ReplayStatus> {
+    crate::capture::recording::replay::status(&app)
+}
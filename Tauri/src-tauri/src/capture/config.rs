@@ -1,19 +1,45 @@
+use crate::error::CaptureError;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Seconds between keyframes unless a custom preset says otherwise
+const DEFAULT_KEYFRAME_SECONDS: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum QualityPreset {
     Low,    // 720p, 5 Mbps
     Medium, // 1080p, 8 Mbps
     High,   // Native, 12 Mbps
+    Custom(CustomQuality),
+}
+
+/// Explicit encoder settings for `QualityPreset::Custom`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CustomQuality {
+    /// Output is scaled to fit within `width` x `height`, keeping the aspect ratio
+    pub width: u32,
+    pub height: u32,
+    /// Peak video bitrate in bits per second
+    pub bitrate: u32,
+    /// Constant rate factor (lower is better); the bitrate caps it
+    pub crf: u8,
+    pub keyframe_interval_seconds: f64,
 }
 
 impl QualityPreset {
+    pub fn max_width(&self) -> u32 {
+        match self {
+            QualityPreset::Custom(custom) => custom.width,
+            _ => u32::MAX,
+        }
+    }
+
     pub fn max_height(&self) -> u32 {
         match self {
             QualityPreset::Low => 720,
             QualityPreset::Medium => 1080,
             QualityPreset::High => u32::MAX,
+            QualityPreset::Custom(custom) => custom.height,
         }
     }
 
@@ -22,7 +48,59 @@ impl QualityPreset {
             QualityPreset::Low => 5_000_000,
             QualityPreset::Medium => 8_000_000,
             QualityPreset::High => 12_000_000,
+            QualityPreset::Custom(custom) => custom.bitrate,
+        }
+    }
+
+    /// x264-scale constant rate factor
+    pub fn crf(&self) -> u8 {
+        match self {
+            QualityPreset::Low => 26,
+            QualityPreset::Medium => 23,
+            QualityPreset::High => 20,
+            QualityPreset::Custom(custom) => custom.crf,
+        }
+    }
+
+    pub fn keyframe_interval_seconds(&self) -> f64 {
+        match self {
+            QualityPreset::Custom(custom) => custom.keyframe_interval_seconds,
+            _ => DEFAULT_KEYFRAME_SECONDS,
+        }
+    }
+
+    /// Encoded size for a `width` x `height` source: scaled down to fit the preset,
+    /// never up, keeping the aspect ratio, with even dimensions for 4:2:0 video
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = (self.max_width() as f64 / width.max(1) as f64)
+            .min(self.max_height() as f64 / height.max(1) as f64)
+            .min(1.0);
+        let even = |v: u32| ((v as f64 * scale) as u32 / 2 * 2).max(2);
+        (even(width), even(height))
+    }
+
+    pub fn validate(&self) -> Result<(), CaptureError> {
+        let QualityPreset::Custom(custom) = self else { return Ok(()) };
+        if !(16..=8192).contains(&custom.width) || !(16..=8192).contains(&custom.height) {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Custom resolution {}x{} must be between 16 and 8192 pixels per side",
+                custom.width, custom.height
+            )));
+        }
+        if !(100_000..=500_000_000).contains(&custom.bitrate) {
+            return Err(CaptureError::InvalidConfig(
+                "Custom bitrate must be between 100 kbps and 500 Mbps".into(),
+            ));
         }
+        if custom.crf > 51 {
+            return Err(CaptureError::InvalidConfig("Custom CRF must be between 0 and 51".into()));
+        }
+        if !(0.1..=60.0).contains(&custom.keyframe_interval_seconds) {
+            return Err(CaptureError::InvalidConfig(
+                "Keyframe interval must be between 0.1 and 60 seconds".into(),
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -42,15 +120,176 @@ pub enum RecordingTarget {
     Window { window_id: u32 },
 }
 
+/// How microphone and system audio are written when both are recorded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AudioMode {
+    /// One track with both sources mixed
+    #[default]
+    Mix,
+    /// One AAC track per source, for editing them independently
+    SeparateTracks,
+}
+
+/// How a window recording fits the window into its fixed output size after a resize
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowScaling {
+    /// Scale to fit, keeping the aspect ratio, with black bars
+    #[default]
+    Letterbox,
+    /// Scale to fill, distorting the aspect ratio
+    Stretch,
+}
+
+/// Container and codec recordings are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputFormat {
+    /// H.264 + AAC in MP4
+    #[default]
+    Mp4,
+    /// HEVC + AAC in MP4
+    Hevc,
+    /// VP9 + Opus in WebM
+    Webm,
+    /// AV1 + AAC in MP4
+    Av1,
+    /// Animated WebP, no audio
+    Webp,
+    /// Animated PNG, no audio
+    Apng,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 | OutputFormat::Hevc | OutputFormat::Av1 => "mp4",
+            OutputFormat::Webm => "webm",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Apng => "png",
+        }
+    }
+
+    /// The format a recording with file extension `extension` was written in
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        use OutputFormat::*;
+        [Mp4, Hevc, Webm, Av1, Webp, Apng].into_iter().find(|f| f.extension().eq_ignore_ascii_case(extension))
+    }
+
+    /// Video containers; the others are animated images, which trimming and the
+    /// exports can't read back
+    pub fn is_video(&self) -> bool {
+        !matches!(self, OutputFormat::Webp | OutputFormat::Apng)
+    }
+
+    pub fn supports_audio(&self) -> bool {
+        self.is_video()
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 => "MP4 (H.264)",
+            OutputFormat::Hevc => "MP4 (HEVC)",
+            OutputFormat::Webm => "WebM (VP9)",
+            OutputFormat::Av1 => "MP4 (AV1)",
+            OutputFormat::Webp => "Animated WebP",
+            OutputFormat::Apng => "Animated PNG",
+        }
+    }
+}
+
+/// Where the keystroke HUD sits in the frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HudPosition {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomCenter,
+    BottomRight,
+}
+
+/// Look of the click and keystroke overlays. Colours are RGBA; sizes are pixels
+/// at 1080p and scale with the recording height.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayStyle {
+    pub keystroke_position: HudPosition,
+    pub font_size: f32,
+    pub text_color: [u8; 4],
+    pub background_color: [u8; 4],
+    pub keystroke_duration_ms: u64,
+    pub click_color: [u8; 4],
+    pub ripple_radius: f32,
+}
+
+impl Default for OverlayStyle {
+    fn default() -> Self {
+        Self {
+            keystroke_position: HudPosition::BottomCenter,
+            font_size: 36.0,
+            text_color: [255, 255, 255, 255],
+            background_color: [0, 0, 0, 170],
+            keystroke_duration_ms: 1500,
+            click_color: [255, 204, 0, 220],
+            ripple_radius: 36.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordingConfig {
     pub quality: QualityPreset,
     pub fps: u32,
     pub include_cursor: bool,
     pub show_mouse_clicks: bool,
+    #[serde(default)]
+    pub show_keystrokes: bool,
+    #[serde(default)]
+    pub overlay: OverlayStyle,
     pub include_microphone: bool,
     pub include_system_audio: bool,
+    /// Leave this app's own sound out of system audio. Linux only; a macOS loopback
+    /// device can't tell apps apart.
     pub exclude_app_audio: bool,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub audio_mode: AudioMode,
+    /// Window targets only; the output keeps the window's size at the start
+    #[serde(default)]
+    pub window_scaling: WindowScaling,
+    /// Input device id from `list_audio_devices`; the system default when unset
+    #[serde(default)]
+    pub microphone_device: Option<String>,
+    /// Output/monitor device to capture system audio from; the default output when unset
+    #[serde(default)]
+    pub system_audio_device: Option<String>,
+    /// Stop automatically after this much recorded (not wall-clock) time
+    #[serde(default)]
+    pub max_duration_seconds: Option<u64>,
+    /// Stop automatically before the output grows past this size
+    #[serde(default)]
+    pub max_file_size_bytes: Option<u64>,
+    /// Stop automatically when the output volume has less free space than this
+    #[serde(default)]
+    pub min_free_disk_bytes: Option<u64>,
+}
+
+impl RecordingConfig {
+    /// Reject settings no recording could start with
+    pub fn validate(&self) -> Result<(), CaptureError> {
+        if self.fps == 0 || self.fps > 120 {
+            return Err(CaptureError::InvalidConfig(format!("Unsupported frame rate: {}", self.fps)));
+        }
+        if self.max_duration_seconds == Some(0) || self.max_file_size_bytes == Some(0) {
+            return Err(CaptureError::InvalidConfig("Recording limits must be greater than zero".into()));
+        }
+        self.quality.validate()
+    }
 }
 
 impl Default for RecordingConfig {
@@ -60,9 +299,44 @@ impl Default for RecordingConfig {
             fps: 60,
             include_cursor: true,
             show_mouse_clicks: true,
+            show_keystrokes: false,
+            overlay: OverlayStyle::default(),
             include_microphone: false,
             include_system_audio: true,
             exclude_app_audio: true,
+            format: OutputFormat::Mp4,
+            audio_mode: AudioMode::Mix,
+            window_scaling: WindowScaling::Letterbox,
+            microphone_device: None,
+            system_audio_device: None,
+            max_duration_seconds: None,
+            max_file_size_bytes: None,
+            min_free_disk_bytes: None,
+        }
+    }
+}
+
+/// Background replay buffer: keeps the most recent stretch of a display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    /// Display to buffer; the primary display when unset
+    pub display_id: Option<u32>,
+    /// How much recent history a saved replay covers
+    pub duration_seconds: u32,
+    pub fps: u32,
+    pub quality: QualityPreset,
+    pub include_cursor: bool,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            display_id: None,
+            duration_seconds: 30,
+            fps: 30,
+            quality: QualityPreset::Medium,
+            include_cursor: true,
         }
     }
 }
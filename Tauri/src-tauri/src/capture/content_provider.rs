@@ -3,6 +3,12 @@ use crate::error::CaptureError;
 
 pub struct ContentProvider;
 
+impl Default for ContentProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ContentProvider {
     pub fn new() -> Self {
         Self
@@ -41,7 +47,21 @@ impl ContentProvider {
 
             Ok(displays)
         }
-        #[cfg(not(target_os = "macos"))]
+        #[cfg(target_os = "linux")]
+        {
+            tokio::task::spawn_blocking(|| {
+                use crate::capture::backend::LinuxBackend;
+                match crate::capture::x11::X11Session::connect() {
+                    Ok(session) => session.displays(),
+                    // Pure Wayland without XWayland: the portal picks sources itself
+                    Err(_) if LinuxBackend::detect() == LinuxBackend::Portal => Ok(vec![]),
+                    Err(e) => Err(e),
+                }
+            })
+            .await
+            .map_err(|e| CaptureError::CaptureFailed(e.to_string()))?
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
         {
             Ok(vec![])
         }
@@ -171,7 +191,21 @@ impl ContentProvider {
 
             Ok(windows)
         }
-        #[cfg(not(target_os = "macos"))]
+        #[cfg(target_os = "linux")]
+        {
+            tokio::task::spawn_blocking(|| {
+                use crate::capture::backend::LinuxBackend;
+                match crate::capture::x11::X11Session::connect() {
+                    Ok(session) => session.windows(),
+                    // Pure Wayland without XWayland: the portal picks sources itself
+                    Err(_) if LinuxBackend::detect() == LinuxBackend::Portal => Ok(vec![]),
+                    Err(e) => Err(e),
+                }
+            })
+            .await
+            .map_err(|e| CaptureError::CaptureFailed(e.to_string()))?
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
         {
             Ok(vec![])
         }
@@ -0,0 +1,145 @@
+//! Global input on macOS via a listen-only Quartz event tap.
+//! Creating the tap fails unless the app has Input Monitoring permission.
+use super::{InputEvent, Modifiers, MouseButton, Subscribers, TimedInput};
+use crate::error::CaptureError;
+use core_foundation::runloop::{kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoop};
+use core_graphics::event::{
+    CGEvent, CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement, CGEventType,
+    EventField,
+};
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long each run loop pass waits before checking whether to stop
+const RUN_LOOP_SLICE: Duration = Duration::from_millis(100);
+
+pub fn spawn(tx: Subscribers, stop: Arc<AtomicBool>) -> Result<JoinHandle<()>, CaptureError> {
+    // The tap must be created on the thread whose run loop services it; report
+    // whether that worked back to the caller before returning
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    let handle = std::thread::Builder::new()
+        .name("input-monitor".into())
+        .spawn(move || {
+            let tap = CGEventTap::new(
+                CGEventTapLocation::Session,
+                CGEventTapPlacement::TailAppendEventTap,
+                CGEventTapOptions::ListenOnly,
+                vec![
+                    CGEventType::LeftMouseDown,
+                    CGEventType::RightMouseDown,
+                    CGEventType::OtherMouseDown,
+                    CGEventType::KeyDown,
+                ],
+                move |_proxy, event_type, event| {
+                    let parsed = match event_type {
+                        CGEventType::LeftMouseDown => Some(click(event, MouseButton::Left)),
+                        CGEventType::RightMouseDown => Some(click(event, MouseButton::Right)),
+                        CGEventType::OtherMouseDown => Some(click(event, MouseButton::Other)),
+                        CGEventType::KeyDown
+                            if event.get_integer_value_field(EventField::KEYBOARD_EVENT_AUTOREPEAT) == 0 =>
+                        {
+                            key(event)
+                        }
+                        _ => None,
+                    };
+                    if let Some(event) = parsed {
+                        tx.send(TimedInput { at: Instant::now(), event });
+                    }
+                    None
+                },
+            );
+
+            let tap = match tap {
+                Ok(tap) => tap,
+                Err(()) => {
+                    let _ = ready_tx.send(Err(CaptureError::PermissionDenied(
+                        "Input Monitoring permission is required to show clicks and keystrokes".into(),
+                    )));
+                    return;
+                }
+            };
+            let source = match tap.mach_port.create_runloop_source(0) {
+                Ok(source) => source,
+                Err(()) => {
+                    let _ = ready_tx.send(Err(CaptureError::CaptureFailed("Could not attach event tap".into())));
+                    return;
+                }
+            };
+            unsafe { CFRunLoop::get_current().add_source(&source, kCFRunLoopCommonModes) };
+            tap.enable();
+            let _ = ready_tx.send(Ok(()));
+
+            while !stop.load(Ordering::Acquire) {
+                unsafe { CFRunLoop::run_in_mode(kCFRunLoopDefaultMode, RUN_LOOP_SLICE, false) };
+            }
+        })?;
+
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(handle),
+        Ok(Err(e)) => {
+            let _ = handle.join();
+            Err(e)
+        }
+        Err(_) => Err(CaptureError::CaptureFailed("Input monitor thread exited".into())),
+    }
+}
+
+/// Polls the pointer position; a fresh event carries the current location
+pub struct PointerReader {
+    source: CGEventSource,
+}
+
+impl PointerReader {
+    pub fn open() -> Result<Self, CaptureError> {
+        let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+            .map_err(|_| CaptureError::CaptureFailed("Could not create an event source".into()))?;
+        Ok(Self { source })
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        let location = CGEvent::new(self.source.clone()).ok()?.location();
+        Some((location.x, location.y))
+    }
+}
+
+fn click(event: &CGEvent, button: MouseButton) -> InputEvent {
+    let location = event.location();
+    InputEvent::Click { x: location.x, y: location.y, button }
+}
+
+fn key(event: &CGEvent) -> Option<InputEvent> {
+    let keycode = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u16;
+    let flags = event.get_flags();
+    let modifiers = Modifiers {
+        control: flags.contains(CGEventFlags::CGEventFlagControl),
+        alt: flags.contains(CGEventFlags::CGEventFlagAlternate),
+        shift: flags.contains(CGEventFlags::CGEventFlagShift),
+        meta: flags.contains(CGEventFlags::CGEventFlagCommand),
+    };
+    Some(InputEvent::Key { key: key_label(keycode)?.to_string(), modifiers })
+}
+
+/// HUD label for an ANSI-layout virtual keycode
+fn key_label(keycode: u16) -> Option<&'static str> {
+    const LETTERS: [(u16, &str); 26] = [
+        (0x00, "A"), (0x0B, "B"), (0x08, "C"), (0x02, "D"), (0x0E, "E"), (0x03, "F"), (0x05, "G"),
+        (0x04, "H"), (0x22, "I"), (0x26, "J"), (0x28, "K"), (0x25, "L"), (0x2E, "M"), (0x2D, "N"),
+        (0x1F, "O"), (0x23, "P"), (0x0C, "Q"), (0x0F, "R"), (0x01, "S"), (0x11, "T"), (0x20, "U"),
+        (0x09, "V"), (0x0D, "W"), (0x07, "X"), (0x10, "Y"), (0x06, "Z"),
+    ];
+    const OTHERS: [(u16, &str); 48] = [
+        (0x1D, "0"), (0x12, "1"), (0x13, "2"), (0x14, "3"), (0x15, "4"), (0x17, "5"), (0x16, "6"),
+        (0x1A, "7"), (0x1C, "8"), (0x19, "9"), (0x31, "Space"), (0x24, "⏎"), (0x4C, "⏎"), (0x30, "⇥"),
+        (0x33, "⌫"), (0x75, "⌦"), (0x35, "⎋"), (0x7B, "←"), (0x7C, "→"), (0x7D, "↓"), (0x7E, "↑"),
+        (0x73, "↖"), (0x77, "↘"), (0x74, "⇞"), (0x79, "⇟"), (0x1B, "-"), (0x18, "="), (0x21, "["),
+        (0x1E, "]"), (0x2A, "\\"), (0x29, ";"), (0x27, "'"), (0x2B, ","), (0x2F, "."), (0x2C, "/"),
+        (0x32, "`"), (0x7A, "F1"), (0x78, "F2"), (0x63, "F3"), (0x76, "F4"), (0x60, "F5"), (0x61, "F6"),
+        (0x62, "F7"), (0x64, "F8"), (0x65, "F9"), (0x6D, "F10"), (0x67, "F11"), (0x6F, "F12"),
+    ];
+    LETTERS.iter().chain(OTHERS.iter()).find(|(code, _)| *code == keycode).map(|(_, label)| *label)
+}
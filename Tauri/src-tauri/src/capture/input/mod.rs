@@ -0,0 +1,195 @@
+//! Global mouse and keyboard event stream, independent of which app has focus.
+//!
+//! Used to draw click and keystroke feedback into recordings and to record the
+//! cursor track that auto-zoom exports follow. Listening needs
+//! Accessibility/Input Monitoring permission on macOS and an X11 session on Linux;
+//! Wayland compositors don't expose global input to clients.
+use crate::error::CaptureError;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Modifiers {
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Command on macOS, Super/Windows elsewhere
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub fn any(&self) -> bool {
+        self.control || self.alt || self.shift || self.meta
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    /// Button press at a global screen position (the platform's pointer coordinates)
+    Click { x: f64, y: f64, button: MouseButton },
+    /// Non-modifier key press; `key` is a display label such as "A", "Space" or "←"
+    Key { key: String, modifiers: Modifiers },
+}
+
+#[derive(Debug, Clone)]
+pub struct TimedInput {
+    pub at: Instant,
+    pub event: InputEvent,
+}
+
+/// Listens for global input on a background thread until dropped.
+/// Shared behind an `Arc` so one listener feeds every consumer of a session.
+pub struct InputMonitor {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    subscribers: Subscribers,
+}
+
+impl InputMonitor {
+    pub fn start() -> Result<Arc<Self>, CaptureError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let subscribers = Subscribers::default();
+        let handle = spawn_listener(subscribers.clone(), stop.clone())?;
+        Ok(Arc::new(Self { stop, handle: Some(handle), subscribers }))
+    }
+
+    /// A new stream of the events received from now on. It keeps the monitor running
+    /// until it is dropped too.
+    pub fn subscribe(self: &Arc<Self>) -> InputSubscription {
+        let (tx, events) = mpsc::channel();
+        self.subscribers.0.lock().unwrap().push(tx);
+        InputSubscription { events, _monitor: self.clone() }
+    }
+}
+
+impl Drop for InputMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// One consumer's view of an `InputMonitor`
+pub struct InputSubscription {
+    events: Receiver<TimedInput>,
+    _monitor: Arc<InputMonitor>,
+}
+
+impl InputSubscription {
+    /// Events received since the last call, oldest first
+    pub fn drain(&self) -> impl Iterator<Item = TimedInput> + '_ {
+        self.events.try_iter()
+    }
+}
+
+/// Where the listener thread sends events: every live subscription
+#[derive(Clone, Default)]
+struct Subscribers(Arc<Mutex<Vec<Sender<TimedInput>>>>);
+
+impl Subscribers {
+    fn send(&self, input: TimedInput) {
+        self.0.lock().unwrap().retain(|tx| tx.send(input.clone()).is_ok());
+    }
+}
+
+/// Reads the global pointer position on demand, in the same coordinates as clicks
+pub struct Pointer {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    inner: platform::PointerReader,
+}
+
+#[cfg(target_os = "linux")]
+use x11 as platform;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+impl Pointer {
+    pub fn open() -> Result<Self, CaptureError> {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        {
+            Ok(Self { inner: platform::PointerReader::open()? })
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        {
+            Err(CaptureError::CaptureFailed("Reading the pointer is not supported on this platform".into()))
+        }
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        {
+            self.inner.position()
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        {
+            None
+        }
+    }
+}
+
+fn spawn_listener(tx: Subscribers, stop: Arc<AtomicBool>) -> Result<JoinHandle<()>, CaptureError> {
+    #[cfg(target_os = "linux")]
+    {
+        x11::spawn(tx, stop)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::spawn(tx, stop)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (tx, stop);
+        Err(CaptureError::CaptureFailed("Global input monitoring is not supported on this platform".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(label: &str) -> TimedInput {
+        TimedInput { at: Instant::now(), event: InputEvent::Key { key: label.into(), modifiers: Modifiers::default() } }
+    }
+
+    #[test]
+    fn subscribers_each_get_every_event_and_dropped_ones_are_pruned() {
+        let subscribers = Subscribers::default();
+        let (first_tx, first) = mpsc::channel();
+        let (second_tx, second) = mpsc::channel();
+        subscribers.0.lock().unwrap().extend([first_tx, second_tx]);
+
+        subscribers.send(key("A"));
+        drop(second);
+        subscribers.send(key("B"));
+
+        let labels: Vec<_> = first
+            .try_iter()
+            .map(|input| match input.event {
+                InputEvent::Key { key, .. } => key,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(labels, ["A", "B"]);
+        assert_eq!(subscribers.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+//! Global input on X11 via XInput2 raw events on the root window
+use super::{InputEvent, Modifiers, MouseButton, Subscribers, TimedInput};
+use crate::capture::x11::{x11_error, X11Session};
+use crate::error::CaptureError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _, KeyEventFlags, XIEventMask};
+use x11rb::protocol::xproto::{ConnectionExt as _, KeyButMask, Keycode};
+use x11rb::protocol::Event;
+
+/// How often the listener checks whether it should stop when no events arrive
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub fn spawn(tx: Subscribers, stop: Arc<AtomicBool>) -> Result<JoinHandle<()>, CaptureError> {
+    let session = X11Session::connect()?;
+    session.conn.xinput_xi_query_version(2, 0).map_err(x11_error)?.reply().map_err(x11_error)?;
+    session
+        .conn
+        .xinput_xi_select_events(
+            session.root,
+            &[xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![XIEventMask::RAW_KEY_PRESS | XIEventMask::RAW_BUTTON_PRESS],
+            }],
+        )
+        .map_err(x11_error)?
+        .check()
+        .map_err(x11_error)?;
+    let keymap = Keymap::load(&session)?;
+
+    let handle = std::thread::Builder::new()
+        .name("input-monitor".into())
+        .spawn(move || {
+            while !stop.load(Ordering::Acquire) {
+                let event = match session.conn.poll_for_event() {
+                    Ok(Some(event)) => event,
+                    Ok(None) => {
+                        std::thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("Input monitor stopped: {}", e);
+                        break;
+                    }
+                };
+                let at = Instant::now();
+                let parsed = match event {
+                    Event::XinputRawButtonPress(e) => click(&session, e.detail),
+                    Event::XinputRawKeyPress(e) if !e.flags.contains(KeyEventFlags::KEY_REPEAT) => {
+                        key(&session, &keymap, e.detail as Keycode)
+                    }
+                    _ => None,
+                };
+                if let Some(event) = parsed {
+                    tx.send(TimedInput { at, event });
+                }
+            }
+        })?;
+    Ok(handle)
+}
+
+/// Pointer position and modifier state right now
+fn pointer(session: &X11Session) -> Option<(i16, i16, KeyButMask)> {
+    let reply = session.conn.query_pointer(session.root).ok()?.reply().ok()?;
+    Some((reply.root_x, reply.root_y, reply.mask))
+}
+
+/// Polls the pointer position over its own connection
+pub struct PointerReader {
+    session: X11Session,
+}
+
+impl PointerReader {
+    pub fn open() -> Result<Self, CaptureError> {
+        Ok(Self { session: X11Session::connect()? })
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        pointer(&self.session).map(|(x, y, _)| (x as f64, y as f64))
+    }
+}
+
+fn click(session: &X11Session, detail: u32) -> Option<InputEvent> {
+    let button = match detail {
+        1 => MouseButton::Left,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        // 4-7 are scroll wheel steps, not clicks
+        4..=7 => return None,
+        _ => MouseButton::Other,
+    };
+    let (x, y, _) = pointer(session)?;
+    Some(InputEvent::Click { x: x as f64, y: y as f64, button })
+}
+
+fn key(session: &X11Session, keymap: &Keymap, keycode: Keycode) -> Option<InputEvent> {
+    let label = key_label(keymap.keysym(keycode)?)?;
+    let (_, _, mask) = pointer(session)?;
+    let modifiers = Modifiers {
+        control: mask.contains(KeyButMask::CONTROL),
+        alt: mask.contains(KeyButMask::MOD1),
+        shift: mask.contains(KeyButMask::SHIFT),
+        meta: mask.contains(KeyButMask::MOD4),
+    };
+    Some(InputEvent::Key { key: label, modifiers })
+}
+
+/// Unshifted keysym for each keycode
+struct Keymap {
+    min_keycode: Keycode,
+    per_keycode: usize,
+    keysyms: Vec<u32>,
+}
+
+impl Keymap {
+    fn load(session: &X11Session) -> Result<Self, CaptureError> {
+        let setup = session.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let reply = session
+            .conn
+            .get_keyboard_mapping(min, max - min + 1)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        Ok(Self {
+            min_keycode: min,
+            per_keycode: reply.keysyms_per_keycode as usize,
+            keysyms: reply.keysyms,
+        })
+    }
+
+    fn keysym(&self, keycode: Keycode) -> Option<u32> {
+        let index = (keycode.checked_sub(self.min_keycode)? as usize) * self.per_keycode;
+        self.keysyms.get(index).copied().filter(|k| *k != 0)
+    }
+}
+
+/// HUD label for a keysym; `None` for modifiers, which are shown alongside other keys
+fn key_label(keysym: u32) -> Option<String> {
+    let label = match keysym {
+        // Latin-1 keysyms match their character codes
+        0x20 => "Space",
+        0x21..=0x7e => return Some((keysym as u8 as char).to_ascii_uppercase().to_string()),
+        0xff08 => "⌫",
+        0xff09 => "⇥",
+        0xff0d | 0xff8d => "⏎",
+        0xff1b => "Esc",
+        0xff50 => "Home",
+        0xff51 => "←",
+        0xff52 => "↑",
+        0xff53 => "→",
+        0xff54 => "↓",
+        0xff55 => "PgUp",
+        0xff56 => "PgDn",
+        0xff57 => "End",
+        0xff63 => "Ins",
+        0xffff => "Del",
+        0xffbe..=0xffc9 => return Some(format!("F{}", keysym - 0xffbe + 1)),
+        // Shift, Control, Caps Lock, Meta, Alt, Super, Hyper
+        0xffe1..=0xffee => return None,
+        _ => return None,
+    };
+    Some(label.to_string())
+}
@@ -3,3 +3,10 @@ pub mod screenshot;
 pub mod recording;
 pub mod content_provider;
 pub mod config;
+pub mod input;
+#[cfg(target_os = "linux")]
+pub mod x11;
+#[cfg(target_os = "linux")]
+pub mod portal;
+#[cfg(target_os = "linux")]
+pub mod backend;
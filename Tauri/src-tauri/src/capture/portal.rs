@@ -0,0 +1,466 @@
+//! xdg-desktop-portal Screenshot and ScreenCast clients for Wayland sessions
+use crate::error::CaptureError;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::time::Duration;
+use zbus::blocking::{Connection, Proxy};
+use zbus::export::futures_core::Stream;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SCREENSHOT_INTERFACE: &str = "org.freedesktop.portal.Screenshot";
+const SCREENCAST_INTERFACE: &str = "org.freedesktop.portal.ScreenCast";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+
+/// How long a request may go unanswered, including time the user spends in a picker
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// ScreenCast source types (bitmask for `SelectSources`)
+pub const SOURCE_MONITOR: u32 = 1;
+pub const SOURCE_WINDOW: u32 = 2;
+
+/// ScreenCast cursor modes (bitmask for `SelectSources`)
+pub const CURSOR_HIDDEN: u32 = 1;
+pub const CURSOR_EMBEDDED: u32 = 2;
+
+pub(crate) fn portal_error(err: impl std::fmt::Display) -> CaptureError {
+    CaptureError::CaptureFailed(format!("Desktop portal: {err}"))
+}
+
+/// A PipeWire stream negotiated through the ScreenCast portal
+#[derive(Debug, Clone)]
+pub struct PortalStream {
+    pub node_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// An active ScreenCast session; closed on drop
+pub struct ScreenCastSession {
+    conn: Connection,
+    pub handle: OwnedObjectPath,
+    pub streams: Vec<PortalStream>,
+}
+
+impl ScreenCastSession {
+    /// Open a PipeWire remote restricted to this session's streams
+    pub fn open_pipewire_remote(&self) -> Result<std::os::fd::OwnedFd, CaptureError> {
+        let proxy = Proxy::new(&self.conn, PORTAL_DESTINATION, PORTAL_PATH, SCREENCAST_INTERFACE)
+            .map_err(portal_error)?;
+        let options: HashMap<&str, Value> = HashMap::new();
+        let fd: zbus::zvariant::OwnedFd = proxy
+            .call("OpenPipeWireRemote", &(&self.handle, options))
+            .map_err(portal_error)?;
+        Ok(fd.into())
+    }
+}
+
+impl Drop for ScreenCastSession {
+    fn drop(&mut self) {
+        let closed = Proxy::new(&self.conn, PORTAL_DESTINATION, &self.handle, SESSION_INTERFACE)
+            .and_then(|p| p.call::<_, _, ()>("Close", &()));
+        if let Err(e) = closed {
+            log::warn!("Failed to close ScreenCast session: {}", e);
+        }
+    }
+}
+
+/// Client for the desktop portal on a D-Bus session bus
+pub struct Portal {
+    conn: Connection,
+    timeout: Duration,
+}
+
+impl Portal {
+    /// Connect to the user's session bus (`$DBUS_SESSION_BUS_ADDRESS`)
+    pub fn session() -> Result<Self, CaptureError> {
+        Ok(Self::with_connection(Connection::session().map_err(portal_error)?))
+    }
+
+    /// Use an existing connection, e.g. a private bus hosting a mock portal
+    pub fn with_connection(conn: Connection) -> Self {
+        Self { conn, timeout: RESPONSE_TIMEOUT }
+    }
+
+    /// Give up on requests the portal hasn't answered after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn proxy(&self, interface: &'static str) -> Result<Proxy<'static>, CaptureError> {
+        Proxy::new(&self.conn, PORTAL_DESTINATION, PORTAL_PATH, interface).map_err(portal_error)
+    }
+
+    /// Interface version, which doubles as an availability probe
+    pub fn version(&self, interface: &'static str) -> Result<u32, CaptureError> {
+        self.proxy(interface)?.get_property("version").map_err(portal_error)
+    }
+
+    pub fn screenshot_available(&self) -> bool {
+        self.version(SCREENSHOT_INTERFACE).is_ok()
+    }
+
+    pub fn screencast_available(&self) -> bool {
+        self.version(SCREENCAST_INTERFACE).is_ok()
+    }
+
+    /// Take a screenshot and return the path of the image the portal wrote.
+    /// `interactive` lets the user pick a window or region in the portal's own UI.
+    pub fn screenshot(&self, interactive: bool) -> Result<std::path::PathBuf, CaptureError> {
+        let token = new_token();
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        options.insert("interactive", Value::from(interactive));
+
+        let results = self.request(SCREENSHOT_INTERFACE, "Screenshot", &token, &("", options))?;
+        let uri: String = results
+            .get("uri")
+            .and_then(|v| String::try_from(v.clone()).ok())
+            .ok_or_else(|| portal_error("Screenshot response had no uri"))?;
+        let path = uri
+            .strip_prefix("file://")
+            .ok_or_else(|| portal_error(format!("Unsupported screenshot uri: {uri}")))?;
+        Ok(std::path::PathBuf::from(percent_decode(path)))
+    }
+
+    /// Negotiate a ScreenCast session for a single monitor or window.
+    /// The portal shows its own source picker; the chosen stream is returned.
+    pub fn screencast(&self, source_types: u32, show_cursor: bool) -> Result<ScreenCastSession, CaptureError> {
+        // CreateSession
+        let token = new_token();
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        let session_token = new_token();
+        options.insert("session_handle_token", Value::from(session_token.as_str()));
+        let results = self.request(SCREENCAST_INTERFACE, "CreateSession", &token, &(options,))?;
+        let handle: String = results
+            .get("session_handle")
+            .and_then(|v| String::try_from(v.clone()).ok())
+            .ok_or_else(|| portal_error("CreateSession response had no session_handle"))?;
+        let handle = OwnedObjectPath::try_from(handle).map_err(portal_error)?;
+
+        // From here on the session must be closed if anything fails
+        let mut session = ScreenCastSession {
+            conn: self.conn.clone(),
+            handle,
+            streams: Vec::new(),
+        };
+
+        // SelectSources
+        let token = new_token();
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        options.insert("types", Value::from(source_types));
+        options.insert("multiple", Value::from(false));
+        options.insert(
+            "cursor_mode",
+            Value::from(if show_cursor { CURSOR_EMBEDDED } else { CURSOR_HIDDEN }),
+        );
+        self.request(SCREENCAST_INTERFACE, "SelectSources", &token, &(&session.handle, options))?;
+
+        // Start
+        let token = new_token();
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        let results = self.request(SCREENCAST_INTERFACE, "Start", &token, &(&session.handle, "", options))?;
+        let streams: Vec<(u32, HashMap<String, OwnedValue>)> = results
+            .get("streams")
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| portal_error("Start response had no streams"))?;
+
+        session.streams = streams
+            .into_iter()
+            .map(|(node_id, props)| {
+                let (width, height) = props
+                    .get("size")
+                    .and_then(|v| <(i32, i32)>::try_from(v.try_clone().ok()?).ok())
+                    .unwrap_or((0, 0));
+                let (x, y) = props
+                    .get("position")
+                    .and_then(|v| <(i32, i32)>::try_from(v.try_clone().ok()?).ok())
+                    .unwrap_or((0, 0));
+                PortalStream {
+                    node_id,
+                    width: width.max(0) as u32,
+                    height: height.max(0) as u32,
+                    x,
+                    y,
+                }
+            })
+            .collect();
+
+        if session.streams.is_empty() {
+            return Err(portal_error("No stream was selected"));
+        }
+        Ok(session)
+    }
+
+    /// Call a portal method that answers through an `org.freedesktop.portal.Request`
+    /// object and wait for its `Response` signal, closing the request if none comes
+    /// within the timeout.
+    fn request<B>(
+        &self,
+        interface: &'static str,
+        method: &str,
+        token: &str,
+        body: &B,
+    ) -> Result<HashMap<String, OwnedValue>, CaptureError>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        // Subscribe before calling so a fast portal can't answer before we listen
+        let sender = self
+            .conn
+            .unique_name()
+            .ok_or_else(|| portal_error("Connection has no unique name"))?
+            .trim_start_matches(':')
+            .replace('.', "_");
+        let request_path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+        let request = Proxy::new(&self.conn, PORTAL_DESTINATION, request_path, REQUEST_INTERFACE)
+            .map_err(portal_error)?;
+        let portal = self.proxy(interface)?;
+
+        // The blocking signal iterator can't time out, so wait on the async stream
+        let message = tauri::async_runtime::block_on(async {
+            let mut responses = request.inner().receive_signal("Response").await.map_err(portal_error)?;
+            let _handle: OwnedObjectPath = portal.inner().call(method, body).await.map_err(portal_error)?;
+            let next = std::future::poll_fn(|cx| Pin::new(&mut responses).poll_next(cx));
+            match tokio::time::timeout(self.timeout, next).await {
+                Ok(message) => message.ok_or_else(|| portal_error(format!("{method}: no response"))),
+                Err(_) => {
+                    // Dismisses any dialog the portal still shows for this request
+                    let _ = request.inner().call::<_, _, ()>("Close", &()).await;
+                    Err(portal_error(format!("{method}: no response after {}s", self.timeout.as_secs())))
+                }
+            }
+        })?;
+        let (response, results): (u32, HashMap<String, OwnedValue>) =
+            message.body().deserialize().map_err(portal_error)?;
+
+        match response {
+            0 => Ok(results),
+            1 => Err(CaptureError::PermissionDenied(format!("{method} was cancelled by the user"))),
+            _ => Err(portal_error(format!("{method} failed"))),
+        }
+    }
+}
+
+fn new_token() -> String {
+    format!("screencapture_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Decode `%XX` escapes in a file URI path
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// These serve a mock portal on a private bus: `cargo test -- --ignored portal`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::message::Header;
+
+    /// A private `dbus-daemon`, killed when dropped
+    struct Bus {
+        child: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Self {
+            let mut child = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon is not installed");
+            let mut address = String::new();
+            BufReader::new(child.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            Self { child, address: address.trim().to_string() }
+        }
+
+        fn connect(&self) -> Connection {
+            zbus::blocking::connection::Builder::address(self.address.as_str()).unwrap().build().unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// Answer the request the caller opened with `handle_token`, as the real portal does
+    async fn respond(
+        conn: &zbus::Connection,
+        header: &Header<'_>,
+        options: &HashMap<String, OwnedValue>,
+        results: HashMap<&str, Value<'_>>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        let sender = header.sender().unwrap().trim_start_matches(':').replace('.', "_");
+        let token = String::try_from(options["handle_token"].try_clone().unwrap()).unwrap();
+        let path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+        let no_destination = None::<zbus::names::BusName<'_>>;
+        conn.emit_signal(no_destination, path.as_str(), REQUEST_INTERFACE, "Response", &(0u32, results))
+            .await?;
+        Ok(OwnedObjectPath::try_from(path).unwrap())
+    }
+
+    struct MockScreenshot {
+        /// Never answer, like a portal stuck behind a dialog nobody sees
+        silent: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.portal.Screenshot")]
+    impl MockScreenshot {
+        #[zbus(property)]
+        fn version(&self) -> u32 {
+            2
+        }
+
+        async fn screenshot(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: Header<'_>,
+            _parent_window: &str,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            if self.silent {
+                return Ok(OwnedObjectPath::try_from(format!("{PORTAL_PATH}/request/silent")).unwrap());
+            }
+            let uri = Value::from("file:///tmp/Screenshot%20from%20mock.png");
+            respond(conn, &header, &options, HashMap::from([("uri", uri)])).await
+        }
+    }
+
+    struct MockScreenCast;
+
+    #[zbus::interface(name = "org.freedesktop.portal.ScreenCast")]
+    impl MockScreenCast {
+        #[zbus(property)]
+        fn version(&self) -> u32 {
+            4
+        }
+
+        async fn create_session(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: Header<'_>,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let handle = Value::from(format!("{PORTAL_PATH}/session/mock/1"));
+            respond(conn, &header, &options, HashMap::from([("session_handle", handle)])).await
+        }
+
+        async fn select_sources(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: Header<'_>,
+            _session: OwnedObjectPath,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            assert_eq!(u32::try_from(&options["types"]).unwrap(), SOURCE_WINDOW);
+            assert_eq!(u32::try_from(&options["cursor_mode"]).unwrap(), CURSOR_EMBEDDED);
+            respond(conn, &header, &options, HashMap::new()).await
+        }
+
+        async fn start(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: Header<'_>,
+            _session: OwnedObjectPath,
+            _parent_window: &str,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let props = HashMap::from([
+                ("size".to_string(), Value::from((1280i32, 720i32))),
+                ("position".to_string(), Value::from((1920i32, 0i32))),
+            ]);
+            let streams = Value::from(vec![(42u32, props)]);
+            respond(conn, &header, &options, HashMap::from([("streams", streams)])).await
+        }
+    }
+
+    /// Serve the mock portal on `bus` under the real portal's name
+    fn serve(bus: &Bus, screenshot: MockScreenshot) -> Connection {
+        zbus::blocking::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(PORTAL_DESTINATION)
+            .unwrap()
+            .serve_at(PORTAL_PATH, screenshot)
+            .unwrap()
+            .serve_at(PORTAL_PATH, MockScreenCast)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn percent_decode_handles_escapes_and_stray_percents() {
+        assert_eq!(percent_decode("/tmp/Screenshot%20from%202024.png"), "/tmp/Screenshot from 2024.png");
+        assert_eq!(percent_decode("/tmp/100%.png"), "/tmp/100%.png");
+        assert_eq!(percent_decode("/tmp/%C3%A9t%C3%A9.png"), "/tmp/été.png");
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn portal_screenshot_returns_the_decoded_file_path() {
+        let bus = Bus::start();
+        let _mock = serve(&bus, MockScreenshot { silent: false });
+        let portal = Portal::with_connection(bus.connect());
+
+        assert!(portal.screenshot_available());
+        assert!(portal.screencast_available());
+        let path = portal.screenshot(false).unwrap();
+        assert_eq!(path, std::path::PathBuf::from("/tmp/Screenshot from mock.png"));
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn portal_screencast_negotiates_a_stream() {
+        let bus = Bus::start();
+        let _mock = serve(&bus, MockScreenshot { silent: false });
+        let portal = Portal::with_connection(bus.connect());
+
+        let session = portal.screencast(SOURCE_WINDOW, true).unwrap();
+        assert_eq!(session.handle.as_str(), "/org/freedesktop/portal/desktop/session/mock/1");
+        let stream = &session.streams[0];
+        assert_eq!((stream.node_id, stream.width, stream.height, stream.x, stream.y), (42, 1280, 720, 1920, 0));
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn portal_request_times_out_without_a_response() {
+        let bus = Bus::start();
+        let _mock = serve(&bus, MockScreenshot { silent: true });
+        let portal = Portal::with_connection(bus.connect()).with_timeout(Duration::from_millis(300));
+
+        let started = std::time::Instant::now();
+        let error = portal.screenshot(false).unwrap_err();
+        assert!(error.to_string().contains("no response"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
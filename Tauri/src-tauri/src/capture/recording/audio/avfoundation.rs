@@ -0,0 +1,75 @@
+//! AVFoundation audio devices via ffmpeg's `avfoundation` input.
+//! macOS has no system-audio tap ffmpeg can open, so system audio needs a loopback
+//! device (e.g. BlackHole) chosen explicitly or detected by name.
+use super::{AudioDevice, AudioSourceKind};
+use crate::error::CaptureError;
+use std::process::Command;
+
+/// Name fragments of common loopback drivers
+const LOOPBACK_NAMES: &[&str] = &["blackhole", "loopback", "soundflower"];
+
+fn is_loopback(name: &str) -> bool {
+    let name = name.to_lowercase();
+    LOOPBACK_NAMES.iter().any(|l| name.contains(l))
+}
+
+pub fn list_devices() -> Result<Vec<AudioDevice>, CaptureError> {
+    let ffmpeg = crate::services::ffmpeg::ffmpeg_path()?;
+    // ffmpeg prints the device list to stderr and then fails on the empty input
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-f", "avfoundation", "-list_devices", "true", "-i", ""])
+        .output()?;
+    let log = String::from_utf8_lossy(&output.stderr);
+
+    let mut devices = Vec::new();
+    let mut in_audio = false;
+    for line in log.lines() {
+        if line.contains("AVFoundation audio devices") {
+            in_audio = true;
+            continue;
+        }
+        if line.contains("AVFoundation video devices") {
+            in_audio = false;
+            continue;
+        }
+        if !in_audio {
+            continue;
+        }
+        // "[AVFoundation indev @ 0x...] [0] MacBook Pro Microphone"
+        let Some((index, name)) = line.rsplit_once("] [").and_then(|(_, rest)| rest.split_once("] ")) else {
+            continue;
+        };
+        let kind = if is_loopback(name) { AudioSourceKind::System } else { AudioSourceKind::Microphone };
+        devices.push(AudioDevice {
+            id: index.to_string(),
+            name: name.trim().to_string(),
+            kind,
+            is_default: false,
+        });
+    }
+
+    if let Some(first_mic) = devices.iter_mut().find(|d| d.kind == AudioSourceKind::Microphone) {
+        first_mic.is_default = true;
+    }
+    Ok(devices)
+}
+
+pub fn default_device(kind: AudioSourceKind) -> Result<String, CaptureError> {
+    match kind {
+        AudioSourceKind::Microphone => Ok("default".into()),
+        AudioSourceKind::System => list_devices()?
+            .into_iter()
+            .find(|d| d.kind == AudioSourceKind::System)
+            .map(|d| d.id)
+            .ok_or_else(|| {
+                CaptureError::RecordingFailed(
+                    "System audio on macOS needs a loopback device such as BlackHole".into(),
+                )
+            }),
+    }
+}
+
+pub fn input_args(device: &str) -> Vec<String> {
+    // Audio-only avfoundation input: no video device before the colon
+    vec!["-f".into(), "avfoundation".into(), "-i".into(), format!(":{device}")]
+}
@@ -0,0 +1,329 @@
+//! Audio capture for recordings.
+//!
+//! Each source (microphone, system audio) runs its own `ffmpeg` capture process that
+//! emits raw PCM. A pump thread reads it, meters levels, drops samples while the
+//! recording is paused and forwards the rest to one of the encoder's audio inputs.
+//! Both the encoder and the pump count samples rather than wall-clock time, so audio
+//! stays on the same media timeline as the video frames.
+//!
+//! With `exclude_app_audio`, system audio is rerouted on Linux so this app's own
+//! playback isn't captured. A macOS loopback device carries every app's sound mixed
+//! together, so there the flag can't be honoured and is only logged.
+use crate::capture::config::RecordingConfig;
+use crate::capture::recording::pipeline::MediaClock;
+use crate::error::CaptureError;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "macos")]
+mod avfoundation;
+#[cfg(target_os = "linux")]
+mod pulse;
+
+/// PCM format shared by the capture processes and the encoder inputs (s16le)
+pub const SAMPLE_RATE: u32 = 48_000;
+pub const CHANNELS: u32 = 2;
+const BYTES_PER_FRAME: usize = 2 * CHANNELS as usize;
+
+/// 20 ms of audio per read
+const CHUNK_BYTES: usize = (SAMPLE_RATE as usize / 50) * BYTES_PER_FRAME;
+/// Level meter update rate
+const LEVEL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AudioSourceKind {
+    Microphone,
+    System,
+}
+
+impl AudioSourceKind {
+    /// Track title written into the output
+    pub fn title(&self) -> &'static str {
+        match self {
+            AudioSourceKind::Microphone => "Microphone",
+            AudioSourceKind::System => "System Audio",
+        }
+    }
+}
+
+/// A capturable audio device
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    /// `System` for output monitors/loopbacks, `Microphone` for inputs
+    pub kind: AudioSourceKind,
+    pub is_default: bool,
+}
+
+/// Live level of one source, linear 0.0 ..= 1.0
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AudioLevel {
+    pub source: AudioSourceKind,
+    pub peak: f32,
+    pub rms: f32,
+}
+
+pub type LevelListener = Arc<dyn Fn(AudioLevel) + Send + Sync>;
+
+/// Devices available for recording on this system
+pub fn list_devices() -> Result<Vec<AudioDevice>, CaptureError> {
+    #[cfg(target_os = "linux")]
+    {
+        pulse::list_devices()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        avfoundation::list_devices()
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Ok(vec![])
+    }
+}
+
+/// The sources `config` asks for, each with the device to capture from.
+///
+/// A source left on its default device is skipped with a warning if no device is
+/// available (e.g. no PulseAudio server); an explicitly chosen device is used as is.
+pub fn requested_sources(config: &RecordingConfig) -> Vec<(AudioSourceKind, String)> {
+    let requested = [
+        (config.include_microphone, AudioSourceKind::Microphone, &config.microphone_device),
+        (config.include_system_audio, AudioSourceKind::System, &config.system_audio_device),
+    ];
+
+    let mut sources = Vec::new();
+    for (enabled, kind, device) in requested {
+        if !enabled {
+            continue;
+        }
+        match device {
+            Some(device) => sources.push((kind, device.clone())),
+            None => match default_device(kind) {
+                Ok(device) => sources.push((kind, device)),
+                Err(e) => log::warn!("Recording without {}: {}", kind.title(), e),
+            },
+        }
+    }
+    sources
+}
+
+fn default_device(kind: AudioSourceKind) -> Result<String, CaptureError> {
+    #[cfg(target_os = "linux")]
+    {
+        pulse::default_device(kind)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        avfoundation::default_device(kind)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = kind;
+        Err(CaptureError::RecordingFailed("Audio capture is not supported on this platform".into()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+type Exclusion = pulse::SelfExclusion;
+#[cfg(not(target_os = "linux"))]
+type Exclusion = std::convert::Infallible;
+
+/// Start leaving this app's playback out of system-audio `device`. Returns the device
+/// to capture instead, and the guard that keeps the rerouting in place.
+fn exclude_self(device: &str) -> Result<(String, Option<Exclusion>), CaptureError> {
+    #[cfg(target_os = "linux")]
+    {
+        let exclusion = pulse::SelfExclusion::start(device)?;
+        let device = exclusion.as_ref().map_or_else(|| device.to_string(), |e| e.device());
+        Ok((device, exclusion))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        log::info!("System audio from {device} includes this app's own sound");
+        Ok((device.to_string(), None))
+    }
+}
+
+/// ffmpeg input arguments that open `device`
+fn input_args(device: &str) -> Vec<String> {
+    #[cfg(target_os = "linux")]
+    {
+        pulse::input_args(device)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        avfoundation::input_args(device)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        vec!["-i".into(), device.into()]
+    }
+}
+
+/// One running audio source feeding one encoder input
+pub struct AudioCapture {
+    kind: AudioSourceKind,
+    child: Child,
+    paused: Arc<AtomicBool>,
+    /// Sample frames handed to the encoder, lead-in included
+    written: Arc<AtomicU64>,
+    pump: Option<JoinHandle<()>>,
+    /// Dropped after the capture process, so rerouted playback is restored last
+    _exclusion: Option<Exclusion>,
+}
+
+impl AudioCapture {
+    /// `exclude_app` leaves this app's own playback out of a `System` source
+    pub fn start(
+        kind: AudioSourceKind,
+        device: &str,
+        exclude_app: bool,
+        sink: Box<dyn Write + Send>,
+        clock: MediaClock,
+        on_level: LevelListener,
+    ) -> Result<Self, CaptureError> {
+        let (device, exclusion) = if exclude_app && kind == AudioSourceKind::System {
+            exclude_self(device)?
+        } else {
+            (device.to_string(), None)
+        };
+        let ffmpeg = crate::services::ffmpeg::ffmpeg_path()?;
+        let mut child = Command::new(ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin"])
+            .args(input_args(&device))
+            .args(["-f", "s16le", "-acodec", "pcm_s16le"])
+            .args(["-ar", &SAMPLE_RATE.to_string(), "-ac", &CHANNELS.to_string()])
+            .arg("pipe:1")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| CaptureError::RecordingFailed(format!("Failed to start audio capture: {e}")))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| CaptureError::RecordingFailed("Audio capture has no stdout".into()))?;
+        let paused = Arc::new(AtomicBool::new(false));
+        let pump_paused = paused.clone();
+        let written = Arc::new(AtomicU64::new(0));
+        let pump_written = written.clone();
+
+        let pump = std::thread::Builder::new()
+            .name(format!("audio-{}", kind.title().to_lowercase().replace(' ', "-")))
+            .spawn(move || pump(kind, stdout, sink, clock, pump_paused, pump_written, on_level))?;
+
+        Ok(Self { kind, child, paused, written, pump: Some(pump), _exclusion: exclusion })
+    }
+
+    pub fn kind(&self) -> AudioSourceKind {
+        self.kind
+    }
+
+    /// Media time of the audio handed to the encoder; `None` until the first samples
+    pub fn media_seconds(&self) -> Option<f64> {
+        let frames = self.written.load(Ordering::Acquire);
+        (frames > 0).then(|| frames as f64 / SAMPLE_RATE as f64)
+    }
+
+    /// Drop captured samples instead of encoding them
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Release);
+    }
+
+    /// End capture. The encoder input sees end-of-stream once the pump drains.
+    pub fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(pump) = self.pump.take() {
+            let _ = pump.join();
+        }
+    }
+}
+
+fn pump(
+    kind: AudioSourceKind,
+    mut input: impl Read,
+    mut sink: Box<dyn Write + Send>,
+    clock: MediaClock,
+    paused: Arc<AtomicBool>,
+    written: Arc<AtomicU64>,
+    on_level: LevelListener,
+) {
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    let mut started = false;
+    let mut meter = LevelMeter::new(kind);
+
+    loop {
+        // Whole chunks only, so writes never split a sample frame
+        if input.read_exact(&mut buf).is_err() {
+            break;
+        }
+        let chunk = &buf[..];
+        if let Some(level) = meter.feed(chunk) {
+            on_level(level);
+        }
+        // Nothing to line up with until the first video frame, and nothing to keep while paused
+        if clock.frames() == 0 || paused.load(Ordering::Acquire) {
+            continue;
+        }
+
+        if !started {
+            // Capture starts a little after the first video frame; lead in with silence
+            // so the first sample lines up with the video timeline
+            started = true;
+            let lead_frames = (clock.elapsed_seconds() * SAMPLE_RATE as f64) as usize;
+            if sink.write_all(&vec![0u8; lead_frames * BYTES_PER_FRAME]).is_err() {
+                break;
+            }
+            written.fetch_add(lead_frames as u64, Ordering::Release);
+        }
+        if sink.write_all(chunk).is_err() {
+            // The encoder has gone away; nothing left to feed
+            break;
+        }
+        written.fetch_add((chunk.len() / BYTES_PER_FRAME) as u64, Ordering::Release);
+    }
+}
+
+/// Peak and RMS over `LEVEL_INTERVAL` windows
+struct LevelMeter {
+    kind: AudioSourceKind,
+    window_start: Instant,
+    peak: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl LevelMeter {
+    fn new(kind: AudioSourceKind) -> Self {
+        Self { kind, window_start: Instant::now(), peak: 0.0, sum_squares: 0.0, samples: 0 }
+    }
+
+    fn feed(&mut self, pcm: &[u8]) -> Option<AudioLevel> {
+        for sample in pcm.chunks_exact(2) {
+            let value = i16::from_le_bytes([sample[0], sample[1]]) as f32 / i16::MAX as f32;
+            self.peak = self.peak.max(value.abs());
+            self.sum_squares += (value * value) as f64;
+            self.samples += 1;
+        }
+
+        if self.window_start.elapsed() < LEVEL_INTERVAL || self.samples == 0 {
+            return None;
+        }
+        let level = AudioLevel {
+            source: self.kind,
+            peak: self.peak.min(1.0),
+            rms: ((self.sum_squares / self.samples as f64).sqrt() as f32).min(1.0),
+        };
+        *self = Self::new(self.kind);
+        Some(level)
+    }
+}
@@ -0,0 +1,344 @@
+//! PulseAudio (and PipeWire's Pulse server) devices via `pactl`.
+//! System audio is captured from the default sink's `.monitor` source, or from a
+//! private null sink when this app's own sound is left out (see `SelfExclusion`).
+use super::{AudioDevice, AudioSourceKind};
+use crate::error::CaptureError;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+fn pactl(args: &[&str]) -> Result<String, CaptureError> {
+    let pactl = crate::services::ffmpeg::find_binary("pactl", "SCREENCAPTURE_PACTL")
+        .ok_or_else(|| CaptureError::RecordingFailed("pactl not found; is PulseAudio or PipeWire running?".into()))?;
+    let output = Command::new(pactl).args(args).env("LC_ALL", "C").output()?;
+    if !output.status.success() {
+        return Err(CaptureError::RecordingFailed(format!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `Default Sink` / `Default Source` from `pactl info`
+fn server_default(key: &str) -> Result<String, CaptureError> {
+    pactl(&["info"])?
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':').map(|v| v.trim().to_string()))
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| CaptureError::RecordingFailed(format!("No {} configured", key.to_lowercase())))
+}
+
+pub fn default_device(kind: AudioSourceKind) -> Result<String, CaptureError> {
+    match kind {
+        AudioSourceKind::System => Ok(format!("{}.monitor", server_default("Default Sink")?)),
+        // ffmpeg's pulse input understands "default" even without pactl
+        AudioSourceKind::Microphone => Ok(server_default("Default Source").unwrap_or_else(|_| "default".into())),
+    }
+}
+
+pub fn list_devices() -> Result<Vec<AudioDevice>, CaptureError> {
+    let default_source = server_default("Default Source").ok();
+    let default_monitor = server_default("Default Sink").ok().map(|s| format!("{s}.monitor"));
+
+    // Blocks of "Source #N" followed by indented "Key: value" lines
+    let mut devices = Vec::new();
+    let mut current: Option<(String, String, bool)> = None;
+    let flush = |entry: Option<(String, String, bool)>, devices: &mut Vec<AudioDevice>| {
+        if let Some((id, name, is_monitor)) = entry {
+            let kind = if is_monitor { AudioSourceKind::System } else { AudioSourceKind::Microphone };
+            let is_default = if is_monitor {
+                Some(&id) == default_monitor.as_ref()
+            } else {
+                Some(&id) == default_source.as_ref()
+            };
+            devices.push(AudioDevice { id, name, kind, is_default });
+        }
+    };
+
+    for line in pactl(&["list", "sources"])?.lines() {
+        let line = line.trim();
+        if line.starts_with("Source #") {
+            flush(current.take(), &mut devices);
+            current = Some((String::new(), String::new(), false));
+        } else if let Some((id, name, is_monitor)) = current.as_mut() {
+            if let Some(v) = line.strip_prefix("Name:") {
+                *id = v.trim().to_string();
+                *is_monitor |= id.ends_with(".monitor");
+            } else if let Some(v) = line.strip_prefix("Description:") {
+                *name = v.trim().to_string();
+            } else if let Some(v) = line.strip_prefix("Monitor of Sink:") {
+                *is_monitor |= v.trim() != "n/a";
+            }
+        }
+    }
+    flush(current.take(), &mut devices);
+
+    devices.retain(|d| !d.id.is_empty());
+    Ok(devices)
+}
+
+pub fn input_args(device: &str) -> Vec<String> {
+    // Small fragments keep capture latency (and so A/V offset) low
+    ["-f", "pulse", "-fragment_size", "3840", "-i", device]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Loads a module and returns its index, as printed by `pactl load-module`
+fn load_module(args: &[&str]) -> Result<String, CaptureError> {
+    let mut command = vec!["load-module"];
+    command.extend_from_slice(args);
+    Ok(pactl(&command)?.trim().to_string())
+}
+
+/// Index of the sink called `name`, from `pactl list short sinks`
+fn sink_index(name: &str) -> Result<String, CaptureError> {
+    pactl(&["list", "short", "sinks"])?
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.split('\t');
+            let index = fields.next()?;
+            (fields.next()? == name).then(|| index.to_string())
+        })
+        .ok_or_else(|| CaptureError::RecordingFailed(format!("No sink named {name}")))
+}
+
+/// A playback stream from `pactl list sink-inputs`
+#[derive(Debug, Default, PartialEq)]
+struct SinkInput {
+    index: String,
+    /// Index of the sink it plays to
+    sink: String,
+    owner_module: Option<String>,
+    process_id: Option<u32>,
+    media_name: Option<String>,
+}
+
+fn parse_sink_inputs(listing: &str) -> Vec<SinkInput> {
+    // Blocks of "Sink Input #N" followed by indented "Key: value" lines and
+    // `key = "value"` properties
+    let mut inputs: Vec<SinkInput> = Vec::new();
+    for line in listing.lines().map(str::trim) {
+        if let Some(index) = line.strip_prefix("Sink Input #") {
+            inputs.push(SinkInput { index: index.trim().to_string(), ..Default::default() });
+            continue;
+        }
+        let Some(input) = inputs.last_mut() else {
+            continue;
+        };
+        let property = |key: &str| {
+            let value = line.strip_prefix(key)?.trim_start().strip_prefix('=')?.trim();
+            Some(value.trim_matches('"').to_string())
+        };
+        if let Some(v) = line.strip_prefix("Sink:") {
+            input.sink = v.trim().to_string();
+        } else if let Some(v) = line.strip_prefix("Owner Module:") {
+            input.owner_module = Some(v.trim().to_string()).filter(|m| m != "n/a");
+        } else if let Some(v) = property("application.process.id") {
+            input.process_id = v.parse().ok();
+        } else if let Some(v) = property("media.name") {
+            input.media_name = Some(v);
+        }
+    }
+    inputs
+}
+
+/// Parent of `pid`: the second field after the parenthesised name in `/proc/<pid>/stat`
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Whether `pid` is this process or runs under it, like the webview's helper processes
+fn is_own_process(mut pid: u32) -> bool {
+    let own = std::process::id();
+    while pid > 1 {
+        if pid == own {
+            return true;
+        }
+        match parent_pid(pid) {
+            Some(parent) if parent != pid => pid = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// Null sinks and loopbacks are named after the process that loaded them
+const EXCLUSION_PREFIX: &str = "screencapture_";
+/// How often streams that started after the recording are moved over
+const MOVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Leaves this app's own playback out of a sink's monitor while alive.
+///
+/// Every other stream on the sink moves to a private null sink, whose monitor is
+/// captured instead and looped back to the sink so it is still heard. Our streams
+/// stay on the sink: audible, but not recorded. Dropping it moves everything back.
+pub struct SelfExclusion {
+    sink: String,
+    capture_sink: String,
+    /// Loaded modules, in load order
+    modules: Vec<String>,
+    stop: Arc<AtomicBool>,
+    mover: Option<JoinHandle<()>>,
+}
+
+impl SelfExclusion {
+    /// `None` when `device` isn't a sink's monitor, so there is nothing to reroute
+    pub fn start(device: &str) -> Result<Option<Self>, CaptureError> {
+        let Some(sink) = device.strip_suffix(".monitor") else {
+            return Ok(None);
+        };
+        remove_stale_modules();
+        let capture_sink = format!("{EXCLUSION_PREFIX}{}", std::process::id());
+        let loopback_name = format!("{capture_sink}_loopback");
+        let mut exclusion = Self {
+            sink: sink.to_string(),
+            capture_sink: capture_sink.clone(),
+            modules: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            mover: None,
+        };
+        // From here on, dropping `exclusion` undoes whatever was set up
+        exclusion.modules.push(load_module(&[
+            "module-null-sink",
+            &format!("sink_name={capture_sink}"),
+            "sink_properties=device.description=ScreenCapture",
+        ])?);
+        let loopback = load_module(&[
+            "module-loopback",
+            &format!("source={capture_sink}.monitor"),
+            &format!("sink={sink}"),
+            "latency_msec=20",
+            "source_dont_move=true",
+            "sink_dont_move=true",
+            &format!("sink_input_properties=media.name={loopback_name}"),
+        ])?;
+        exclusion.modules.push(loopback.clone());
+
+        let sink_index = sink_index(sink)?;
+        let is_excluded = move |input: &SinkInput| {
+            input.owner_module.as_ref() == Some(&loopback)
+                || input.media_name.as_ref() == Some(&loopback_name)
+                || input.process_id.is_some_and(is_own_process)
+        };
+        let move_others = move || -> Result<(), CaptureError> {
+            for input in parse_sink_inputs(&pactl(&["list", "sink-inputs"])?) {
+                if input.sink == sink_index && !is_excluded(&input) {
+                    if let Err(e) = pactl(&["move-sink-input", &input.index, &capture_sink]) {
+                        log::debug!("Could not reroute sink input {}: {}", input.index, e);
+                    }
+                }
+            }
+            Ok(())
+        };
+        move_others()?;
+        let stop = exclusion.stop.clone();
+        exclusion.mover = Some(std::thread::Builder::new().name("audio-exclusion".into()).spawn(move || {
+            while !stop.load(Ordering::Acquire) {
+                std::thread::sleep(MOVE_INTERVAL);
+                if let Err(e) = move_others() {
+                    log::debug!("Could not reroute new playback: {}", e);
+                }
+            }
+        })?);
+        Ok(Some(exclusion))
+    }
+
+    /// The device to capture instead of the sink's own monitor
+    pub fn device(&self) -> String {
+        format!("{}.monitor", self.capture_sink)
+    }
+}
+
+impl Drop for SelfExclusion {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(mover) = self.mover.take() {
+            let _ = mover.join();
+        }
+        // Back to the sink they came from, rather than wherever unloading would send them
+        if let (Ok(index), Ok(listing)) = (sink_index(&self.capture_sink), pactl(&["list", "sink-inputs"])) {
+            for input in parse_sink_inputs(&listing).into_iter().filter(|i| i.sink == index) {
+                let _ = pactl(&["move-sink-input", &input.index, &self.sink]);
+            }
+        }
+        for module in self.modules.iter().rev() {
+            if let Err(e) = pactl(&["unload-module", module]) {
+                log::warn!("Could not unload audio module {}: {}", module, e);
+            }
+        }
+    }
+}
+
+/// Unload exclusion modules left loaded by a run that didn't exit cleanly
+fn remove_stale_modules() {
+    let Ok(modules) = pactl(&["list", "short", "modules"]) else {
+        return;
+    };
+    for line in modules.lines() {
+        let mut fields = line.split('\t');
+        let (Some(index), Some(_), Some(args)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let owner = args
+            .split(EXCLUSION_PREFIX)
+            .nth(1)
+            .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+            .and_then(|pid| pid.parse::<u32>().ok());
+        if owner.is_some_and(|pid| pid != std::process::id() && !Path::new(&format!("/proc/{pid}")).exists()) {
+            log::info!("Unloading audio module {} left by an earlier run", index);
+            let _ = pactl(&["unload-module", index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sink_inputs() {
+        let listing = "Sink Input #41\n\
+            \tDriver: protocol-native.c\n\
+            \tOwner Module: n/a\n\
+            \tClient: 12\n\
+            \tSink: 0\n\
+            \tProperties:\n\
+            \t\tmedia.name = \"Playback\"\n\
+            \t\tapplication.process.id = \"4242\"\n\
+            \n\
+            Sink Input #57\n\
+            \tOwner Module: 23\n\
+            \tSink: 1\n";
+        assert_eq!(
+            parse_sink_inputs(listing),
+            vec![
+                SinkInput {
+                    index: "41".into(),
+                    sink: "0".into(),
+                    owner_module: None,
+                    process_id: Some(4242),
+                    media_name: Some("Playback".into()),
+                },
+                SinkInput { index: "57".into(), sink: "1".into(), owner_module: Some("23".into()), ..Default::default() },
+            ]
+        );
+    }
+
+    #[test]
+    fn children_count_as_own_process() {
+        assert!(is_own_process(std::process::id()));
+        let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        assert!(is_own_process(child.id()));
+        let _ = child.kill();
+        let _ = child.wait();
+        assert!(!is_own_process(1));
+    }
+}
@@ -0,0 +1,233 @@
+//! Recording session state machine.
+//!
+//! All state changes go through `RecordingCoordinator::transition`, which rejects
+//! moves the session lifecycle doesn't allow and notifies a listener on every change.
+use crate::capture::recording::RecordingSessionState;
+use crate::error::CaptureError;
+
+type StateListener = Box<dyn Fn(&RecordingSessionState) + Send + Sync>;
+
+/// Owns the current `RecordingSessionState` and enforces legal transitions
+pub struct RecordingCoordinator {
+    state: RecordingSessionState,
+    listener: Option<StateListener>,
+}
+
+impl Default for RecordingCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordingCoordinator {
+    pub fn new() -> Self {
+        Self { state: RecordingSessionState::Idle, listener: None }
+    }
+
+    /// Called with the new state after every successful transition
+    pub fn set_listener(&mut self, listener: impl Fn(&RecordingSessionState) + Send + Sync + 'static) {
+        self.listener = Some(Box::new(listener));
+    }
+
+    pub fn state(&self) -> &RecordingSessionState {
+        &self.state
+    }
+
+    /// Whether a session is between Starting and its terminal state
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.state,
+            RecordingSessionState::Starting
+                | RecordingSessionState::Recording { .. }
+                | RecordingSessionState::Paused { .. }
+                | RecordingSessionState::Stopping
+                | RecordingSessionState::ExportingGif
+        )
+    }
+
+    /// Update the elapsed time of a running recording. Returns false outside `Recording`,
+    /// so time spent paused is never counted.
+    /// This is progress, not a transition, so the listener isn't notified.
+    pub fn update_elapsed(&mut self, seconds: f64) -> bool {
+        match &mut self.state {
+            RecordingSessionState::Recording { elapsed_seconds } => {
+                *elapsed_seconds = elapsed_seconds.max(seconds);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the session lifecycle allows moving from `from` to `to`
+    pub fn can_transition(from: &RecordingSessionState, to: &RecordingSessionState) -> bool {
+        use RecordingSessionState::*;
+
+        matches!(
+            (from, to),
+            // A new session can begin from rest or after any terminal state
+            (Idle | Completed | Failed { .. } | Cancelled, Selecting | Starting)
+                | (Completed | Failed { .. } | Cancelled, Idle)
+                | (Selecting, Starting | Idle | Cancelled)
+                | (Starting, Recording { .. } | Failed { .. } | Cancelled)
+                | (Recording { .. }, Paused { .. } | Stopping | Failed { .. } | Cancelled)
+                | (Paused { .. }, Recording { .. } | Stopping | Failed { .. } | Cancelled)
+                | (Stopping, Completed | ExportingGif | Failed { .. })
+                | (ExportingGif, Completed | Failed { .. } | Cancelled)
+        )
+    }
+
+    /// Move to `to`, or return `InvalidTransition` and leave the state untouched
+    pub fn transition(&mut self, to: RecordingSessionState) -> Result<(), CaptureError> {
+        if !Self::can_transition(&self.state, &to) {
+            return Err(CaptureError::InvalidTransition {
+                from: self.state.name(),
+                to: to.name(),
+            });
+        }
+        self.state = to;
+        if let Some(listener) = &self.listener {
+            listener(&self.state);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use RecordingSessionState::*;
+
+    fn all_states() -> Vec<RecordingSessionState> {
+        vec![
+            Idle,
+            Selecting,
+            Starting,
+            Recording { elapsed_seconds: 0.0 },
+            Paused { elapsed_seconds: 0.0 },
+            Stopping,
+            ExportingGif,
+            Completed,
+            Failed { message: "boom".into() },
+            Cancelled,
+        ]
+    }
+
+    /// The full table of legal moves, by state name
+    const LEGAL: &[(&str, &str)] = &[
+        ("idle", "selecting"),
+        ("idle", "starting"),
+        ("selecting", "starting"),
+        ("selecting", "idle"),
+        ("selecting", "cancelled"),
+        ("starting", "recording"),
+        ("starting", "failed"),
+        ("starting", "cancelled"),
+        ("recording", "paused"),
+        ("recording", "stopping"),
+        ("recording", "failed"),
+        ("recording", "cancelled"),
+        ("paused", "recording"),
+        ("paused", "stopping"),
+        ("paused", "failed"),
+        ("paused", "cancelled"),
+        ("stopping", "completed"),
+        ("stopping", "exportingGif"),
+        ("stopping", "failed"),
+        ("exportingGif", "completed"),
+        ("exportingGif", "failed"),
+        ("exportingGif", "cancelled"),
+        ("completed", "idle"),
+        ("completed", "selecting"),
+        ("completed", "starting"),
+        ("failed", "idle"),
+        ("failed", "selecting"),
+        ("failed", "starting"),
+        ("cancelled", "idle"),
+        ("cancelled", "selecting"),
+        ("cancelled", "starting"),
+    ];
+
+    fn coordinator_in(state: RecordingSessionState) -> RecordingCoordinator {
+        RecordingCoordinator { state, listener: None }
+    }
+
+    #[test]
+    fn every_pair_matches_the_transition_table() {
+        for from in all_states() {
+            for to in all_states() {
+                let expected = LEGAL.contains(&(from.name(), to.name()));
+                let mut coordinator = coordinator_in(from.clone());
+                let result = coordinator.transition(to.clone());
+
+                assert_eq!(result.is_ok(), expected, "{} -> {}", from.name(), to.name());
+                let current = coordinator.state().name();
+                if expected {
+                    assert_eq!(current, to.name());
+                } else {
+                    assert_eq!(current, from.name(), "state changed on rejected transition");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_transition_reports_both_states() {
+        let mut coordinator = RecordingCoordinator::new();
+        match coordinator.transition(Cancelled) {
+            Err(CaptureError::InvalidTransition { from, to }) => {
+                assert_eq!(from, "idle");
+                assert_eq!(to, "cancelled");
+            }
+            other => panic!("expected InvalidTransition, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn listener_sees_every_successful_transition_only() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let mut coordinator = RecordingCoordinator::new();
+        coordinator.set_listener(move |s| sink.lock().unwrap().push(s.name()));
+
+        coordinator.transition(Starting).unwrap();
+        coordinator.transition(Recording { elapsed_seconds: 0.0 }).unwrap();
+        assert!(coordinator.transition(Completed).is_err());
+        coordinator.transition(Stopping).unwrap();
+        coordinator.transition(ExportingGif).unwrap();
+        coordinator.transition(Completed).unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["starting", "recording", "stopping", "exportingGif", "completed"]
+        );
+    }
+
+    #[test]
+    fn elapsed_updates_only_while_recording_and_never_decrease() {
+        let mut coordinator = RecordingCoordinator::new();
+        assert!(!coordinator.update_elapsed(1.0));
+
+        coordinator.transition(Starting).unwrap();
+        coordinator.transition(Recording { elapsed_seconds: 0.0 }).unwrap();
+        assert!(coordinator.update_elapsed(2.5));
+        assert!(coordinator.update_elapsed(1.0));
+        assert!(matches!(coordinator.state(), Recording { elapsed_seconds } if *elapsed_seconds == 2.5));
+
+        coordinator.transition(Paused { elapsed_seconds: 2.5 }).unwrap();
+        assert!(!coordinator.update_elapsed(3.0));
+        coordinator.transition(Recording { elapsed_seconds: 2.5 }).unwrap();
+        assert!(coordinator.update_elapsed(3.0));
+
+        coordinator.transition(Stopping).unwrap();
+        assert!(!coordinator.update_elapsed(4.0));
+    }
+
+    #[test]
+    fn active_states() {
+        for state in all_states() {
+            let active = matches!(state.name(), "starting" | "recording" | "paused" | "stopping" | "exportingGif");
+            assert_eq!(coordinator_in(state.clone()).is_active(), active, "{}", state.name());
+        }
+    }
+}
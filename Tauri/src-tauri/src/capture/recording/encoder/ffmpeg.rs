@@ -0,0 +1,270 @@
+//! Encoder that pipes raw BGRA frames into a local `ffmpeg` process
+use super::{Encoder, EncoderProgress, EncoderSettings};
+use crate::capture::config::AudioMode;
+use crate::capture::recording::audio;
+use crate::capture::recording::source::Frame;
+use crate::error::CaptureError;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+pub struct FfmpegEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: Option<JoinHandle<String>>,
+    output: PathBuf,
+    frame_len: usize,
+    audio_inputs: Vec<Box<dyn Write + Send>>,
+    /// Updated from ffmpeg's `-progress` reports, about twice a second
+    progress: Arc<Mutex<Option<EncoderProgress>>>,
+}
+
+impl FfmpegEncoder {
+    pub fn spawn(settings: &EncoderSettings) -> Result<Self, CaptureError> {
+        let ffmpeg = crate::services::ffmpeg::ffmpeg_path()?;
+        // Segments are one GOP long, so every segment starts on a keyframe
+        let keyframe_seconds = match settings.segments {
+            Some(segments) => segments.seconds as f64,
+            None => settings.quality.keyframe_interval_seconds(),
+        };
+        let format = super::format::resolve(
+            settings.format,
+            !settings.audio.is_empty(),
+            &settings.quality,
+            settings.fps,
+            keyframe_seconds,
+        )?;
+        let (out_width, out_height) = settings.quality.output_size(settings.width, settings.height);
+        let video_filter = if (out_width, out_height) == (settings.width, settings.height) {
+            "null".to_string()
+        } else if (out_width, out_height) == (settings.width / 2 * 2, settings.height / 2 * 2) {
+            // 4:2:0 needs even dimensions
+            "crop=trunc(iw/2)*2:trunc(ih/2)*2".to_string()
+        } else {
+            format!("scale={out_width}:{out_height}:flags=bicubic")
+        };
+
+        let mut command = Command::new(ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostats", "-progress", "pipe:1", "-y"])
+            // Input: raw frames on stdin at a constant rate
+            .args(["-f", "rawvideo", "-pix_fmt", "bgra"])
+            .args(["-video_size", &format!("{}x{}", settings.width, settings.height)])
+            .args(["-framerate", &settings.fps.to_string()])
+            .args(["-i", "pipe:0"]);
+
+        // Audio: raw PCM on fds 3, 4, ... one per source
+        for (i, _) in settings.audio.iter().enumerate() {
+            command
+                .args(["-f", "s16le", "-ar", &audio::SAMPLE_RATE.to_string()])
+                .args(["-ac", &audio::CHANNELS.to_string(), "-thread_queue_size", "1024"])
+                .args(["-i", &format!("pipe:{}", AUDIO_FD_BASE + i as i32)]);
+        }
+        command.args(["-map", "0:v"]);
+        match (settings.audio.len(), settings.audio_mode) {
+            (0, _) => {}
+            (1, _) | (_, AudioMode::SeparateTracks) => {
+                for (i, kind) in settings.audio.iter().enumerate() {
+                    command
+                        .args(["-map", &format!("{}:a", i + 1)])
+                        .arg(format!("-metadata:s:a:{i}"))
+                        .arg(format!("title={}", kind.title()));
+                }
+            }
+            (n, AudioMode::Mix) => {
+                let inputs: String = (1..=n).map(|i| format!("[{i}:a]")).collect();
+                command
+                    .args(["-filter_complex", &format!("{inputs}amix=inputs={n}:duration=longest:normalize=0[mix]")])
+                    .args(["-map", "[mix]"]);
+            }
+        }
+
+        command
+            .args(["-vf", &video_filter])
+            .args(&format.video)
+            .args(&format.audio);
+        match settings.segments {
+            Some(segments) => {
+                command
+                    .args(["-f", "segment", "-segment_format", "mpegts"])
+                    .args(["-segment_time", &segments.seconds.to_string()])
+                    .args(["-segment_wrap", &segments.wrap.to_string(), "-reset_timestamps", "1"]);
+            }
+            None => {
+                command.args(&format.muxer);
+            }
+        }
+        command
+            .arg(&settings.output)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let audio_inputs = attach_audio_pipes(&mut command, settings.audio.len())?;
+        let mut child = command
+            .spawn()
+            .map_err(|e| CaptureError::RecordingFailed(format!("Failed to start ffmpeg: {e}")))?;
+
+        let stdin = child.stdin.take();
+        // Drain stderr on a thread so a chatty ffmpeg can never block on a full pipe
+        let stderr = child.stderr.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                let mut log = String::new();
+                let _ = pipe.read_to_string(&mut log);
+                log
+            })
+        });
+
+        let progress = Arc::new(Mutex::new(None));
+        if let Some(stdout) = child.stdout.take() {
+            let reports = progress.clone();
+            std::thread::spawn(move || read_progress(stdout, &reports));
+        }
+
+        Ok(Self {
+            child,
+            stdin,
+            stderr,
+            output: settings.output.clone(),
+            frame_len: (settings.width * settings.height * 4) as usize,
+            audio_inputs,
+            progress,
+        })
+    }
+
+    fn stderr_log(&mut self) -> String {
+        self.stderr
+            .take()
+            .and_then(|h| h.join().ok())
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Parse `key=value` blocks from `-progress` until ffmpeg closes its stdout.
+/// Each block ends with a `progress=` line.
+fn read_progress(stdout: impl Read, reports: &Mutex<Option<EncoderProgress>>) {
+    let mut current = EncoderProgress::default();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        match line.split_once('=') {
+            Some(("frame", value)) => current.frames = value.trim().parse().unwrap_or(current.frames),
+            Some(("total_size", value)) => current.bytes = value.trim().parse().unwrap_or(current.bytes),
+            Some(("progress", _)) => *reports.lock().unwrap() = Some(current),
+            _ => {}
+        }
+    }
+}
+
+/// First fd number of the audio input pipes in the child
+const AUDIO_FD_BASE: i32 = 3;
+const MAX_AUDIO_INPUTS: usize = 2;
+
+/// Create one pipe per audio input and map the read ends to fds 3, 4, ... in the child.
+/// Returns the write ends. The parent's copies of the read ends live in the `pre_exec`
+/// hook and close when `command` is dropped after spawning.
+#[cfg(unix)]
+fn attach_audio_pipes(command: &mut Command, count: usize) -> Result<Vec<Box<dyn Write + Send>>, CaptureError> {
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if count > MAX_AUDIO_INPUTS {
+        return Err(CaptureError::InvalidConfig(format!("At most {MAX_AUDIO_INPUTS} audio sources are supported")));
+    }
+
+    let mut readers: Vec<OwnedFd> = Vec::with_capacity(count);
+    let mut writers: Vec<Box<dyn Write + Send>> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (reader, writer) = std::io::pipe()?;
+        readers.push(reader.into());
+        writers.push(Box::new(writer));
+    }
+
+    let mut fds = [-1; MAX_AUDIO_INPUTS];
+    for (slot, reader) in fds.iter_mut().zip(&readers) {
+        *slot = reader.as_raw_fd();
+    }
+    unsafe {
+        command.pre_exec(move || {
+            // Move every read end above the target range first, so mapping one onto
+            // fd 3 can never clobber another that happens to live there
+            let mut moved = [-1; MAX_AUDIO_INPUTS];
+            for (moved, fd) in moved.iter_mut().zip(fds).take(count) {
+                *moved = libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 16);
+                if *moved < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            for (i, fd) in moved.iter().take(count).enumerate() {
+                // dup2 clears close-on-exec on the target
+                if libc::dup2(*fd, AUDIO_FD_BASE + i as i32) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            // Keep the read ends alive until exec
+            let _ = &readers;
+            Ok(())
+        });
+    }
+    Ok(writers)
+}
+
+#[cfg(not(unix))]
+fn attach_audio_pipes(_command: &mut Command, count: usize) -> Result<Vec<Box<dyn Write + Send>>, CaptureError> {
+    if count == 0 {
+        Ok(Vec::new())
+    } else {
+        Err(CaptureError::RecordingFailed("Audio recording is not supported on this platform".into()))
+    }
+}
+
+impl Encoder for FfmpegEncoder {
+    fn write_frame(&mut self, frame: &Frame) -> Result<(), CaptureError> {
+        if frame.data.len() != self.frame_len {
+            return Err(CaptureError::RecordingFailed(format!(
+                "Frame size changed mid-recording ({}x{})",
+                frame.width, frame.height
+            )));
+        }
+        let stdin = self.stdin.as_mut().ok_or(CaptureError::RecordingNotActive)?;
+        if let Err(e) = stdin.write_all(&frame.data) {
+            // A broken pipe means ffmpeg exited; its log says why
+            let log = self.stderr_log();
+            return Err(CaptureError::RecordingFailed(format!("ffmpeg stopped accepting frames: {e} {log}")));
+        }
+        Ok(())
+    }
+
+    fn take_audio_inputs(&mut self) -> Vec<Box<dyn Write + Send>> {
+        std::mem::take(&mut self.audio_inputs)
+    }
+
+    fn progress(&self) -> Option<EncoderProgress> {
+        *self.progress.lock().unwrap()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), CaptureError> {
+        // Closing stdin signals end of stream; ffmpeg then writes the moov atom
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        let log = self.stderr_log();
+        if !status.success() {
+            return Err(CaptureError::RecordingFailed(format!("ffmpeg exited with {status}: {log}")));
+        }
+        Ok(())
+    }
+
+    fn abort(mut self: Box<Self>) {
+        drop(self.stdin.take());
+        self.audio_inputs.clear();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = self.stderr_log();
+        let _ = std::fs::remove_file(&self.output);
+    }
+}
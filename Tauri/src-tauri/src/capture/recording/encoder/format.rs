@@ -0,0 +1,219 @@
+//! ffmpeg codec and muxer arguments for each `OutputFormat`, and detection of
+//! which of them the installed ffmpeg can produce.
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use serde::Serialize;
+
+/// How an encoder takes a quality target and a bitrate cap
+#[derive(Clone, Copy)]
+enum RateControl {
+    /// `-crf` bounded by `-maxrate`/`-bufsize` (x264, x265)
+    CappedCrf,
+    /// `-crf` with `-b:v` as the ceiling (libvpx, libaom)
+    ConstrainedQuality,
+    /// `-crf` only; the encoder has no usable cap in ffmpeg
+    Crf,
+    /// Image formats: no rate control, no GOP
+    None,
+}
+
+/// A video encoder and the arguments that tune it for live capture
+struct VideoCodec {
+    encoder: &'static str,
+    args: &'static [&'static str],
+    rate_control: RateControl,
+    /// Added to the preset's x264-scale CRF for encoders with a wider scale
+    crf_offset: u8,
+}
+
+/// Encoders for `format`, most preferred first
+fn video_candidates(format: OutputFormat) -> &'static [VideoCodec] {
+    match format {
+        OutputFormat::Mp4 => &[VideoCodec {
+            encoder: "libx264",
+            args: &["-preset", "veryfast", "-pix_fmt", "yuv420p"],
+            rate_control: RateControl::CappedCrf,
+            crf_offset: 0,
+        }],
+        OutputFormat::Hevc => &[VideoCodec {
+            encoder: "libx265",
+            args: &["-preset", "superfast", "-pix_fmt", "yuv420p", "-tag:v", "hvc1"],
+            rate_control: RateControl::CappedCrf,
+            crf_offset: 3,
+        }],
+        OutputFormat::Webm => &[VideoCodec {
+            encoder: "libvpx-vp9",
+            args: &["-deadline", "realtime", "-cpu-used", "8", "-row-mt", "1", "-pix_fmt", "yuv420p"],
+            rate_control: RateControl::ConstrainedQuality,
+            crf_offset: 9,
+        }],
+        OutputFormat::Av1 => &[
+            VideoCodec {
+                encoder: "libsvtav1",
+                args: &["-preset", "10", "-pix_fmt", "yuv420p"],
+                rate_control: RateControl::Crf,
+                crf_offset: 12,
+            },
+            VideoCodec {
+                encoder: "libaom-av1",
+                args: &["-usage", "realtime", "-cpu-used", "8", "-row-mt", "1", "-pix_fmt", "yuv420p"],
+                rate_control: RateControl::ConstrainedQuality,
+                crf_offset: 12,
+            },
+        ],
+        OutputFormat::Webp => &[VideoCodec {
+            encoder: "libwebp_anim",
+            args: &["-lossless", "0", "-quality", "75", "-loop", "0", "-pix_fmt", "yuv420p"],
+            rate_control: RateControl::None,
+            crf_offset: 0,
+        }],
+        OutputFormat::Apng => &[VideoCodec {
+            encoder: "apng",
+            args: &["-plays", "0", "-pix_fmt", "rgb24"],
+            rate_control: RateControl::None,
+            crf_offset: 0,
+        }],
+    }
+}
+
+/// Rate control and keyframe arguments for `codec` at `quality`
+fn rate_args(codec: &VideoCodec, quality: &QualityPreset, fps: u32, keyframe_seconds: f64) -> Vec<String> {
+    if matches!(codec.rate_control, RateControl::None) {
+        return Vec::new();
+    }
+    let crf = (quality.crf() + codec.crf_offset).min(63).to_string();
+    let bitrate = quality.bitrate().to_string();
+    let mut args: Vec<String> = match codec.rate_control {
+        RateControl::CappedCrf => vec![
+            "-crf".into(),
+            crf,
+            "-maxrate".into(),
+            bitrate,
+            "-bufsize".into(),
+            // Two seconds of buffer lets short bursts (scrolling, cuts) borrow ahead
+            (quality.bitrate() as u64 * 2).to_string(),
+        ],
+        RateControl::ConstrainedQuality => vec!["-crf".into(), crf, "-b:v".into(), bitrate],
+        RateControl::Crf | RateControl::None => vec!["-crf".into(), crf],
+    };
+
+    // Fixed GOP: predictable seeking and cheap keyframe-aligned trims
+    let gop = ((fps as f64 * keyframe_seconds).round() as u32).max(1).to_string();
+    args.extend(["-g".into(), gop.clone(), "-keyint_min".into(), gop]);
+    if matches!(codec.encoder, "libx264" | "libx265") {
+        args.extend(["-sc_threshold".into(), "0".into()]);
+    }
+    args
+}
+
+fn audio_encoder(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Webm => "libopus",
+        _ => "aac",
+    }
+}
+
+/// Resolved arguments for one recording
+pub struct FormatArgs {
+    pub video: Vec<String>,
+    pub audio: Vec<&'static str>,
+    pub muxer: Vec<&'static str>,
+}
+
+/// Encoder, rate control and muxer arguments for `format` at `quality`, or
+/// `InvalidConfig` if ffmpeg lacks what it needs
+pub fn resolve(
+    format: OutputFormat,
+    with_audio: bool,
+    quality: &QualityPreset,
+    fps: u32,
+    keyframe_seconds: f64,
+) -> Result<FormatArgs, CaptureError> {
+    if with_audio && !format.supports_audio() {
+        return Err(CaptureError::InvalidConfig(format!("{} recordings cannot include audio", format.label())));
+    }
+    let codec = video_candidates(format)
+        .iter()
+        .find(|c| ffmpeg::has_encoder(c.encoder))
+        .ok_or_else(|| unavailable(format, video_candidates(format).iter().map(|c| c.encoder)))?;
+
+    let audio = if with_audio {
+        let encoder = audio_encoder(format);
+        if !ffmpeg::has_encoder(encoder) {
+            return Err(unavailable(format, [encoder]));
+        }
+        let bitrate = if encoder == "libopus" { "128k" } else { "160k" };
+        vec!["-c:a", encoder, "-b:a", bitrate]
+    } else {
+        vec![]
+    };
+
+    let mut video: Vec<String> = vec!["-c:v".into(), codec.encoder.into()];
+    video.extend(codec.args.iter().map(|a| a.to_string()));
+    video.extend(rate_args(codec, quality, fps, keyframe_seconds));
+
+    let muxer = match format {
+        // Fragmented MP4 stays playable up to the last fragment if we never get to finish
+        OutputFormat::Mp4 | OutputFormat::Hevc | OutputFormat::Av1 => {
+            vec!["-f", "mp4", "-movflags", "+frag_keyframe+empty_moov+default_base_moof"]
+        }
+        OutputFormat::Webm => vec!["-f", "webm"],
+        OutputFormat::Webp => vec!["-f", "webp"],
+        OutputFormat::Apng => vec!["-f", "apng"],
+    };
+    Ok(FormatArgs { video, audio, muxer })
+}
+
+/// Whether ffmpeg has a video encoder for `format`
+pub fn is_available(format: OutputFormat) -> bool {
+    video_candidates(format).iter().any(|c| ffmpeg::has_encoder(c.encoder))
+}
+
+/// `InvalidConfig` naming the missing encoder unless `format` is available
+pub fn check_available(format: OutputFormat) -> Result<(), CaptureError> {
+    if is_available(format) {
+        Ok(())
+    } else {
+        Err(unavailable(format, video_candidates(format).iter().map(|c| c.encoder)))
+    }
+}
+
+fn unavailable<'a>(format: OutputFormat, encoders: impl IntoIterator<Item = &'a str>) -> CaptureError {
+    let encoders: Vec<_> = encoders.into_iter().collect();
+    CaptureError::InvalidConfig(format!(
+        "{} is not available: ffmpeg was built without {}",
+        format.label(),
+        encoders.join(" or ")
+    ))
+}
+
+/// Whether a format can be recorded here, for the settings UI
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatSupport {
+    pub format: OutputFormat,
+    pub label: &'static str,
+    pub extension: &'static str,
+    pub supports_audio: bool,
+    pub available: bool,
+}
+
+pub fn supported_formats() -> Vec<FormatSupport> {
+    [
+        OutputFormat::Mp4,
+        OutputFormat::Hevc,
+        OutputFormat::Webm,
+        OutputFormat::Av1,
+        OutputFormat::Webp,
+        OutputFormat::Apng,
+    ]
+    .into_iter()
+    .map(|format| FormatSupport {
+        format,
+        label: format.label(),
+        extension: format.extension(),
+        supports_audio: format.supports_audio(),
+        available: is_available(format),
+    })
+    .collect()
+}
@@ -0,0 +1,74 @@
+//! Encoders: consumers of raw frames that produce the recording file
+use crate::capture::config::{AudioMode, OutputFormat, QualityPreset};
+use crate::capture::recording::audio::AudioSourceKind;
+use crate::capture::recording::source::Frame;
+use crate::error::CaptureError;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub mod ffmpeg;
+pub mod format;
+
+/// Resolved output parameters for one recording
+#[derive(Debug, Clone)]
+pub struct EncoderSettings {
+    /// Size of the frames written; the output is scaled to fit `quality`
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub quality: QualityPreset,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    /// One raw PCM input per source, in this order
+    pub audio: Vec<AudioSourceKind>,
+    pub audio_mode: AudioMode,
+    /// Write a wrapping ring of segments instead of one file; `output` is then a
+    /// printf-style pattern such as `segment-%03d.ts`
+    pub segments: Option<SegmentSettings>,
+}
+
+/// Rolling output for the replay buffer
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentSettings {
+    /// Length of each segment; every segment starts on a keyframe
+    pub seconds: u32,
+    /// Number of segment files before the oldest is overwritten
+    pub wrap: u32,
+}
+
+/// How far an encoder has got with the frames written to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncoderProgress {
+    pub frames: u64,
+    /// Output written so far
+    pub bytes: u64,
+}
+
+/// Turns a constant-rate stream of frames into a media file.
+///
+/// Frames arrive in presentation order, one per `1 / fps` of media time.
+pub trait Encoder: Send {
+    fn write_frame(&mut self, frame: &Frame) -> Result<(), CaptureError>;
+
+    /// Writers for the audio inputs requested in `EncoderSettings::audio`, in order.
+    /// Each expects s16le PCM at `audio::SAMPLE_RATE`; dropping one ends that input.
+    fn take_audio_inputs(&mut self) -> Vec<Box<dyn Write + Send>> {
+        Vec::new()
+    }
+
+    /// Latest progress report; `None` for encoders that don't report any
+    fn progress(&self) -> Option<EncoderProgress> {
+        None
+    }
+
+    /// Flush and close the output. Returns once the file is complete on disk.
+    fn finish(self: Box<Self>) -> Result<(), CaptureError>;
+
+    /// Tear down without producing a usable file, removing any partial output
+    fn abort(self: Box<Self>);
+}
+
+/// Open the default encoder (ffmpeg, in `settings.format`)
+pub fn open_encoder(settings: &EncoderSettings) -> Result<Box<dyn Encoder>, CaptureError> {
+    Ok(Box::new(ffmpeg::FfmpegEncoder::spawn(settings)?))
+}
@@ -0,0 +1,113 @@
+//! Automatic stop conditions for long-running recordings
+use crate::capture::config::RecordingConfig;
+use crate::error::CaptureError;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Output size and disk space are polled at this interval rather than every frame
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// ffmpeg still holds buffered frames when asked to stop; leave room for that much output
+const FLUSH_HEADROOM: Duration = Duration::from_secs(3);
+
+/// Why a recording ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    /// Stopped by the user
+    Requested,
+    MaxDuration,
+    MaxFileSize,
+    LowDiskSpace,
+    /// The recorded window was closed
+    WindowClosed,
+}
+
+/// Limits from `RecordingConfig`, checked by the pipeline as it runs
+#[derive(Debug)]
+pub struct RecordingLimits {
+    max_duration: Option<f64>,
+    max_file_size: Option<u64>,
+    min_free_disk: Option<u64>,
+    output: PathBuf,
+    last_check: Option<(Instant, u64)>,
+}
+
+impl RecordingLimits {
+    pub fn new(config: &RecordingConfig, output: PathBuf) -> Self {
+        Self {
+            max_duration: config.max_duration_seconds.map(|s| s as f64),
+            max_file_size: config.max_file_size_bytes,
+            min_free_disk: config.min_free_disk_bytes,
+            output,
+            last_check: None,
+        }
+    }
+
+    /// Refuse to start when the disk is already below the free-space limit
+    pub fn check_can_start(&self) -> Result<(), CaptureError> {
+        if let (Some(min), Some(free)) = (self.min_free_disk, free_disk_space(&self.output)) {
+            if free < min {
+                return Err(CaptureError::StorageError(format!(
+                    "Not enough free disk space to record ({} MB free)",
+                    free / (1024 * 1024)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The limit that has been reached at `media_seconds` into the recording, if any
+    pub fn check(&mut self, media_seconds: f64) -> Option<StopReason> {
+        if matches!(self.max_duration, Some(max) if media_seconds >= max) {
+            return Some(StopReason::MaxDuration);
+        }
+        if self.max_file_size.is_none() && self.min_free_disk.is_none() {
+            return None;
+        }
+
+        let now = Instant::now();
+        if matches!(self.last_check, Some((at, _)) if now.duration_since(at) < DISK_CHECK_INTERVAL) {
+            return None;
+        }
+        let size = std::fs::metadata(&self.output).map(|m| m.len()).unwrap_or(0);
+        // Bytes the encoder is likely to write after we ask it to stop
+        let headroom = match self.last_check {
+            Some((at, last_size)) => {
+                let rate = size.saturating_sub(last_size) as f64 / now.duration_since(at).as_secs_f64();
+                (rate * FLUSH_HEADROOM.as_secs_f64()) as u64
+            }
+            None => 0,
+        };
+        self.last_check = Some((now, size));
+
+        if matches!(self.max_file_size, Some(max) if size + headroom >= max) {
+            return Some(StopReason::MaxFileSize);
+        }
+        match (self.min_free_disk, free_disk_space(&self.output)) {
+            (Some(min), Some(free)) if free.saturating_sub(headroom) < min => Some(StopReason::LowDiskSpace),
+            _ => None,
+        }
+    }
+}
+
+/// Space available to unprivileged writers on the volume holding `path`
+#[cfg(unix)]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // The output may not exist yet; its directory does
+    let dir = if path.is_dir() { path } else { path.parent()? };
+    let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,99 @@
+//! Named markers dropped during a recording, written to the file as chapters
+use crate::error::CaptureError;
+use crate::services::editor::TimeRange;
+use crate::services::export::ScratchDir;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+/// A point on a recording's media timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingMarker {
+    /// Seconds from the start of the recording, excluding paused time
+    pub time: f64,
+    pub name: String,
+}
+
+/// Markers of a source that survive keeping `keep`, moved onto the edited timeline
+pub fn remap(markers: &[RecordingMarker], keep: &[TimeRange]) -> Vec<RecordingMarker> {
+    let mut offset = 0.0;
+    let mut remapped = Vec::new();
+    for range in keep {
+        remapped.extend(
+            markers
+                .iter()
+                .filter(|m| m.time >= range.start && m.time < range.end)
+                .map(|m| RecordingMarker { time: offset + m.time - range.start, name: m.name.clone() }),
+        );
+        offset += range.duration();
+    }
+    remapped
+}
+
+/// Escape a value for an ffmetadata file
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Replace the chapters of `path` with one chapter per marker, each running to the
+/// next marker or the end. Streams are copied, so this is quick even for long files.
+pub fn write_chapters(path: &Path, markers: &[RecordingMarker], duration: f64) -> Result<(), CaptureError> {
+    let mut markers: Vec<&RecordingMarker> = markers.iter().filter(|m| m.time < duration).collect();
+    markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    if markers.is_empty() {
+        return Ok(());
+    }
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (i, marker) in markers.iter().enumerate() {
+        let end = markers.get(i + 1).map_or(duration, |next| next.time);
+        let _ = write!(
+            metadata,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (marker.time * 1000.0).round() as u64,
+            (end * 1000.0).round() as u64,
+            escape(&marker.name)
+        );
+    }
+
+    let scratch = ScratchDir::new("chapters")?;
+    let metadata_path = scratch.join("chapters.txt");
+    std::fs::write(&metadata_path, metadata)?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    // Written beside the file so it can be swapped in with a rename
+    let output = path.with_file_name(format!(".{}.chapters.{extension}", uuid::Uuid::new_v4()));
+
+    let mut args = vec![
+        "-i".to_string(),
+        path.to_string_lossy().into_owned(),
+        "-i".into(),
+        metadata_path.to_string_lossy().into_owned(),
+        "-map".into(),
+        "0".into(),
+        "-map_chapters".into(),
+        "1".into(),
+        "-c".into(),
+        "copy".into(),
+    ];
+    if extension == "mp4" {
+        args.extend(["-movflags".into(), "+faststart".into()]);
+    }
+    args.push(output.to_string_lossy().into_owned());
+
+    let never = AtomicBool::new(false);
+    let result = crate::services::ffmpeg::run(args, 0.0, &never, |_| {})
+        .map_err(|e| CaptureError::RecordingFailed(format!("Could not write chapters: {e}")))
+        .and_then(|_| Ok(std::fs::rename(&output, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&output);
+    }
+    result
+}
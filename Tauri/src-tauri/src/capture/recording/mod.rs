@@ -0,0 +1,480 @@
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use crate::capture::config::{RecordingConfig, RecordingTarget};
+use crate::error::CaptureError;
+use crate::events;
+use crate::state::app_state::AppState;
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::services::storage::{preview, recovery};
+
+pub mod audio;
+pub mod coordinator;
+pub mod encoder;
+pub mod limits;
+pub mod markers;
+pub mod overlay;
+pub mod pipeline;
+pub mod replay;
+pub mod source;
+pub mod stats;
+pub mod ticker;
+pub mod track;
+
+use encoder::EncoderSettings;
+use limits::{RecordingLimits, StopReason};
+use markers::RecordingMarker;
+use pipeline::{MediaClock, Pipeline, PipelineCommand, PipelineOutcome};
+use source::SourceStatus;
+use track::CursorTrack;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum RecordingSessionState {
+    Idle,
+    Selecting,
+    Starting,
+    Recording { elapsed_seconds: f64 },
+    Paused { elapsed_seconds: f64 },
+    Stopping,
+    #[serde(rename = "exportingGif")]
+    ExportingGif,
+    Completed,
+    Failed { message: String },
+    Cancelled,
+}
+
+impl RecordingSessionState {
+    /// Serialized state name, for logs and errors
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordingSessionState::Idle => "idle",
+            RecordingSessionState::Selecting => "selecting",
+            RecordingSessionState::Starting => "starting",
+            RecordingSessionState::Recording { .. } => "recording",
+            RecordingSessionState::Paused { .. } => "paused",
+            RecordingSessionState::Stopping => "stopping",
+            RecordingSessionState::ExportingGif => "exportingGif",
+            RecordingSessionState::Completed => "completed",
+            RecordingSessionState::Failed { .. } => "failed",
+            RecordingSessionState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Payload of `RECORDING_COMPLETED`: the saved item plus why the recording ended
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingCompleted {
+    #[serde(flatten)]
+    pub item: CaptureItem,
+    pub stop_reason: StopReason,
+}
+
+/// Handle to the pipeline thread of the in-flight recording
+pub struct ActiveRecording {
+    control: Sender<PipelineCommand>,
+    /// Receives the saved item (or `None` when cancelled) once the session has ended
+    done: Receiver<Result<Option<CaptureItem>, CaptureError>>,
+    clock: MediaClock,
+    /// Shared with the pipeline thread, which saves them with the item
+    markers: Arc<Mutex<Vec<RecordingMarker>>>,
+    /// Set while the followed window is hidden; only the window coming back resumes then
+    source_hidden: Arc<AtomicBool>,
+}
+
+fn transition(app: &AppHandle, to: RecordingSessionState) -> Result<(), CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let mut coordinator = state.recording_coordinator.lock().unwrap();
+    coordinator.transition(to)
+}
+
+/// For teardown paths where there is no caller to report a rejected transition to
+fn transition_or_log(app: &AppHandle, to: RecordingSessionState) {
+    if let Err(e) = transition(app, to) {
+        log::warn!("{}", e);
+    }
+}
+
+/// Start a screen recording session
+pub async fn start_recording(
+    target: RecordingTarget,
+    config: RecordingConfig,
+    app: &AppHandle,
+) -> Result<(), CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+
+    config.validate()?;
+
+    // Check if already recording
+    if state.recording_coordinator.lock().unwrap().is_active() {
+        return Err(CaptureError::RecordingFailed("Recording already in progress".into()));
+    }
+    transition(app, RecordingSessionState::Starting)?;
+
+    // Encode into a .partial file that is only renamed once it is complete
+    let partial = {
+        let storage = state.storage.lock().unwrap();
+        let filename = storage.generate_filename(&CaptureType::Recording, config.format.extension());
+        recovery::partial_path(&storage.screenshots_dir().join(filename))
+    };
+    let output = partial.clone();
+    let limits = RecordingLimits::new(&config, partial.clone());
+    let level_app = app.clone();
+    let on_level: audio::LevelListener = Arc::new(move |level| {
+        let _ = level_app.emit(events::RECORDING_AUDIO_LEVEL, level);
+    });
+    let status_app = app.clone();
+    let source_hidden = Arc::new(AtomicBool::new(false));
+    let status_hidden = source_hidden.clone();
+    let on_source_status = move |status| {
+        status_hidden.store(status == SourceStatus::Hidden, Ordering::Release);
+        source_status_changed(&status_app, status)
+    };
+    let stats_app = app.clone();
+    let on_stats = move |stats: &stats::RecordingStats| {
+        let _ = stats_app.emit(events::RECORDING_STATS, stats);
+    };
+
+    let track = Arc::new(Mutex::new(CursorTrack { cursor_in_video: config.include_cursor, ..Default::default() }));
+    let session_track = track.clone();
+
+    // Opening the source can block on a system picker, so keep it off the async runtime
+    let opened = tokio::task::spawn_blocking(move || -> Result<Pipeline, CaptureError> {
+        if let Some(dir) = output.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        limits.check_can_start()?;
+        // Fail on a missing encoder before the user picks a source
+        encoder::format::check_available(config.format)?;
+        let source = source::open_source(&target, &config)?;
+        let (width, height) = source.size();
+        {
+            let mut track = track.lock().unwrap();
+            track.width = width;
+            track.height = height;
+        }
+        let audio_sources = if config.format.supports_audio() {
+            audio::requested_sources(&config)
+        } else {
+            if config.include_microphone || config.include_system_audio {
+                log::warn!("{} recordings have no audio; recording video only", config.format.label());
+            }
+            Vec::new()
+        };
+        let mut encoder = encoder::open_encoder(&EncoderSettings {
+            width,
+            height,
+            fps: config.fps,
+            quality: config.quality,
+            output,
+            format: config.format,
+            segments: None,
+            audio: audio_sources.iter().map(|(kind, _)| *kind).collect(),
+            audio_mode: config.audio_mode,
+        })?;
+        let audio_inputs = encoder.take_audio_inputs();
+        let mut pipeline = Pipeline::new(source, encoder, config.fps)
+            .with_limits(limits)
+            .on_source_status(on_source_status)
+            .on_stats(on_stats);
+
+        // Overlays and the cursor track (which only auto-zoom exports need) are a
+        // nicety: without global input access, record without them. One listener feeds both.
+        match crate::capture::input::InputMonitor::start() {
+            Ok(input) => {
+                if let Some(overlay) = overlay::OverlayCompositor::new(input.subscribe(), &config) {
+                    pipeline = pipeline.with_overlay(overlay);
+                }
+                match track::TrackRecorder::start(track, input.subscribe()) {
+                    Ok(recorder) => pipeline = pipeline.with_track(recorder),
+                    Err(e) => log::warn!("Recording without a cursor track: {}", e),
+                }
+            }
+            Err(e) => log::warn!("Recording without click/keystroke overlays or a cursor track: {}", e),
+        }
+
+        let mut captures = Vec::with_capacity(audio_sources.len());
+        for ((kind, device), input) in audio_sources.into_iter().zip(audio_inputs) {
+            let exclude_app = config.exclude_app_audio;
+            match audio::AudioCapture::start(kind, &device, exclude_app, input, pipeline.clock(), on_level.clone()) {
+                Ok(capture) => captures.push(capture),
+                Err(e) => {
+                    captures.into_iter().for_each(audio::AudioCapture::stop);
+                    pipeline.abort();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(pipeline.with_audio(captures))
+    })
+    .await
+    .map_err(|e| CaptureError::RecordingFailed(e.to_string()))
+    .and_then(|r| r);
+
+    let pipeline = match opened {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            transition_or_log(app, RecordingSessionState::Failed { message: e.to_string() });
+            return Err(e);
+        }
+    };
+
+    let (control_tx, control_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    // Register the session before the thread starts so an immediate failure can clear it
+    let markers = Arc::new(Mutex::new(Vec::new()));
+    let session_markers = markers.clone();
+    *state.recording.lock().unwrap() = Some(ActiveRecording {
+        control: control_tx,
+        done: done_rx,
+        clock: pipeline.clock(),
+        markers,
+        source_hidden,
+    });
+    if let Err(e) = transition(app, RecordingSessionState::Recording { elapsed_seconds: 0.0 }) {
+        state.recording.lock().unwrap().take();
+        pipeline.abort();
+        return Err(e);
+    }
+
+    let app_handle = app.clone();
+    let spawned = std::thread::Builder::new()
+        .name("recording-pipeline".into())
+        .spawn(move || {
+            let ticker = ticker::DurationTicker::spawn(app_handle.clone(), pipeline.clock());
+            if let Err(e) = &ticker {
+                log::warn!("Failed to start duration ticker: {}", e);
+            }
+            let result = pipeline.run(control_rx);
+            // Stop ticking before the terminal transition so no stale duration follows it
+            drop(ticker);
+            let markers = std::mem::take(&mut *session_markers.lock().unwrap());
+            let track = std::mem::take(&mut *session_track.lock().unwrap());
+            let _ = done_tx.send(finish_session(&app_handle, &partial, markers, track, result));
+        });
+
+    if let Err(e) = spawned {
+        state.recording.lock().unwrap().take();
+        transition_or_log(app, RecordingSessionState::Failed { message: e.to_string() });
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Record the outcome of a pipeline run: save the item, update state and notify the frontend
+fn finish_session(
+    app: &AppHandle,
+    partial: &std::path::Path,
+    markers: Vec<RecordingMarker>,
+    track: CursorTrack,
+    result: Result<PipelineOutcome, CaptureError>,
+) -> Result<Option<CaptureItem>, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    state.recording.lock().unwrap().take();
+
+    match result {
+        Ok(PipelineOutcome::Finished { frames, reason, stats }) => {
+            if reason != StopReason::Requested {
+                // The pipeline stopped itself; catch the session up unless a user stop raced it
+                let mut coordinator = state.recording_coordinator.lock().unwrap();
+                if matches!(
+                    coordinator.state(),
+                    RecordingSessionState::Recording { .. } | RecordingSessionState::Paused { .. }
+                ) {
+                    let _ = coordinator.transition(RecordingSessionState::Stopping);
+                }
+            }
+
+            // Only a cleanly closed file gets its final name; on failure it stays
+            // .partial and is offered for recovery on the next launch
+            let saved = recovery::finalize(partial).and_then(|path| {
+                let filename = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                log::info!("Recording finished: {} ({} frames)", filename, frames);
+                if !markers.is_empty() {
+                    // Chapters are a convenience; the markers are kept on the item regardless
+                    let written = crate::services::ffmpeg::probe(&path)
+                        .and_then(|info| markers::write_chapters(&path, &markers, info.duration));
+                    if let Err(e) = written {
+                        log::warn!("Recording saved without chapters: {}", e);
+                    }
+                }
+                let mut item = CaptureItem::new_recording(filename);
+                item.markers = markers;
+                if !track.samples.is_empty() || !track.clicks.is_empty() {
+                    if let Err(e) = track::save(&item.id, &track) {
+                        log::warn!("Recording saved without a cursor track: {}", e);
+                    }
+                }
+                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                item.stats = Some(stats.finished(size));
+                let mut storage = state.storage.lock().unwrap();
+                storage.history.add(item.clone());
+                storage.save_history()?;
+                Ok(item)
+            });
+
+            match saved {
+                Ok(item) => {
+                    transition_or_log(app, RecordingSessionState::Completed);
+                    let _ = app.emit(
+                        events::RECORDING_COMPLETED,
+                        RecordingCompleted { item: item.clone(), stop_reason: reason },
+                    );
+                    preview::generate_in_background(app, item.id.clone());
+                    Ok(Some(item))
+                }
+                Err(e) => Err(fail_session(app, e)),
+            }
+        }
+        Ok(PipelineOutcome::Cancelled) => {
+            transition_or_log(app, RecordingSessionState::Cancelled);
+            Ok(None)
+        }
+        Err(e) => Err(fail_session(app, e)),
+    }
+}
+
+/// Mirror the pipeline pausing itself for a hidden source (e.g. a minimised window)
+/// in the session state, so the UI shows it as paused until the source returns
+fn source_status_changed(app: &AppHandle, status: SourceStatus) {
+    let state: tauri::State<'_, AppState> = app.state();
+    let mut coordinator = state.recording_coordinator.lock().unwrap();
+    let next = match (coordinator.state(), status) {
+        (RecordingSessionState::Recording { elapsed_seconds }, SourceStatus::Hidden) => {
+            Some(RecordingSessionState::Paused { elapsed_seconds: *elapsed_seconds })
+        }
+        (RecordingSessionState::Paused { elapsed_seconds }, SourceStatus::Live) => {
+            Some(RecordingSessionState::Recording { elapsed_seconds: *elapsed_seconds })
+        }
+        _ => None,
+    };
+    if let Some(next) = next {
+        if let Err(e) = coordinator.transition(next) {
+            log::warn!("{}", e);
+        }
+    }
+    drop(coordinator);
+    let _ = app.emit(events::RECORDING_SOURCE_STATUS, serde_json::json!({ "status": status }));
+}
+
+fn fail_session(app: &AppHandle, e: CaptureError) -> CaptureError {
+    log::error!("Recording failed: {}", e);
+    let message = e.to_string();
+    transition_or_log(app, RecordingSessionState::Failed { message: message.clone() });
+    let _ = app.emit(events::RECORDING_FAILED, serde_json::json!({ "message": message }));
+    e
+}
+
+/// Send a command to the active pipeline and wait for the session to end
+async fn end_session(
+    app: &AppHandle,
+    command: PipelineCommand,
+) -> Result<Option<CaptureItem>, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let active = {
+        let mut recording = state.recording.lock().unwrap();
+        if recording.is_none() {
+            return Err(CaptureError::RecordingNotActive);
+        }
+        // Validate before taking the handle so a rejected stop leaves the session running
+        if command == PipelineCommand::Stop {
+            transition(app, RecordingSessionState::Stopping)?;
+        }
+        recording.take().ok_or(CaptureError::RecordingNotActive)?
+    };
+    let _ = active.control.send(command);
+
+    tokio::task::spawn_blocking(move || active.done.recv())
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))?
+        .map_err(|_| CaptureError::RecordingFailed("Recording pipeline exited unexpectedly".into()))?
+}
+
+/// Pause the current recording. Nothing is captured or encoded until it resumes.
+pub fn pause_recording(app: &AppHandle) -> Result<(), CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let recording = state.recording.lock().unwrap();
+    let active = recording.as_ref().ok_or(CaptureError::RecordingNotActive)?;
+
+    let mut coordinator = state.recording_coordinator.lock().unwrap();
+    let elapsed_seconds = match coordinator.state() {
+        RecordingSessionState::Recording { elapsed_seconds } => *elapsed_seconds,
+        _ => 0.0,
+    };
+    coordinator.transition(RecordingSessionState::Paused { elapsed_seconds })?;
+    active
+        .control
+        .send(PipelineCommand::Pause)
+        .map_err(|_| CaptureError::RecordingNotActive)
+}
+
+/// Resume a paused recording
+pub fn resume_recording(app: &AppHandle) -> Result<(), CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let recording = state.recording.lock().unwrap();
+    let active = recording.as_ref().ok_or(CaptureError::RecordingNotActive)?;
+    // The pipeline is waiting for the window, not for us; resuming now would only
+    // start the clock with no frames behind it
+    if active.source_hidden.load(Ordering::Acquire) {
+        return Err(CaptureError::RecordingFailed(
+            "The recorded window is hidden; recording resumes when it is shown again".into(),
+        ));
+    }
+
+    let mut coordinator = state.recording_coordinator.lock().unwrap();
+    let elapsed_seconds = match coordinator.state() {
+        RecordingSessionState::Paused { elapsed_seconds } => *elapsed_seconds,
+        _ => 0.0,
+    };
+    coordinator.transition(RecordingSessionState::Recording { elapsed_seconds })?;
+    active
+        .control
+        .send(PipelineCommand::Resume)
+        .map_err(|_| CaptureError::RecordingNotActive)
+}
+
+/// Mark the current point of the recording's media timeline. Unnamed markers are
+/// numbered in order.
+pub fn add_marker(app: &AppHandle, name: Option<String>) -> Result<RecordingMarker, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let recording = state.recording.lock().unwrap();
+    let active = recording.as_ref().ok_or(CaptureError::RecordingNotActive)?;
+
+    let mut markers = active.markers.lock().unwrap();
+    let name = match name.map(|n| n.trim().to_string()) {
+        Some(name) if !name.is_empty() => name,
+        _ => format!("Marker {}", markers.len() + 1),
+    };
+    let marker = RecordingMarker { time: active.clock.elapsed_seconds(), name };
+    markers.push(marker.clone());
+    drop(markers);
+    drop(recording);
+
+    log::info!("Marker \"{}\" at {:.2}s", marker.name, marker.time);
+    let _ = app.emit(events::RECORDING_MARKER_ADDED, &marker);
+    Ok(marker)
+}
+
+/// Stop the current recording
+pub async fn stop_recording(app: &AppHandle) -> Result<CaptureItem, CaptureError> {
+    end_session(app, PipelineCommand::Stop)
+        .await?
+        .ok_or(CaptureError::RecordingNotActive)
+}
+
+/// Cancel the current recording
+pub async fn cancel_recording(app: &AppHandle) -> Result<(), CaptureError> {
+    end_session(app, PipelineCommand::Cancel).await.map(|_| ())
+}
+
+/// Get current recording state
+pub fn get_state(state: &tauri::State<'_, AppState>) -> RecordingSessionState {
+    state.recording_coordinator.lock().unwrap().state().clone()
+}
@@ -0,0 +1,264 @@
+//! Compositor stage that draws click ripples and a keystroke HUD onto frames
+//! before they reach the encoder, fed by the global input stream.
+use crate::capture::config::{HudPosition, OverlayStyle, RecordingConfig};
+use crate::capture::input::{InputEvent, InputSubscription, Modifiers, MouseButton};
+use crate::capture::recording::source::Frame;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use std::time::{Duration, Instant};
+
+/// How long a click ripple takes to expand and fade out
+const RIPPLE_DURATION: Duration = Duration::from_millis(500);
+/// Most key labels shown at once; older ones scroll off
+const MAX_KEYS: usize = 6;
+/// Sizes in the style are for 1080p output and scale with the frame height
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+/// Bold sans fonts that cover the modifier glyphs, most preferred first
+const FONT_CANDIDATES: &[&str] = &[
+    "/System/Library/Fonts/SFNS.ttf",
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans-Bold.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans-Bold.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans-Bold.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Bold.ttf",
+    "/usr/share/fonts/noto/NotoSans-Bold.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Bold.ttf",
+];
+/// Used when none of the candidates is installed (see fonts/LICENSE-DejaVu.txt)
+const BUNDLED_FONT: &[u8] = include_bytes!("../../../fonts/DejaVuSans-Bold.ttf");
+
+struct Ripple {
+    x: f64,
+    y: f64,
+    button: MouseButton,
+    started: Instant,
+}
+
+struct KeyPress {
+    label: String,
+    at: Instant,
+}
+
+pub struct OverlayCompositor {
+    input: InputSubscription,
+    style: OverlayStyle,
+    show_clicks: bool,
+    show_keystrokes: bool,
+    font: Option<FontVec>,
+    ripples: Vec<Ripple>,
+    keys: Vec<KeyPress>,
+}
+
+impl OverlayCompositor {
+    /// `None` when the config asks for no overlays
+    pub fn new(input: InputSubscription, config: &RecordingConfig) -> Option<Self> {
+        if !config.show_mouse_clicks && !config.show_keystrokes {
+            return None;
+        }
+        let font = config.show_keystrokes.then(load_font);
+        Some(Self {
+            input,
+            style: config.overlay.clone(),
+            show_clicks: config.show_mouse_clicks,
+            show_keystrokes: config.show_keystrokes,
+            font,
+            ripples: Vec::new(),
+            keys: Vec::new(),
+        })
+    }
+
+    /// Draw the current overlays onto `frame`. `to_frame` maps a global pointer
+    /// position to frame pixels, or `None` when it's outside the captured area.
+    pub fn apply(&mut self, frame: &mut Frame, mut to_frame: impl FnMut(f64, f64) -> Option<(f64, f64)>) {
+        let now = Instant::now();
+        let events: Vec<_> = self.input.drain().collect();
+        for input in events {
+            match input.event {
+                InputEvent::Click { x, y, button } if self.show_clicks => {
+                    if let Some((x, y)) = to_frame(x, y) {
+                        self.ripples.push(Ripple { x, y, button, started: input.at });
+                    }
+                }
+                InputEvent::Key { key, modifiers } if self.show_keystrokes => {
+                    let label = self.key_label(&key, &modifiers);
+                    self.keys.push(KeyPress { label, at: input.at });
+                }
+                _ => {}
+            }
+        }
+
+        let key_duration = Duration::from_millis(self.style.keystroke_duration_ms);
+        self.ripples.retain(|r| now.duration_since(r.started) < RIPPLE_DURATION);
+        self.keys.retain(|k| now.duration_since(k.at) < key_duration);
+        if self.keys.len() > MAX_KEYS {
+            self.keys.drain(..self.keys.len() - MAX_KEYS);
+        }
+
+        let scale = frame.height as f32 / REFERENCE_HEIGHT;
+        for ripple in &self.ripples {
+            let t = now.duration_since(ripple.started).as_secs_f32() / RIPPLE_DURATION.as_secs_f32();
+            draw_ripple(frame, ripple, t, &self.style, scale);
+        }
+        if !self.keys.is_empty() {
+            self.draw_keys(frame, now, key_duration, scale);
+        }
+    }
+
+    /// "⌘⇧K" when the font has the modifier glyphs, "Ctrl+Shift+K" otherwise
+    fn key_label(&self, key: &str, modifiers: &Modifiers) -> String {
+        let has_glyphs = self
+            .font
+            .as_ref()
+            .map(|f| ['⌃', '⌥', '⇧', '⌘'].iter().all(|c| f.glyph_id(*c).0 != 0))
+            .unwrap_or(false);
+        let use_glyphs = cfg!(target_os = "macos") && has_glyphs;
+
+        let held = [
+            (modifiers.control, "⌃", "Ctrl"),
+            (modifiers.alt, "⌥", "Alt"),
+            (modifiers.shift, "⇧", "Shift"),
+            (modifiers.meta, "⌘", if cfg!(target_os = "macos") { "Cmd" } else { "Super" }),
+        ];
+        let mut label = String::new();
+        for (_, glyph, name) in held.iter().filter(|(down, _, _)| *down) {
+            if use_glyphs {
+                label.push_str(glyph);
+            } else {
+                label.push_str(name);
+                label.push('+');
+            }
+        }
+        label.push_str(key);
+        label
+    }
+
+    fn draw_keys(&self, frame: &mut Frame, now: Instant, key_duration: Duration, scale: f32) {
+        let Some(font) = &self.font else { return };
+        let px = PxScale::from(self.style.font_size * scale);
+        let scaled = font.as_scaled(px);
+        let text = self.keys.iter().map(|k| k.label.as_str()).collect::<Vec<_>>().join("  ");
+
+        let text_width: f32 = text.chars().map(|c| scaled.h_advance(scaled.glyph_id(c))).sum();
+        let padding = 0.6 * px.y;
+        let box_w = text_width + 2.0 * padding;
+        let box_h = scaled.height() + padding;
+        let margin = 40.0 * scale;
+        let (fw, fh) = (frame.width as f32, frame.height as f32);
+        let x = match self.style.keystroke_position {
+            HudPosition::TopLeft | HudPosition::BottomLeft => margin,
+            HudPosition::TopCenter | HudPosition::BottomCenter => (fw - box_w) / 2.0,
+            HudPosition::TopRight | HudPosition::BottomRight => fw - box_w - margin,
+        };
+        let y = match self.style.keystroke_position {
+            HudPosition::TopLeft | HudPosition::TopCenter | HudPosition::TopRight => margin,
+            _ => fh - box_h - margin,
+        };
+
+        // Fade the whole HUD out with the newest key
+        let newest = self.keys.last().map(|k| now.duration_since(k.at)).unwrap_or_default();
+        let remaining = key_duration.saturating_sub(newest).as_secs_f32();
+        let fade = (remaining / 0.25).min(1.0);
+
+        let mut background = self.style.background_color;
+        background[3] = (background[3] as f32 * fade) as u8;
+        fill_rounded_rect(frame, x, y, box_w, box_h, box_h * 0.25, background);
+
+        let mut caret = x + padding;
+        let baseline = y + padding / 2.0 + scaled.ascent();
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            let glyph = id.with_scale_and_position(px, ab_glyph::point(caret, baseline));
+            caret += scaled.h_advance(id);
+            let Some(outline) = font.outline_glyph(glyph) else { continue };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                blend(
+                    frame,
+                    bounds.min.x as i64 + gx as i64,
+                    bounds.min.y as i64 + gy as i64,
+                    self.style.text_color,
+                    coverage * fade,
+                );
+            });
+        }
+    }
+}
+
+/// First bold sans font found on the system, or the bundled one
+pub(crate) fn load_font() -> FontVec {
+    FONT_CANDIDATES
+        .iter()
+        .find_map(|path| {
+            let data = std::fs::read(path).ok()?;
+            FontVec::try_from_vec_and_index(data, 0).ok()
+        })
+        .unwrap_or_else(|| FontVec::try_from_vec(BUNDLED_FONT.to_vec()).expect("bundled font is valid"))
+}
+
+/// Expanding ring with a fading dot at the click point; right clicks get a double ring
+fn draw_ripple(frame: &mut Frame, ripple: &Ripple, t: f32, style: &OverlayStyle, scale: f32) {
+    let max_radius = style.ripple_radius * scale;
+    let alpha = 1.0 - t;
+    let thickness = (3.0 * scale).max(1.5);
+    let mut rings = vec![max_radius * (0.3 + 0.7 * t)];
+    if ripple.button == MouseButton::Right {
+        rings.push(rings[0] * 0.65);
+    }
+
+    let reach = max_radius + thickness;
+    let (cx, cy) = (ripple.x as f32, ripple.y as f32);
+    for py in (cy - reach).floor() as i64..=(cy + reach).ceil() as i64 {
+        for px in (cx - reach).floor() as i64..=(cx + reach).ceil() as i64 {
+            let d = ((px as f32 + 0.5 - cx).powi(2) + (py as f32 + 0.5 - cy).powi(2)).sqrt();
+            let ring = rings
+                .iter()
+                .map(|r| (thickness / 2.0 - (d - r).abs() + 0.5).clamp(0.0, 1.0))
+                .fold(0.0f32, f32::max);
+            let dot = (max_radius * 0.25 * (1.0 - t) - d + 0.5).clamp(0.0, 1.0) * 0.6;
+            let coverage = ring.max(dot) * alpha;
+            if coverage > 0.0 {
+                blend(frame, px, py, style.click_color, coverage);
+            }
+        }
+    }
+}
+
+fn fill_rounded_rect(frame: &mut Frame, x: f32, y: f32, w: f32, h: f32, radius: f32, rgba: [u8; 4]) {
+    for py in y.floor() as i64..(y + h).ceil() as i64 {
+        for px in x.floor() as i64..(x + w).ceil() as i64 {
+            // Distance outside the rounded corner, if in a corner region
+            let (fx, fy) = (px as f32 + 0.5, py as f32 + 0.5);
+            let qx = (x + radius - fx).max(fx - (x + w - radius)).max(0.0);
+            let qy = (y + radius - fy).max(fy - (y + h - radius)).max(0.0);
+            let coverage = (radius - (qx * qx + qy * qy).sqrt() + 0.5).clamp(0.0, 1.0);
+            blend(frame, px, py, rgba, coverage);
+        }
+    }
+}
+
+/// Alpha-blend an RGBA colour onto one BGRA pixel, ignoring out-of-bounds positions
+fn blend(frame: &mut Frame, x: i64, y: i64, rgba: [u8; 4], coverage: f32) {
+    if x < 0 || y < 0 || x >= frame.width as i64 || y >= frame.height as i64 {
+        return;
+    }
+    let a = (rgba[3] as f32 / 255.0) * coverage.clamp(0.0, 1.0);
+    let i = ((y as u32 * frame.width + x as u32) * 4) as usize;
+    let px = &mut frame.data[i..i + 3];
+    for (dst, src) in px.iter_mut().zip([rgba[2], rgba[1], rgba[0]]) {
+        *dst = (*dst as f32 * (1.0 - a) + src as f32 * a).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_font_parses_and_has_key_glyphs() {
+        let font = FontVec::try_from_vec(BUNDLED_FONT.to_vec()).unwrap();
+        for c in ['A', '⌘', '⇧', '←'] {
+            assert_ne!(font.glyph_id(c).0, 0, "missing {c}");
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Drives a frame source into an encoder at a constant frame rate
+use crate::capture::recording::audio::AudioCapture;
+use crate::capture::recording::encoder::Encoder;
+use crate::capture::recording::limits::{RecordingLimits, StopReason};
+use crate::capture::recording::overlay::OverlayCompositor;
+use crate::capture::recording::source::{FrameSource, SourceStatus};
+use crate::capture::recording::stats::{RecordingStats, StatsCollector, StatsListener};
+use crate::capture::recording::track::TrackRecorder;
+use crate::error::CaptureError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often a hidden source is checked for coming back
+const HIDDEN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often stats are reported while running
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Commands sent to a running pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineCommand {
+    /// Finish encoding and keep the output
+    Stop,
+    /// Tear down and discard the output
+    Cancel,
+    /// Stop capturing and encoding until `Resume`; the output continues seamlessly
+    Pause,
+    Resume,
+}
+
+/// How a pipeline run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineOutcome {
+    Finished { frames: u64, reason: StopReason, stats: RecordingStats },
+    Cancelled,
+}
+
+/// Position on the output's media timeline, derived from frames handed to the encoder.
+/// Cheap to clone and safe to read from other threads.
+#[derive(Debug, Clone)]
+pub struct MediaClock {
+    frames: Arc<AtomicU64>,
+    fps: u32,
+}
+
+impl MediaClock {
+    fn new(fps: u32) -> Self {
+        Self { frames: Arc::new(AtomicU64::new(0)), fps }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Acquire)
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.frames() as f64 / self.fps as f64
+    }
+}
+
+pub struct Pipeline {
+    source: Box<dyn FrameSource>,
+    encoder: Box<dyn Encoder>,
+    fps: u32,
+    clock: MediaClock,
+    limits: Option<RecordingLimits>,
+    audio: Vec<AudioCapture>,
+    overlay: Option<OverlayCompositor>,
+    track: Option<TrackRecorder>,
+    on_source_status: Option<Box<dyn Fn(SourceStatus) + Send>>,
+    stats: StatsCollector,
+    on_stats: Option<StatsListener>,
+}
+
+/// What ended a wait for a hidden source
+enum HiddenWait {
+    Live,
+    Closed,
+    Command(PipelineCommand),
+}
+
+impl Pipeline {
+    pub fn new(source: Box<dyn FrameSource>, encoder: Box<dyn Encoder>, fps: u32) -> Self {
+        let fps = fps.max(1);
+        Self {
+            source,
+            encoder,
+            fps,
+            clock: MediaClock::new(fps),
+            limits: None,
+            audio: Vec::new(),
+            overlay: None,
+            track: None,
+            on_source_status: None,
+            stats: StatsCollector::default(),
+            on_stats: None,
+        }
+    }
+
+    /// Stop on its own once any of `limits` is reached
+    pub fn with_limits(mut self, limits: RecordingLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    pub fn clock(&self) -> MediaClock {
+        self.clock.clone()
+    }
+
+    /// Audio sources feeding the encoder's audio inputs; paused and stopped with the video
+    pub fn with_audio(mut self, audio: Vec<AudioCapture>) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Draw click and keystroke overlays onto every frame before encoding
+    pub fn with_overlay(mut self, overlay: OverlayCompositor) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Record the pointer and clicks over the frames as they are captured
+    pub fn with_track(mut self, track: TrackRecorder) -> Self {
+        self.track = Some(track);
+        self
+    }
+
+    /// Called when the source becomes hidden (the run pauses itself) and live again
+    pub fn on_source_status(mut self, listener: impl Fn(SourceStatus) + Send + 'static) -> Self {
+        self.on_source_status = Some(Box::new(listener));
+        self
+    }
+
+    /// Called with fresh stats about once a second while frames are flowing
+    pub fn on_stats(mut self, listener: impl Fn(&RecordingStats) + Send + 'static) -> Self {
+        self.on_stats = Some(Box::new(listener));
+        self
+    }
+
+    fn stats(&mut self, frames: u64) -> RecordingStats {
+        self.stats.snapshot(frames, self.fps, self.encoder.as_ref(), &self.audio)
+    }
+
+    fn notify_source_status(&self, status: SourceStatus) {
+        if let Some(listener) = &self.on_source_status {
+            listener(status);
+        }
+    }
+
+    /// Release the source and discard the output without running
+    pub fn abort(mut self) {
+        self.source.stop();
+        self.stop_audio();
+        self.encoder.abort();
+    }
+
+    fn set_audio_paused(&self, paused: bool) {
+        for capture in &self.audio {
+            capture.set_paused(paused);
+        }
+    }
+
+    /// End every audio input so the encoder sees end-of-stream on all of them
+    fn stop_audio(&mut self) {
+        for capture in self.audio.drain(..) {
+            capture.stop();
+        }
+    }
+
+    /// Poll a hidden source until it is live or closed, honouring commands meanwhile
+    fn wait_while_hidden(&mut self, control: &Receiver<PipelineCommand>) -> HiddenWait {
+        loop {
+            match control.recv_timeout(HIDDEN_POLL_INTERVAL) {
+                Ok(PipelineCommand::Pause) => match wait_for_resume(control) {
+                    PipelineCommand::Resume => {}
+                    other => return HiddenWait::Command(other),
+                },
+                Ok(PipelineCommand::Resume) | Err(RecvTimeoutError::Timeout) => {}
+                Ok(command) => return HiddenWait::Command(command),
+                Err(RecvTimeoutError::Disconnected) => return HiddenWait::Command(PipelineCommand::Stop),
+            }
+            match self.source.status() {
+                SourceStatus::Live => return HiddenWait::Live,
+                SourceStatus::Closed => return HiddenWait::Closed,
+                SourceStatus::Hidden => {}
+            }
+        }
+    }
+
+    /// Run until a command arrives (or the control channel closes, which counts as Stop),
+    /// a limit is reached or the source closes. A hidden source pauses the run.
+    /// Blocks the calling thread; run it on a dedicated thread.
+    pub fn run(mut self, control: Receiver<PipelineCommand>) -> Result<PipelineOutcome, CaptureError> {
+        let fps = self.fps as f64;
+        let mut start = Instant::now();
+        let mut frames: u64 = 0;
+        let mut reason = StopReason::Requested;
+        let mut stats_due = Instant::now() + STATS_INTERVAL;
+
+        let ended = loop {
+            match self.source.status() {
+                SourceStatus::Live => {}
+                SourceStatus::Closed => {
+                    log::info!("Recording source closed");
+                    reason = StopReason::WindowClosed;
+                    break Ok(PipelineCommand::Stop);
+                }
+                SourceStatus::Hidden => {
+                    log::info!("Recording source hidden; pausing");
+                    let hidden_at = Instant::now();
+                    self.set_audio_paused(true);
+                    self.notify_source_status(SourceStatus::Hidden);
+                    match self.wait_while_hidden(&control) {
+                        HiddenWait::Live => {
+                            // Same as a user pause: the timeline continues without a gap
+                            start += hidden_at.elapsed();
+                            self.set_audio_paused(false);
+                            self.notify_source_status(SourceStatus::Live);
+                            continue;
+                        }
+                        HiddenWait::Closed => {
+                            reason = StopReason::WindowClosed;
+                            break Ok(PipelineCommand::Stop);
+                        }
+                        HiddenWait::Command(command) => break Ok(command),
+                    }
+                }
+            }
+
+            let mut frame = match self.source.capture() {
+                Ok(frame) => frame,
+                Err(e) => break Err(e),
+            };
+            self.stats.frame_captured();
+            if let Some(overlay) = self.overlay.as_mut() {
+                let source = &mut self.source;
+                overlay.apply(&mut frame, |x, y| source.map_point(x, y));
+            }
+            if let Some(track) = self.track.as_mut() {
+                let source = &mut self.source;
+                track.record(frames as f64 / fps, |x, y| source.map_point(x, y));
+            }
+
+            // The encoder assumes a constant rate, so if capture fell behind, repeat this
+            // frame until the media timeline catches up with the wall clock
+            let due = (start.elapsed().as_secs_f64() * fps) as u64 + 1;
+            let copies = due.saturating_sub(frames).max(1);
+            let written = (0..copies).try_for_each(|_| {
+                self.encoder.write_frame(&frame)?;
+                frames += 1;
+                self.clock.frames.store(frames, Ordering::Release);
+                Ok::<(), CaptureError>(())
+            });
+            if let Err(e) = written {
+                break Err(e);
+            }
+
+            if Instant::now() >= stats_due {
+                stats_due = Instant::now() + STATS_INTERVAL;
+                if self.on_stats.is_some() {
+                    let stats = self.stats(frames);
+                    if let Some(listener) = &self.on_stats {
+                        listener(&stats);
+                    }
+                }
+            }
+
+            if let Some(hit) = self.limits.as_mut().and_then(|l| l.check(frames as f64 / fps)) {
+                log::info!("Recording limit reached: {:?}", hit);
+                reason = hit;
+                break Ok(PipelineCommand::Stop);
+            }
+
+            let next_frame_at = start + Duration::from_secs_f64(frames as f64 / fps);
+            let command = match control.recv_timeout(next_frame_at.saturating_duration_since(Instant::now())) {
+                Ok(command) => command,
+                Err(RecvTimeoutError::Disconnected) => PipelineCommand::Stop,
+                Err(RecvTimeoutError::Timeout) => continue,
+            };
+            match command {
+                PipelineCommand::Stop | PipelineCommand::Cancel => break Ok(command),
+                PipelineCommand::Pause => {
+                    let paused_at = Instant::now();
+                    self.set_audio_paused(true);
+                    match wait_for_resume(&control) {
+                        // Shift the schedule by the pause so the media timeline picks up
+                        // right after the last frame: no gap, no repeated frames
+                        PipelineCommand::Resume => start += paused_at.elapsed(),
+                        other => break Ok(other),
+                    }
+                    self.set_audio_paused(false);
+                }
+                PipelineCommand::Resume => {}
+            }
+        };
+
+        // Sampled while audio is still attached, for the drift figures
+        let stats = self.stats(frames);
+        self.source.stop();
+        self.stop_audio();
+
+        match ended {
+            Ok(PipelineCommand::Stop) => {
+                self.encoder.finish()?;
+                log::info!(
+                    "Recording stats: {} frames, {} dropped ({:.2}%), max encoder queue {}, max A/V drift {:?} ms",
+                    stats.frames_written,
+                    stats.frames_dropped,
+                    stats.dropped_percent,
+                    stats.max_encoder_queue_depth,
+                    stats.max_av_drift_ms.map(|d| d.round())
+                );
+                Ok(PipelineOutcome::Finished { frames, reason, stats })
+            }
+            Ok(PipelineCommand::Cancel) => {
+                self.encoder.abort();
+                Ok(PipelineOutcome::Cancelled)
+            }
+            Ok(PipelineCommand::Pause | PipelineCommand::Resume) => unreachable!("pause never ends a run"),
+            Err(e) => {
+                self.encoder.abort();
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Block while paused, returning the command that ends the pause
+fn wait_for_resume(control: &Receiver<PipelineCommand>) -> PipelineCommand {
+    loop {
+        match control.recv() {
+            Ok(PipelineCommand::Pause) => {}
+            Ok(command) => return command,
+            Err(_) => return PipelineCommand::Stop,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Background replay buffer ("instant replay").
+//!
+//! A display is encoded continuously into a fixed ring of short MPEG-TS segments in
+//! a scratch directory; ffmpeg overwrites the oldest segment once the ring is full,
+//! so disk use is bounded by the configured duration and no frames are kept in
+//! memory. Saving joins the newest segments into an MP4 without re-encoding and
+//! leaves the buffer running. Replays are video only.
+use super::encoder::{self, EncoderSettings, SegmentSettings};
+use super::pipeline::{MediaClock, Pipeline, PipelineCommand, PipelineOutcome};
+use super::source;
+use crate::capture::config::{OutputFormat, RecordingConfig, RecordingTarget, ReplayConfig};
+use crate::error::CaptureError;
+use crate::events;
+use crate::services::export::ScratchDir;
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::services::storage::preview;
+use crate::state::app_state::AppState;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Length of one ring segment; saved replays are rounded up to whole segments
+const SEGMENT_SECONDS: u32 = 2;
+const MIN_DURATION_SECONDS: u32 = 5;
+const MAX_DURATION_SECONDS: u32 = 600;
+const SEGMENT_PREFIX: &str = "segment-";
+
+/// Payload of `REPLAY_STATE_CHANGED` and result of `get_replay_status`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStatus {
+    pub active: bool,
+    pub duration_seconds: u32,
+    /// Seconds a save would contain right now
+    pub buffered_seconds: f64,
+}
+
+/// A running replay buffer
+pub struct ReplayBuffer {
+    config: ReplayConfig,
+    control: Sender<PipelineCommand>,
+    thread: Option<JoinHandle<Result<PipelineOutcome, CaptureError>>>,
+    clock: MediaClock,
+    // Dropped after the pipeline thread has exited
+    scratch: ScratchDir,
+}
+
+impl ReplayBuffer {
+    /// Open the display and start buffering. Blocks while the source opens.
+    pub fn start(config: ReplayConfig) -> Result<Self, CaptureError> {
+        if !(MIN_DURATION_SECONDS..=MAX_DURATION_SECONDS).contains(&config.duration_seconds) {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Replay duration must be between {MIN_DURATION_SECONDS} and {MAX_DURATION_SECONDS} seconds"
+            )));
+        }
+        if config.fps == 0 || config.fps > 60 {
+            return Err(CaptureError::InvalidConfig(format!("Unsupported replay frame rate: {}", config.fps)));
+        }
+        config.quality.validate()?;
+
+        let recording = RecordingConfig {
+            fps: config.fps,
+            include_cursor: config.include_cursor,
+            show_mouse_clicks: false,
+            include_microphone: false,
+            include_system_audio: false,
+            ..RecordingConfig::default()
+        };
+        let target = RecordingTarget::Fullscreen { display_id: config.display_id };
+        let scratch = ScratchDir::new("replay")?;
+
+        let source = source::open_source(&target, &recording)?;
+        let (width, height) = source.size();
+        let encoder = encoder::open_encoder(&EncoderSettings {
+            width,
+            height,
+            fps: config.fps,
+            quality: config.quality,
+            output: scratch.join(&format!("{SEGMENT_PREFIX}%03d.ts")),
+            format: OutputFormat::Mp4,
+            audio: Vec::new(),
+            audio_mode: Default::default(),
+            segments: Some(SegmentSettings {
+                seconds: SEGMENT_SECONDS,
+                // Enough for the duration plus the segment being written
+                wrap: config.duration_seconds.div_ceil(SEGMENT_SECONDS) + 2,
+            }),
+        });
+        let encoder = match encoder {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                let mut source = source;
+                source.stop();
+                return Err(e);
+            }
+        };
+
+        let pipeline = Pipeline::new(source, encoder, config.fps);
+        let clock = pipeline.clock();
+        let (control, control_rx) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("replay-pipeline".into())
+            .spawn(move || pipeline.run(control_rx))?;
+
+        log::info!("Replay buffer started ({}s at {} fps)", config.duration_seconds, config.fps);
+        Ok(Self { config, control, thread: Some(thread), clock, scratch })
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let running = self.thread.as_ref().is_some_and(|t| !t.is_finished());
+        ReplayStatus {
+            active: running,
+            duration_seconds: self.config.duration_seconds,
+            buffered_seconds: self.clock.elapsed_seconds().min(self.config.duration_seconds as f64),
+        }
+    }
+
+    /// Stop buffering and discard everything buffered
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for ReplayBuffer {
+    fn drop(&mut self) {
+        let _ = self.control.send(PipelineCommand::Cancel);
+        if let Some(thread) = self.thread.take() {
+            if let Ok(Err(e)) = thread.join() {
+                log::warn!("Replay buffer ended with an error: {}", e);
+            }
+        }
+        log::info!("Replay buffer stopped");
+    }
+}
+
+/// Join the newest segments in `dir` covering `duration_seconds` into `output`
+fn join_segments(dir: &Path, duration_seconds: u32, output: &Path) -> Result<(), CaptureError> {
+    let mut segments: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(SEGMENT_PREFIX))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    if segments.is_empty() {
+        return Err(CaptureError::RecordingFailed("Nothing has been buffered yet".into()));
+    }
+    // Newest first; the newest is still being written and is cut at its last full packet
+    segments.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    segments.truncate(duration_seconds.div_ceil(SEGMENT_SECONDS) as usize + 1);
+    segments.reverse();
+
+    // Snapshot them before ffmpeg wraps around and overwrites the oldest
+    let snapshot = ScratchDir::new("replay-save")?;
+    let mut list = String::new();
+    for (i, (_, path)) in segments.iter().enumerate() {
+        let copy = snapshot.join(&format!("{i:03}.ts"));
+        std::fs::copy(path, &copy)?;
+        let _ = writeln!(list, "file '{}'", copy.to_string_lossy().replace('\'', r"'\''"));
+    }
+    let list_path = snapshot.join("segments.txt");
+    std::fs::write(&list_path, list)?;
+
+    let args = [
+        "-f".into(),
+        "concat".into(),
+        "-safe".into(),
+        "0".into(),
+        "-i".into(),
+        list_path.to_string_lossy().into_owned(),
+        "-c".into(),
+        "copy".into(),
+        "-movflags".into(),
+        "+faststart".into(),
+        output.to_string_lossy().into_owned(),
+    ];
+    let never = AtomicBool::new(false);
+    let result = crate::services::ffmpeg::run(args, 0.0, &never, |_| {})
+        .map_err(|e| CaptureError::RecordingFailed(format!("Could not save replay: {e}")));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
+
+fn emit_status(app: &AppHandle, status: ReplayStatus) {
+    let _ = app.emit(events::REPLAY_STATE_CHANGED, status);
+}
+
+/// Start the replay buffer, replacing one that has died
+pub async fn start(app: &AppHandle, config: ReplayConfig) -> Result<ReplayStatus, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    {
+        let mut replay = state.replay.lock().unwrap();
+        match replay.as_ref().map(|r| r.status().active) {
+            Some(true) => return Err(CaptureError::RecordingFailed("The replay buffer is already running".into())),
+            Some(false) => {
+                if let Some(dead) = replay.take() {
+                    dead.stop();
+                }
+            }
+            None => {}
+        }
+    }
+
+    let buffer = tokio::task::spawn_blocking(move || ReplayBuffer::start(config))
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))??;
+    let status = buffer.status();
+    let mut replay = state.replay.lock().unwrap();
+    if replay.is_some() {
+        // Lost a race with another start
+        buffer.stop();
+        return Err(CaptureError::RecordingFailed("The replay buffer is already running".into()));
+    }
+    *replay = Some(buffer);
+    drop(replay);
+    emit_status(app, status.clone());
+    Ok(status)
+}
+
+/// Stop the replay buffer. Returns false if it wasn't running.
+pub async fn stop(app: &AppHandle) -> Result<bool, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let Some(buffer) = state.replay.lock().unwrap().take() else {
+        return Ok(false);
+    };
+    let config = buffer.config.clone();
+    tokio::task::spawn_blocking(move || buffer.stop())
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))?;
+    emit_status(
+        app,
+        ReplayStatus { active: false, duration_seconds: config.duration_seconds, buffered_seconds: 0.0 },
+    );
+    Ok(true)
+}
+
+pub fn status(app: &AppHandle) -> Option<ReplayStatus> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let replay = state.replay.lock().unwrap();
+    replay.as_ref().map(|r| r.status())
+}
+
+/// Save the buffered replay as a recording in history
+pub async fn save(app: &AppHandle) -> Result<CaptureItem, CaptureError> {
+    let state: tauri::State<'_, AppState> = app.state();
+    let (filename, output) = {
+        let storage = state.storage.lock().unwrap();
+        let filename = storage.generate_filename(&CaptureType::Recording, "mp4");
+        let dir = storage.screenshots_dir();
+        std::fs::create_dir_all(&dir)?;
+        (filename.clone(), dir.join(filename))
+    };
+
+    let (dir, duration_seconds) = {
+        let replay = state.replay.lock().unwrap();
+        let buffer = replay.as_ref().ok_or(CaptureError::RecordingNotActive)?;
+        if !buffer.status().active {
+            return Err(CaptureError::RecordingFailed("The replay buffer has stopped".into()));
+        }
+        (buffer.scratch.path().to_path_buf(), buffer.config.duration_seconds)
+    };
+    // The buffer keeps running while the ring is read
+    tokio::task::spawn_blocking(move || join_segments(&dir, duration_seconds, &output))
+        .await
+        .map_err(|e| CaptureError::RecordingFailed(e.to_string()))??;
+
+    let item = CaptureItem::new_recording(filename);
+    {
+        let mut storage = state.storage.lock().unwrap();
+        storage.history.add(item.clone());
+        storage.save_history()?;
+    }
+    log::info!("Replay saved: {}", item.filename);
+    let _ = app.emit(events::CAPTURE_COMPLETED, &item);
+    preview::generate_in_background(app, item.id.clone());
+    Ok(item)
+}
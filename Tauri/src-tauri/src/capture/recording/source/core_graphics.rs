@@ -0,0 +1,159 @@
+//! CoreGraphics frame source: one CGDisplay/CGWindowList image per frame
+use super::{Frame, FrameSource, Placement, SourceStatus};
+use crate::capture::config::{CaptureRect, RecordingConfig, RecordingTarget, WindowScaling};
+use crate::capture::screenshot::{grab_display_rgba, grab_window_rgba};
+use crate::error::CaptureError;
+
+pub struct CoreGraphicsSource {
+    target: RecordingTarget,
+    width: u32,
+    height: u32,
+    scaling: WindowScaling,
+    /// Window targets: size of the last window image and where it went in the frame
+    window_pixels: (u32, u32),
+    placement: Placement,
+}
+
+/// Window-list entry for one window: whether it is on screen and its bounds in global points
+struct WindowState {
+    on_screen: bool,
+    bounds: (f64, f64, f64, f64),
+}
+
+/// `None` once the window has been closed
+fn window_state(window_id: u32) -> Option<WindowState> {
+    use core_foundation::array::CFArray;
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::dictionary::CFDictionary;
+    use core_foundation::number::CFNumber;
+    use core_foundation::string::CFString;
+
+    extern "C" {
+        fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> core_foundation::base::CFTypeRef;
+    }
+
+    // kCGWindowListOptionIncludingWindow
+    let cf_ref = unsafe { CGWindowListCopyWindowInfo(1 << 3, window_id) };
+    if cf_ref.is_null() {
+        return None;
+    }
+    let array: CFArray<CFType> = unsafe { CFArray::wrap_under_create_rule(cf_ref as _) };
+    let item = array.get(0)?;
+    let dict: CFDictionary<CFString, CFType> = unsafe { CFDictionary::wrap_under_get_rule(item.as_CFTypeRef() as _) };
+
+    // The key is absent for windows that are off screen
+    let on_screen = dict
+        .find(CFString::new("kCGWindowIsOnscreen"))
+        .and_then(|v| v.downcast::<CFBoolean>())
+        .is_some_and(bool::from);
+    let bounds = dict.find(CFString::new("kCGWindowBounds")).map(|v| {
+        let bounds: CFDictionary<CFString, CFType> = unsafe { CFDictionary::wrap_under_get_rule(v.as_CFTypeRef() as _) };
+        let number = |key: &str| {
+            bounds
+                .find(CFString::new(key))
+                .and_then(|n| n.downcast::<CFNumber>())
+                .and_then(|n| n.to_f64())
+                .unwrap_or(0.0)
+        };
+        (number("X"), number("Y"), number("Width"), number("Height"))
+    });
+    Some(WindowState { on_screen, bounds: bounds.unwrap_or_default() })
+}
+
+impl CoreGraphicsSource {
+    pub fn open(target: &RecordingTarget, config: &RecordingConfig) -> Result<Self, CaptureError> {
+        let mut source = Self {
+            target: target.clone(),
+            width: 0,
+            height: 0,
+            scaling: config.window_scaling,
+            window_pixels: (0, 0),
+            placement: Placement::IDENTITY,
+        };
+        // Grab once up front to validate the target and fix the output size
+        let first = source.grab()?;
+        source.width = first.width();
+        source.height = first.height();
+        source.window_pixels = (source.width, source.height);
+        Ok(source)
+    }
+
+    fn grab(&self) -> Result<image::RgbaImage, CaptureError> {
+        match &self.target {
+            RecordingTarget::Fullscreen { display_id } => grab_display_rgba(*display_id, None),
+            RecordingTarget::Area { x, y, width, height, display_id } => {
+                let rect = CaptureRect { x: *x, y: *y, width: *width, height: *height };
+                grab_display_rgba(Some(*display_id), Some(&rect))
+            }
+            RecordingTarget::Window { window_id } => grab_window_rgba(*window_id),
+        }
+    }
+}
+
+impl FrameSource for CoreGraphicsSource {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        let image = self.grab()?;
+        let (width, height) = image.dimensions();
+        let mut data = image.into_raw();
+        for px in data.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+        // Windows can change size mid-recording; keep the encoder's frame size fixed
+        let (frame, placement) = Frame { width, height, data }.fit(self.width, self.height, self.scaling);
+        self.window_pixels = (width, height);
+        self.placement = placement;
+        Ok(frame)
+    }
+
+    fn status(&mut self) -> SourceStatus {
+        let RecordingTarget::Window { window_id } = self.target else {
+            return SourceStatus::Live;
+        };
+        match window_state(window_id) {
+            None => SourceStatus::Closed,
+            Some(state) if !state.on_screen => SourceStatus::Hidden,
+            Some(_) => SourceStatus::Live,
+        }
+    }
+
+    fn map_point(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        use core_graphics::display::CGDisplay;
+
+        // Captured region in global points; frames are in pixels
+        let display_bounds = |id: Option<u32>| CGDisplay::new(id.unwrap_or_else(|| CGDisplay::main().id)).bounds();
+        let (left, top, width, height) = match &self.target {
+            RecordingTarget::Fullscreen { display_id } => {
+                let b = display_bounds(*display_id);
+                (b.origin.x, b.origin.y, b.size.width, b.size.height)
+            }
+            RecordingTarget::Area { x, y, width, height, display_id } => {
+                let b = display_bounds(Some(*display_id));
+                (b.origin.x + x, b.origin.y + y, *width, *height)
+            }
+            RecordingTarget::Window { window_id } => {
+                // Window images are in pixels at the window's current size, then fitted
+                let (left, top, width, height) = window_state(*window_id)?.bounds;
+                let (pixels_x, pixels_y) = self.window_pixels;
+                if width <= 0.0 || height <= 0.0 || x < left || y < top || x >= left + width || y >= top + height {
+                    return None;
+                }
+                return Some(self.placement.map(
+                    (x - left) * pixels_x as f64 / width,
+                    (y - top) * pixels_y as f64 / height,
+                ));
+            }
+        };
+        if width <= 0.0 || height <= 0.0 || x < left || y < top || x >= left + width || y >= top + height {
+            return None;
+        }
+        Some((
+            (x - left) * self.width as f64 / width,
+            (y - top) * self.height as f64 / height,
+        ))
+    }
+}
@@ -0,0 +1,162 @@
+//! Frame sources: per-backend producers of raw frames for the recording pipeline
+use crate::capture::config::{RecordingConfig, RecordingTarget, WindowScaling};
+use crate::error::CaptureError;
+use serde::Serialize;
+
+#[cfg(target_os = "macos")]
+pub mod core_graphics;
+#[cfg(target_os = "linux")]
+pub mod portal;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+/// A single captured frame as tightly packed BGRA rows (`data.len() == width * height * 4`)
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    /// Copy out a sub-rectangle, clamped to the frame bounds
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Frame {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for row in y..y + height {
+            let start = ((row * self.width + x) * 4) as usize;
+            data.extend_from_slice(&self.data[start..start + (width * 4) as usize]);
+        }
+        Frame { width, height, data }
+    }
+
+    /// Scale into a `width` x `height` frame, letterboxed on black or stretched
+    pub fn fit(self, width: u32, height: u32, scaling: WindowScaling) -> (Frame, Placement) {
+        if (self.width, self.height) == (width, height) {
+            return (self, Placement::IDENTITY);
+        }
+        let (scaled_width, scaled_height) = match scaling {
+            WindowScaling::Stretch => (width, height),
+            WindowScaling::Letterbox => {
+                let scale = (width as f64 / self.width as f64).min(height as f64 / self.height as f64);
+                (
+                    ((self.width as f64 * scale).round() as u32).clamp(1, width),
+                    ((self.height as f64 * scale).round() as u32).clamp(1, height),
+                )
+            }
+        };
+        let placement = Placement {
+            x: ((width - scaled_width) / 2) as f64,
+            y: ((height - scaled_height) / 2) as f64,
+            scale_x: scaled_width as f64 / self.width as f64,
+            scale_y: scaled_height as f64 / self.height as f64,
+        };
+
+        // Channel order doesn't matter to the filter, so BGRA goes through as RGBA
+        let Some(image) = image::RgbaImage::from_raw(self.width, self.height, self.data) else {
+            return (Frame::black(width, height), placement);
+        };
+        let scaled = image::imageops::resize(&image, scaled_width, scaled_height, image::imageops::FilterType::Triangle);
+        if (scaled_width, scaled_height) == (width, height) {
+            return (Frame { width, height, data: scaled.into_raw() }, placement);
+        }
+
+        let mut frame = Frame::black(width, height);
+        let (left, top) = (placement.x as u32, placement.y as u32);
+        let row_len = (scaled_width * 4) as usize;
+        for (row, pixels) in scaled.as_raw().chunks_exact(row_len).enumerate() {
+            let start = (((top + row as u32) * width + left) * 4) as usize;
+            frame.data[start..start + row_len].copy_from_slice(pixels);
+        }
+        (frame, placement)
+    }
+
+    fn black(width: u32, height: u32) -> Frame {
+        let data = [0, 0, 0, 255].repeat((width * height) as usize);
+        Frame { width, height, data }
+    }
+}
+
+/// Where `Frame::fit` put the source pixels in the output frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub x: f64,
+    pub y: f64,
+    pub scale_x: f64,
+    pub scale_y: f64,
+}
+
+impl Placement {
+    pub const IDENTITY: Placement = Placement { x: 0.0, y: 0.0, scale_x: 1.0, scale_y: 1.0 };
+
+    /// Map a point in source pixels to output pixels
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.x + x * self.scale_x, self.y + y * self.scale_y)
+    }
+}
+
+/// Whether a source's target can be captured right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceStatus {
+    Live,
+    /// Temporarily not visible, e.g. a minimised window; the pipeline pauses
+    Hidden,
+    /// Gone for good, e.g. a closed window; the pipeline stops
+    Closed,
+}
+
+/// Produces frames for one recording session.
+///
+/// The pipeline calls `capture` once per output frame at the configured FPS, so
+/// implementations return the most recent screen contents rather than queueing.
+pub trait FrameSource: Send {
+    /// Frame size in pixels; fixed for the lifetime of the source
+    fn size(&self) -> (u32, u32);
+
+    /// Grab the current contents of the target
+    fn capture(&mut self) -> Result<Frame, CaptureError>;
+
+    /// Checked before every frame; sources whose target can't disappear are always live
+    fn status(&mut self) -> SourceStatus {
+        SourceStatus::Live
+    }
+
+    /// Map a global pointer position (as reported by `capture::input`) to frame pixels.
+    /// `None` when it falls outside the captured area or the source can't tell.
+    fn map_point(&mut self, _x: f64, _y: f64) -> Option<(f64, f64)> {
+        None
+    }
+
+    /// Release capture resources. Called once when the session ends.
+    fn stop(&mut self) {}
+}
+
+/// Open the frame source for the current platform and desktop session.
+/// May block while the user answers a system picker (e.g. the ScreenCast portal).
+pub fn open_source(
+    target: &RecordingTarget,
+    config: &RecordingConfig,
+) -> Result<Box<dyn FrameSource>, CaptureError> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(Box::new(core_graphics::CoreGraphicsSource::open(target, config)?))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use crate::capture::backend::LinuxBackend;
+
+        match LinuxBackend::detect() {
+            LinuxBackend::X11 => x11::open(target, config),
+            LinuxBackend::Portal => Ok(Box::new(portal::PortalSource::open(target, config)?)),
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (target, config);
+        Err(CaptureError::RecordingFailed("Recording is not supported on this platform".into()))
+    }
+}
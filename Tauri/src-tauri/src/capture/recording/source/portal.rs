@@ -0,0 +1,203 @@
+//! ScreenCast portal frame source for Wayland sessions.
+//!
+//! The portal hands us a PipeWire remote; a `gst-launch-1.0` child converts the
+//! negotiated stream to raw BGRA on stdout, and a reader thread keeps only the
+//! most recent frame.
+use super::{Frame, FrameSource, SourceStatus};
+use crate::capture::config::{RecordingConfig, RecordingTarget, WindowScaling};
+use crate::capture::portal::{Portal, ScreenCastSession, SOURCE_MONITOR, SOURCE_WINDOW};
+use crate::error::CaptureError;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How long to wait for PipeWire to deliver the first frame
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct LatestFrame {
+    data: Option<Vec<u8>>,
+    closed: bool,
+}
+
+pub struct PortalSource {
+    // Dropping the session closes it, so it must live as long as the stream
+    _session: ScreenCastSession,
+    child: Child,
+    latest: Arc<(Mutex<LatestFrame>, Condvar)>,
+    stream_width: u32,
+    stream_height: u32,
+    crop: Option<(u32, u32, u32, u32)>,
+    /// Window streams ending means the window was closed
+    window: bool,
+}
+
+impl PortalSource {
+    pub fn open(target: &RecordingTarget, config: &RecordingConfig) -> Result<Self, CaptureError> {
+        let window = matches!(target, RecordingTarget::Window { .. });
+        let source_types = if window { SOURCE_WINDOW } else { SOURCE_MONITOR };
+
+        let session = Portal::session()?.screencast(source_types, config.include_cursor)?;
+        let stream = session.streams[0].clone();
+        if stream.width == 0 || stream.height == 0 {
+            return Err(CaptureError::RecordingFailed("ScreenCast stream has no size".into()));
+        }
+
+        // Area targets record the monitor the user picked and crop to the rect
+        let crop = match target {
+            RecordingTarget::Area { x, y, width, height, .. } => {
+                Some((*x as u32, *y as u32, *width as u32, *height as u32))
+            }
+            _ => None,
+        };
+
+        let gst = crate::services::ffmpeg::find_binary("gst-launch-1.0", "SCREENCAPTURE_GST_LAUNCH")
+            .ok_or_else(|| {
+                CaptureError::RecordingFailed(
+                    "gst-launch-1.0 not found; install GStreamer with the PipeWire plugin".into(),
+                )
+            })?;
+
+        let remote = session.open_pipewire_remote()?;
+        let remote_fd = remote.as_raw_fd();
+
+        let mut command = Command::new(gst);
+        command
+            .args([
+                "-q",
+                "pipewiresrc",
+                "fd=3",
+                &format!("path={}", stream.node_id),
+                "always-copy=true",
+                "!",
+                "videoconvert",
+                "!",
+                // The compositor follows the window; a resized window is scaled back to
+                // the negotiated size, with borders unless stretching
+                "videoscale",
+                match config.window_scaling {
+                    WindowScaling::Letterbox => "add-borders=true",
+                    WindowScaling::Stretch => "add-borders=false",
+                },
+                "!",
+                &format!(
+                    "video/x-raw,format=BGRA,width={},height={}",
+                    stream.width, stream.height
+                ),
+                "!",
+                "fdsink",
+                "fd=1",
+                "sync=false",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        // Hand the PipeWire remote to the child as fd 3
+        unsafe {
+            command.pre_exec(move || {
+                let result = if remote_fd == 3 {
+                    libc::fcntl(3, libc::F_SETFD, 0)
+                } else {
+                    libc::dup2(remote_fd, 3)
+                };
+                if result < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let mut child = command.spawn()?;
+        drop(remote);
+
+        let mut stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| CaptureError::RecordingFailed("gst-launch has no stdout".into()))?;
+        let frame_len = (stream.width * stream.height * 4) as usize;
+        let latest = Arc::new((Mutex::new(LatestFrame::default()), Condvar::new()));
+        let reader_latest = latest.clone();
+
+        std::thread::Builder::new()
+            .name("portal-frame-reader".into())
+            .spawn(move || {
+                let (slot, ready) = &*reader_latest;
+                loop {
+                    let mut buf = vec![0u8; frame_len];
+                    if stdout.read_exact(&mut buf).is_err() {
+                        break;
+                    }
+                    slot.lock().unwrap().data = Some(buf);
+                    ready.notify_all();
+                }
+                slot.lock().unwrap().closed = true;
+                ready.notify_all();
+            })?;
+
+        Ok(Self {
+            _session: session,
+            child,
+            latest,
+            stream_width: stream.width,
+            stream_height: stream.height,
+            crop,
+            window,
+        })
+    }
+}
+
+impl FrameSource for PortalSource {
+    fn size(&self) -> (u32, u32) {
+        match self.crop {
+            Some((x, y, w, h)) => (
+                w.min(self.stream_width.saturating_sub(x)),
+                h.min(self.stream_height.saturating_sub(y)),
+            ),
+            None => (self.stream_width, self.stream_height),
+        }
+    }
+
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        let (slot, ready) = &*self.latest;
+        let (latest, timeout) = ready
+            .wait_timeout_while(slot.lock().unwrap(), FIRST_FRAME_TIMEOUT, |f| {
+                f.data.is_none() && !f.closed
+            })
+            .unwrap();
+
+        let data = match &latest.data {
+            Some(data) if !latest.closed => data.clone(),
+            _ if latest.closed => {
+                return Err(CaptureError::RecordingFailed("ScreenCast stream ended".into()))
+            }
+            _ => {
+                debug_assert!(timeout.timed_out());
+                return Err(CaptureError::RecordingFailed("No frames from ScreenCast stream".into()));
+            }
+        };
+        drop(latest);
+
+        let frame = Frame { width: self.stream_width, height: self.stream_height, data };
+        Ok(match self.crop {
+            Some((x, y, w, h)) => frame.crop(x, y, w, h),
+            None => frame,
+        })
+    }
+
+    fn status(&mut self) -> SourceStatus {
+        if self.window && self.latest.0.lock().unwrap().closed {
+            SourceStatus::Closed
+        } else {
+            SourceStatus::Live
+        }
+    }
+
+    fn stop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
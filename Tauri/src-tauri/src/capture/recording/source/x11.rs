@@ -0,0 +1,286 @@
+//! X11 frame sources: GetImage on the root window, or on a window's Composite pixmap
+use super::{Frame, FrameSource, Placement, SourceStatus};
+use crate::capture::config::{RecordingConfig, RecordingTarget, WindowScaling};
+use crate::capture::x11::{x11_error, X11Session};
+use crate::error::CaptureError;
+use x11rb::connection::Connection;
+use x11rb::protocol::composite::{ConnectionExt as _, Redirect};
+use x11rb::protocol::xproto::{ChangeWindowAttributesAux, ConnectionExt as _, EventMask, MapState, Pixmap, Window};
+use x11rb::protocol::Event;
+
+/// Open the source for `target`: windows are followed, everything else is a fixed
+/// region of the root window
+pub fn open(target: &RecordingTarget, config: &RecordingConfig) -> Result<Box<dyn FrameSource>, CaptureError> {
+    let session = X11Session::connect()?;
+    let (x, y, width, height) = match target {
+        RecordingTarget::Fullscreen { display_id } => {
+            let m = session.monitor(*display_id)?;
+            (m.x, m.y, m.width, m.height)
+        }
+        RecordingTarget::Area { x, y, width, height, display_id } => {
+            let m = session.monitor(Some(*display_id))?;
+            (m.x + *x as i32, m.y + *y as i32, *width as u32, *height as u32)
+        }
+        RecordingTarget::Window { window_id } => {
+            return Ok(Box::new(X11WindowSource::open(session, *window_id, config.window_scaling)?));
+        }
+    };
+
+    // GetImage fails with BadMatch for any part outside the root window
+    let (root_width, root_height) = session.root_size()?;
+    let (left, top) = (x.max(0), y.max(0));
+    let right = (x + width as i32).min(root_width as i32);
+    let bottom = (y + height as i32).min(root_height as i32);
+    if right <= left || bottom <= top {
+        return Err(CaptureError::InvalidConfig("Recording area is empty or off screen".into()));
+    }
+    if (left, top, right, bottom) != (x, y, x + width as i32, y + height as i32) {
+        log::warn!("Recording area {}x{}+{}+{} clipped to the screen", width, height, x, y);
+    }
+    Ok(Box::new(X11Source {
+        session,
+        x: left,
+        y: top,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    }))
+}
+
+/// A fixed region of the root window
+pub struct X11Source {
+    session: X11Session,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl FrameSource for X11Source {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        let data = self
+            .session
+            .grab_bgra(self.session.root, self.x, self.y, self.width, self.height)?;
+        Ok(Frame { width: self.width, height: self.height, data })
+    }
+
+    fn map_point(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let (fx, fy) = (x - self.x as f64, y - self.y as f64);
+        let inside = fx >= 0.0 && fy >= 0.0 && fx < self.width as f64 && fy < self.height as f64;
+        inside.then_some((fx, fy))
+    }
+}
+
+/// A client window, followed as it moves and resizes.
+///
+/// With the Composite extension the window is redirected so its contents are read
+/// from an off-screen pixmap, unaffected by whatever overlaps it. Frames keep the
+/// window's size at the start; later sizes are fitted into it.
+///
+/// The window's size and visibility are kept up to date from its structure and
+/// property events, so a frame costs one GetImage round-trip.
+pub struct X11WindowSource {
+    session: X11Session,
+    window: Window,
+    composite: bool,
+    /// Named for the window's current size; renamed after a resize or remap
+    pixmap: Option<(Pixmap, u32, u32)>,
+    width: u32,
+    height: u32,
+    scaling: WindowScaling,
+    placement: Placement,
+    /// The window's current size
+    current: (u32, u32),
+    mapped: bool,
+    /// `_NET_WM_STATE_HIDDEN`, i.e. minimised
+    hidden: bool,
+    closed: bool,
+    /// Top-left corner in root coordinates; looked up again after the window moves
+    origin: Option<(f64, f64)>,
+}
+
+impl X11WindowSource {
+    fn open(session: X11Session, window: Window, scaling: WindowScaling) -> Result<Self, CaptureError> {
+        // Listen before reading the initial state so no change falls in between
+        let events = EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE;
+        session
+            .conn
+            .change_window_attributes(window, &ChangeWindowAttributesAux::new().event_mask(events))
+            .map_err(x11_error)?
+            .check()
+            .map_err(|_| CaptureError::CaptureFailed(format!("Window {} not found", window)))?;
+        let (width, height) = session.window_size(window)?;
+        if width == 0 || height == 0 {
+            return Err(CaptureError::InvalidConfig("Recording area is empty".into()));
+        }
+
+        let composite = session
+            .conn
+            .composite_query_version(0, 4)
+            .map_err(x11_error)?
+            .reply()
+            .is_ok_and(|v| v.major_version > 0 || v.minor_version >= 2);
+        if composite {
+            // Automatic keeps the window on screen as usual; the redirect ends with our connection
+            session
+                .conn
+                .composite_redirect_window(window, Redirect::AUTOMATIC)
+                .map_err(x11_error)?
+                .check()
+                .map_err(x11_error)?;
+        } else {
+            log::warn!("X11 Composite unavailable; windows overlapping the recording will show in it");
+        }
+
+        let mapped = session
+            .conn
+            .get_window_attributes(window)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .map_state
+            == MapState::VIEWABLE;
+        let hidden = session.is_hidden(window)?;
+
+        Ok(Self {
+            session,
+            window,
+            composite,
+            pixmap: None,
+            width,
+            height,
+            scaling,
+            placement: Placement::IDENTITY,
+            current: (width, height),
+            mapped,
+            hidden,
+            closed: false,
+            origin: None,
+        })
+    }
+
+    /// Apply the events that arrived since the last frame, without blocking
+    fn poll_events(&mut self) {
+        let wm_state = self.session.atoms._NET_WM_STATE;
+        loop {
+            let event = match self.session.conn.poll_for_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("X11 connection lost while recording a window: {}", e);
+                    self.closed = true;
+                    break;
+                }
+            };
+            match event {
+                Event::ConfigureNotify(e) if e.window == self.window => {
+                    self.current = (e.width as u32, e.height as u32);
+                    self.origin = None;
+                }
+                Event::MapNotify(e) if e.window == self.window => {
+                    self.mapped = true;
+                    self.origin = None;
+                }
+                Event::UnmapNotify(e) if e.window == self.window => {
+                    // An unmapped window's pixmap is gone; name a fresh one when it returns
+                    self.mapped = false;
+                    self.release_pixmap();
+                }
+                Event::DestroyNotify(e) if e.window == self.window => self.closed = true,
+                Event::PropertyNotify(e) if e.window == self.window && e.atom == wm_state => {
+                    self.hidden = self.session.is_hidden(self.window).unwrap_or(false);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn release_pixmap(&mut self) {
+        if let Some((pixmap, _, _)) = self.pixmap.take() {
+            let _ = self.session.conn.free_pixmap(pixmap);
+        }
+    }
+
+    /// The drawable holding the window's pixels at `width` x `height`
+    fn drawable(&mut self, width: u32, height: u32) -> Result<u32, CaptureError> {
+        if !self.composite {
+            return Ok(self.window);
+        }
+        if let Some((pixmap, w, h)) = self.pixmap {
+            if (w, h) == (width, height) {
+                return Ok(pixmap);
+            }
+        }
+        self.release_pixmap();
+        let conn = &self.session.conn;
+        let pixmap = conn.generate_id().map_err(x11_error)?;
+        conn.composite_name_window_pixmap(self.window, pixmap)
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+        self.pixmap = Some((pixmap, width, height));
+        Ok(pixmap)
+    }
+}
+
+impl FrameSource for X11WindowSource {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn status(&mut self) -> SourceStatus {
+        self.poll_events();
+        if self.closed {
+            SourceStatus::Closed
+        } else if !self.mapped || self.hidden {
+            SourceStatus::Hidden
+        } else {
+            SourceStatus::Live
+        }
+    }
+
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        self.poll_events();
+        let (width, height) = self.current;
+        if width == 0 || height == 0 {
+            return Err(CaptureError::CaptureFailed("Window has no size".into()));
+        }
+        let drawable = self.drawable(width, height)?;
+        let data = self.session.grab_bgra(drawable, 0, 0, width, height)?;
+        let (frame, placement) = Frame { width, height, data }.fit(self.width, self.height, self.scaling);
+        self.placement = placement;
+        Ok(frame)
+    }
+
+    fn map_point(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        // Window managers reparent clients, so event coordinates aren't root-relative;
+        // ask the server once per move instead
+        let (left, top) = match self.origin {
+            Some(origin) => origin,
+            None => {
+                let reply = self
+                    .session
+                    .conn
+                    .translate_coordinates(self.window, self.session.root, 0, 0)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+                *self.origin.insert((reply.dst_x as f64, reply.dst_y as f64))
+            }
+        };
+        let (fx, fy) = self.placement.map(x - left, y - top);
+        let inside = fx >= 0.0 && fy >= 0.0 && fx < self.width as f64 && fy < self.height as f64;
+        inside.then_some((fx, fy))
+    }
+
+    fn stop(&mut self) {
+        self.release_pixmap();
+        if self.composite {
+            let _ = self.session.conn.composite_unredirect_window(self.window, Redirect::AUTOMATIC);
+        }
+        let _ = self.session.conn.flush();
+    }
+}
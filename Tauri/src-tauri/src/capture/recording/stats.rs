@@ -0,0 +1,116 @@
+//! Recording health: frame delivery, encoder backlog and audio/video sync
+use crate::capture::recording::audio::AudioCapture;
+use crate::capture::recording::encoder::Encoder;
+use serde::{Deserialize, Serialize};
+
+/// Snapshot of a session's health, emitted while recording and saved with the item
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordingStats {
+    /// Media time covered so far
+    pub elapsed_seconds: f64,
+    /// Fresh frames grabbed from the source
+    pub frames_captured: u64,
+    /// Frames handed to the encoder, including repeats
+    pub frames_written: u64,
+    /// Frames the encoder has confirmed encoding
+    pub frames_encoded: u64,
+    /// Output frames filled by repeating the previous frame because capture fell behind
+    pub frames_dropped: u64,
+    pub dropped_percent: f64,
+    /// Frames written but not yet encoded
+    pub encoder_queue_depth: u64,
+    pub max_encoder_queue_depth: u64,
+    /// Audio minus video media time across sources, largest magnitude; `None` without audio
+    pub av_drift_ms: Option<f64>,
+    pub max_av_drift_ms: Option<f64>,
+    pub output_bytes: u64,
+    /// Average bits per second of output so far
+    pub output_bitrate: u64,
+}
+
+pub type StatsListener = Box<dyn Fn(&RecordingStats) + Send>;
+
+/// Accumulates `RecordingStats` for one pipeline run
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    frames_captured: u64,
+    max_queue_depth: u64,
+    max_drift_ms: Option<f64>,
+}
+
+impl StatsCollector {
+    pub fn frame_captured(&mut self) {
+        self.frames_captured += 1;
+    }
+
+    pub fn snapshot(
+        &mut self,
+        frames_written: u64,
+        fps: u32,
+        encoder: &dyn Encoder,
+        audio: &[AudioCapture],
+    ) -> RecordingStats {
+        let elapsed_seconds = frames_written as f64 / fps.max(1) as f64;
+        // Encoders that can't report progress are treated as keeping up
+        let progress = encoder.progress();
+        let frames_encoded = progress.map_or(frames_written, |p| p.frames.min(frames_written));
+        let output_bytes = progress.map_or(0, |p| p.bytes);
+        let queue_depth = frames_written - frames_encoded;
+        self.max_queue_depth = self.max_queue_depth.max(queue_depth);
+
+        let drift_ms = audio
+            .iter()
+            .filter_map(|a| a.media_seconds())
+            .map(|seconds| (seconds - elapsed_seconds) * 1000.0)
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()));
+        if let Some(drift) = drift_ms {
+            if self.max_drift_ms.is_none_or(|max| drift.abs() > max.abs()) {
+                self.max_drift_ms = Some(drift);
+            }
+        }
+
+        let frames_dropped = frames_written.saturating_sub(self.frames_captured);
+        let encoded_seconds = frames_encoded as f64 / fps.max(1) as f64;
+        RecordingStats {
+            elapsed_seconds,
+            frames_captured: self.frames_captured,
+            frames_written,
+            frames_encoded,
+            frames_dropped,
+            dropped_percent: percent(frames_dropped, frames_written),
+            encoder_queue_depth: queue_depth,
+            max_encoder_queue_depth: self.max_queue_depth,
+            av_drift_ms: drift_ms,
+            max_av_drift_ms: self.max_drift_ms,
+            output_bytes,
+            output_bitrate: bitrate(output_bytes, encoded_seconds),
+        }
+    }
+}
+
+impl RecordingStats {
+    /// Final figures once the encoder has flushed and the file is `output_bytes` long
+    pub fn finished(mut self, output_bytes: u64) -> Self {
+        self.frames_encoded = self.frames_written;
+        self.encoder_queue_depth = 0;
+        self.output_bytes = output_bytes;
+        self.output_bitrate = bitrate(output_bytes, self.elapsed_seconds);
+        self
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+fn bitrate(bytes: u64, seconds: f64) -> u64 {
+    if seconds > 0.0 {
+        (bytes as f64 * 8.0 / seconds) as u64
+    } else {
+        0
+    }
+}
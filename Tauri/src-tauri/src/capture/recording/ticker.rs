@@ -0,0 +1,80 @@
+//! Live recording duration, read from the media timeline rather than the wall clock
+use crate::capture::recording::pipeline::MediaClock;
+use crate::events;
+use crate::state::app_state::AppState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// ~10 Hz keeps the timer visibly live without flooding the webview
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Background thread emitting `RECORDING_DURATION` while a pipeline runs
+pub struct DurationTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DurationTicker {
+    pub fn spawn(app: AppHandle, clock: MediaClock) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let handle = std::thread::Builder::new()
+            .name("recording-ticker".into())
+            .spawn(move || {
+                let mut last_emitted: Option<f64> = None;
+                let mut last_tooltip_second = None;
+
+                while !stop_flag.load(Ordering::Acquire) {
+                    // The clock only moves forward, but never let a reader see time go backwards
+                    let elapsed = clock.elapsed_seconds().max(last_emitted.unwrap_or(0.0));
+
+                    if last_emitted != Some(elapsed) {
+                        let state: tauri::State<'_, AppState> = app.state();
+                        let recording = state.recording_coordinator.lock().unwrap().update_elapsed(elapsed);
+                        if recording {
+                            last_emitted = Some(elapsed);
+                            let _ = app.emit(
+                                events::RECORDING_DURATION,
+                                serde_json::json!({ "elapsed_seconds": elapsed }),
+                            );
+
+                            let second = elapsed as u64;
+                            if last_tooltip_second != Some(second) {
+                                last_tooltip_second = Some(second);
+                                crate::tray::menu::set_recording_tooltip(&app, Some(elapsed));
+                            }
+                        }
+                    }
+
+                    std::thread::park_timeout(TICK_INTERVAL);
+                }
+
+                crate::tray::menu::set_recording_tooltip(&app, None);
+            })?;
+
+        Ok(Self { stop, handle: Some(handle) })
+    }
+
+    /// Stop ticking and wait for the thread to exit
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DurationTicker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,158 @@
+//! Cursor and click track recorded alongside a video, for exports that follow
+//! the user's activity (auto-zoom).
+//!
+//! Tracks are kept as JSON sidecars next to the history, one per item, on the
+//! timeline of the item's source file; edits are applied when the track is read.
+use crate::capture::input::{InputEvent, InputSubscription, MouseButton, Pointer};
+use crate::error::CaptureError;
+use crate::services::editor::TimeRange;
+use crate::services::storage::manager::StorageManager;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Pointer samples closer together than this are skipped
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(33);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CursorSample {
+    /// Seconds on the media timeline
+    pub time: f64,
+    /// Position in frame pixels
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClickSample {
+    pub time: f64,
+    pub x: f64,
+    pub y: f64,
+    pub button: MouseButton,
+}
+
+/// Where the pointer was over a recording. Samples are only taken while the pointer
+/// is inside the captured area.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CursorTrack {
+    /// Frame size the positions refer to
+    pub width: u32,
+    pub height: u32,
+    /// Whether the system cursor was captured into the video
+    pub cursor_in_video: bool,
+    pub samples: Vec<CursorSample>,
+    pub clicks: Vec<ClickSample>,
+}
+
+impl CursorTrack {
+    /// Interpolated pointer position at `time`; `None` before the first sample
+    pub fn position_at(&self, time: f64) -> Option<(f64, f64)> {
+        let next = self.samples.partition_point(|s| s.time <= time);
+        let before = next.checked_sub(1).map(|i| self.samples[i])?;
+        let Some(after) = self.samples.get(next) else {
+            return Some((before.x, before.y));
+        };
+        let span = after.time - before.time;
+        let t = if span > 0.0 { (time - before.time) / span } else { 0.0 };
+        Some((before.x + (after.x - before.x) * t, before.y + (after.y - before.y) * t))
+    }
+
+    /// The track of a source after keeping only `keep`, moved onto the edited timeline
+    pub fn remap(&self, keep: &[TimeRange]) -> Self {
+        let mut track = Self { samples: Vec::new(), clicks: Vec::new(), ..self.clone() };
+        let mut offset = 0.0;
+        for range in keep {
+            let shift = |time: f64| offset + time - range.start;
+            let inside = |time: f64| time >= range.start && time < range.end;
+            track.samples.extend(
+                self.samples
+                    .iter()
+                    .filter(|s| inside(s.time))
+                    .map(|s| CursorSample { time: shift(s.time), ..*s }),
+            );
+            track.clicks.extend(
+                self.clicks
+                    .iter()
+                    .filter(|c| inside(c.time))
+                    .map(|c| ClickSample { time: shift(c.time), ..*c }),
+            );
+            offset += range.duration();
+        }
+        track
+    }
+}
+
+/// Pipeline stage that samples the pointer and clicks against the media clock
+pub struct TrackRecorder {
+    pointer: Pointer,
+    input: InputSubscription,
+    track: Arc<Mutex<CursorTrack>>,
+    last_sample: Option<Instant>,
+}
+
+impl TrackRecorder {
+    /// Record into `track`, which should already carry the frame size
+    pub fn start(track: Arc<Mutex<CursorTrack>>, input: InputSubscription) -> Result<Self, CaptureError> {
+        Ok(Self { pointer: Pointer::open()?, input, track, last_sample: None })
+    }
+
+    /// Sample for a frame at media time `time`. `to_frame` maps a global pointer
+    /// position to frame pixels, or `None` when it's outside the captured area.
+    pub fn record(&mut self, time: f64, mut to_frame: impl FnMut(f64, f64) -> Option<(f64, f64)>) {
+        let now = Instant::now();
+        let events: Vec<_> = self.input.drain().collect();
+        let mut track = self.track.lock().unwrap();
+        for input in events {
+            if let InputEvent::Click { x, y, button } = input.event {
+                if let Some((x, y)) = to_frame(x, y) {
+                    // Clicks since the last frame belong a little before it
+                    let at = (time - now.duration_since(input.at).as_secs_f64()).max(0.0);
+                    track.clicks.push(ClickSample { time: at, x, y, button });
+                }
+            }
+        }
+
+        if self.last_sample.is_some_and(|last| now.duration_since(last) < SAMPLE_INTERVAL) {
+            return;
+        }
+        self.last_sample = Some(now);
+        if let Some((x, y)) = self.pointer.position().and_then(|(x, y)| to_frame(x, y)) {
+            track.samples.push(CursorSample { time, x, y });
+        }
+    }
+}
+
+fn path(id: &str) -> PathBuf {
+    StorageManager::tracks_dir().join(format!("{id}.json"))
+}
+
+/// Save the track of item `id`
+pub fn save(id: &str, track: &CursorTrack) -> Result<(), CaptureError> {
+    std::fs::create_dir_all(StorageManager::tracks_dir())?;
+    std::fs::write(path(id), serde_json::to_vec(track)?)?;
+    Ok(())
+}
+
+/// The track of item `id`, on its source's timeline; `None` when none was recorded
+pub fn load(id: &str) -> Result<Option<CursorTrack>, CaptureError> {
+    match std::fs::read(path(id)) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Give item `to` the track of item `from`, for edits that share its source
+pub fn copy(from: &str, to: &str) {
+    if path(from).exists() {
+        if let Err(e) = std::fs::copy(path(from), path(to)) {
+            log::warn!("Could not copy cursor track: {}", e);
+        }
+    }
+}
+
+/// Drop the track of item `id`
+pub fn remove(id: &str) {
+    let _ = std::fs::remove_file(path(id));
+}
@@ -47,7 +47,16 @@ pub async fn capture_fullscreen(
         unsafe { core_foundation::base::CFRelease(cg_image_ref as _); }
         result
     }
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    {
+        let format = format.clone();
+        run_blocking(move || {
+            let image = linux::capture_display(display_id)?;
+            encode_image(image::DynamicImage::ImageRgba8(image), &format)
+        })
+        .await
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
         Err(CaptureError::CaptureFailed("Not supported on this platform".into()))
     }
@@ -75,7 +84,17 @@ pub async fn capture_area(
         unsafe { core_foundation::base::CFRelease(cg_image_ref as _); }
         result
     }
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    {
+        let format = format.clone();
+        let rect = rect.clone();
+        run_blocking(move || {
+            let image = linux::capture_area(&rect, display_id)?;
+            encode_image(image::DynamicImage::ImageRgba8(image), &format)
+        })
+        .await
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
         Err(CaptureError::CaptureFailed("Not supported on this platform".into()))
     }
@@ -107,7 +126,16 @@ pub async fn capture_window(
         unsafe { core_foundation::base::CFRelease(cg_image_ref as _); }
         result
     }
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    {
+        let format = format.clone();
+        run_blocking(move || {
+            let image = linux::capture_window(window_id)?;
+            encode_image(image::DynamicImage::ImageRgba8(image), &format)
+        })
+        .await
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
         Err(CaptureError::CaptureFailed("Not supported on this platform".into()))
     }
@@ -118,7 +146,57 @@ fn encode_cgimage_raw(
     cg_image: *const core_graphics::sys::CGImage,
     format: &ImageFormat,
 ) -> Result<Vec<u8>, CaptureError> {
-    use image::{DynamicImage, RgbaImage};
+    encode_image(image::DynamicImage::ImageRgba8(cgimage_to_rgba(cg_image)?), format)
+}
+
+/// Grab a display (or a display-relative rect) as RGBA, for callers that need raw pixels
+#[cfg(target_os = "macos")]
+pub(crate) fn grab_display_rgba(
+    display_id: Option<u32>,
+    rect: Option<&CaptureRect>,
+) -> Result<image::RgbaImage, CaptureError> {
+    use core_graphics::geometry::{CGPoint, CGSize, CGRect};
+
+    let id = display_id.unwrap_or_else(|| CGDisplay::main().id);
+    let cg_image_ref = match rect {
+        Some(rect) => {
+            let cg_rect = CGRect::new(
+                &CGPoint::new(rect.x, rect.y),
+                &CGSize::new(rect.width, rect.height),
+            );
+            unsafe { CGDisplayCreateImageForRect(id, cg_rect) }
+        }
+        None => unsafe { CGDisplayCreateImage(id) },
+    };
+    if cg_image_ref.is_null() {
+        return Err(CaptureError::CaptureFailed("CGDisplayCreateImage returned null".into()));
+    }
+    let result = cgimage_to_rgba(cg_image_ref as _);
+    unsafe { core_foundation::base::CFRelease(cg_image_ref as _); }
+    result
+}
+
+/// Grab a single window's contents as RGBA
+#[cfg(target_os = "macos")]
+pub(crate) fn grab_window_rgba(window_id: u32) -> Result<image::RgbaImage, CaptureError> {
+    use core_graphics::geometry::{CGPoint, CGSize, CGRect};
+
+    let null_rect = CGRect::new(&CGPoint::new(f64::INFINITY, f64::INFINITY), &CGSize::new(0.0, 0.0));
+    // kCGWindowListOptionIncludingWindow = 1 << 3, kCGWindowImageDefault = 0
+    let cg_image_ref = unsafe { CGWindowListCreateImage(null_rect, 1 << 3, window_id, 0) };
+    if cg_image_ref.is_null() {
+        return Err(CaptureError::CaptureFailed("CGWindowListCreateImage returned null".into()));
+    }
+    let result = cgimage_to_rgba(cg_image_ref as _);
+    unsafe { core_foundation::base::CFRelease(cg_image_ref as _); }
+    result
+}
+
+#[cfg(target_os = "macos")]
+fn cgimage_to_rgba(
+    cg_image: *const core_graphics::sys::CGImage,
+) -> Result<image::RgbaImage, CaptureError> {
+    use image::RgbaImage;
 
     let width = unsafe { CGImageGetWidth(cg_image) };
     let height = unsafe { CGImageGetHeight(cg_image) };
@@ -174,10 +252,14 @@ fn encode_cgimage_raw(
         }
     }
 
-    let img = RgbaImage::from_raw(width as u32, height as u32, rgba)
-        .ok_or_else(|| CaptureError::CaptureFailed("Pixel buffer size mismatch".into()))?;
-    let dynamic = DynamicImage::ImageRgba8(img);
+    RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .ok_or_else(|| CaptureError::CaptureFailed("Pixel buffer size mismatch".into()))
+}
 
+fn encode_image(
+    dynamic: image::DynamicImage,
+    format: &ImageFormat,
+) -> Result<Vec<u8>, CaptureError> {
     let mut buf = Vec::new();
     let mut cursor = std::io::Cursor::new(&mut buf);
     match format {
@@ -195,3 +277,126 @@ fn encode_cgimage_raw(
     }
     Ok(buf)
 }
+
+#[cfg(target_os = "linux")]
+async fn run_blocking<T, F>(f: F) -> Result<T, CaptureError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, CaptureError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| CaptureError::CaptureFailed(e.to_string()))?
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::capture::backend::LinuxBackend;
+    use crate::capture::config::CaptureRect;
+    use crate::capture::portal::Portal;
+    use crate::capture::x11::X11Session;
+    use crate::error::CaptureError;
+    use image::RgbaImage;
+
+    pub fn capture_display(display_id: Option<u32>) -> Result<RgbaImage, CaptureError> {
+        match LinuxBackend::detect() {
+            LinuxBackend::X11 => {
+                let session = X11Session::connect()?;
+                let m = session.monitor(display_id)?;
+                session.grab(session.root, m.x, m.y, m.width, m.height)
+            }
+            LinuxBackend::Portal => crop_desktop(portal_screenshot(false)?, display_id, None),
+        }
+    }
+
+    pub fn capture_area(rect: &CaptureRect, display_id: u32) -> Result<RgbaImage, CaptureError> {
+        match LinuxBackend::detect() {
+            LinuxBackend::X11 => {
+                let session = X11Session::connect()?;
+                let m = session.monitor(Some(display_id))?;
+                session.grab(
+                    session.root,
+                    m.x + rect.x as i32,
+                    m.y + rect.y as i32,
+                    rect.width as u32,
+                    rect.height as u32,
+                )
+            }
+            LinuxBackend::Portal => crop_desktop(portal_screenshot(false)?, Some(display_id), Some(rect)),
+        }
+    }
+
+    pub fn capture_window(window_id: u32) -> Result<RgbaImage, CaptureError> {
+        match LinuxBackend::detect() {
+            LinuxBackend::X11 => {
+                let session = X11Session::connect()?;
+                let (width, height) = session.window_size(window_id)?;
+                session.grab(window_id, 0, 0, width, height)
+            }
+            // Wayland compositors don't expose window IDs; only the portal's own picker
+            // (window 0) can choose one
+            LinuxBackend::Portal if window_id == 0 => portal_screenshot(true),
+            LinuxBackend::Portal => Err(CaptureError::InvalidConfig(format!(
+                "Window {window_id} can't be captured directly on Wayland; pass window 0 to pick it in the system dialog"
+            ))),
+        }
+    }
+
+    fn portal_screenshot(interactive: bool) -> Result<RgbaImage, CaptureError> {
+        let path = Portal::session()?.screenshot(interactive)?;
+        let image = image::open(&path)?.to_rgba8();
+        // The portal writes its own copy; we store ours through StorageManager
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Failed to remove portal screenshot {}: {}", path.display(), e);
+        }
+        Ok(image)
+    }
+
+    /// Crop a full-desktop portal screenshot to a display (and optionally a rect within it).
+    /// Monitor layout comes from XWayland when available; without it the whole image is
+    /// treated as the requested display.
+    fn crop_desktop(
+        image: RgbaImage,
+        display_id: Option<u32>,
+        rect: Option<&CaptureRect>,
+    ) -> Result<RgbaImage, CaptureError> {
+        let monitors = X11Session::connect().and_then(|s| s.monitors()).unwrap_or_default();
+        let monitor = match display_id {
+            Some(id) => monitors.iter().find(|m| m.id == id),
+            None => monitors.iter().find(|m| m.is_primary),
+        };
+
+        let (mut x, mut y, mut width, mut height) = (0.0, 0.0, image.width() as f64, image.height() as f64);
+        let mut scale = 1.0;
+
+        if let Some(m) = monitor {
+            // The screenshot spans the bounding box of all monitors, possibly at a HiDPI scale
+            let min_x = monitors.iter().map(|m| m.x).min().unwrap_or(0);
+            let min_y = monitors.iter().map(|m| m.y).min().unwrap_or(0);
+            let max_x = monitors.iter().map(|m| m.x + m.width as i32).max().unwrap_or(0);
+            if max_x > min_x {
+                scale = image.width() as f64 / (max_x - min_x) as f64;
+            }
+            x = (m.x - min_x) as f64 * scale;
+            y = (m.y - min_y) as f64 * scale;
+            width = m.width as f64 * scale;
+            height = m.height as f64 * scale;
+        }
+
+        if let Some(r) = rect {
+            x += r.x * scale;
+            y += r.y * scale;
+            width = r.width * scale;
+            height = r.height * scale;
+        }
+
+        let x = (x.max(0.0) as u32).min(image.width());
+        let y = (y.max(0.0) as u32).min(image.height());
+        let width = (width as u32).min(image.width() - x);
+        let height = (height as u32).min(image.height() - y);
+        if width == 0 || height == 0 {
+            return Err(CaptureError::CaptureFailed("Capture region is outside the desktop".into()));
+        }
+        Ok(image::imageops::crop_imm(&image, x, y, width, height).to_image())
+    }
+}
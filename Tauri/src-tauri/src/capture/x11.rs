@@ -0,0 +1,444 @@
+//! X11 display and window discovery via RandR monitors and EWMH hints
+use crate::capture::config::{DisplayInfo, WindowInfo};
+use crate::error::CaptureError;
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::ConnectionExt as _;
+use image::RgbaImage;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, GetPropertyReply, ImageFormat, ImageOrder, Window,
+};
+use x11rb::rust_connection::RustConnection;
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        UTF8_STRING,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_STATE,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+    }
+}
+
+/// A RandR monitor in root window coordinates
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub is_primary: bool,
+}
+
+/// A connection to an X server plus the atoms needed to read EWMH properties
+pub struct X11Session {
+    pub conn: RustConnection,
+    pub root: Window,
+    pub atoms: Atoms,
+}
+
+pub(crate) fn x11_error(err: impl std::fmt::Display) -> CaptureError {
+    CaptureError::CaptureFailed(format!("X11: {err}"))
+}
+
+impl X11Session {
+    /// Connect to the display named by `$DISPLAY`
+    pub fn connect() -> Result<Self, CaptureError> {
+        Self::connect_to(None)
+    }
+
+    /// Connect to a specific display (e.g. `":99"` for an Xvfb server)
+    pub fn connect_to(display: Option<&str>) -> Result<Self, CaptureError> {
+        let (conn, screen_num) = x11rb::connect(display).map_err(x11_error)?;
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        Ok(Self { conn, root, atoms })
+    }
+
+    /// Active RandR monitors, primary first
+    pub fn monitors(&self) -> Result<Vec<Monitor>, CaptureError> {
+        let reply = self
+            .conn
+            .randr_get_monitors(self.root, true)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+
+        let mut monitors: Vec<Monitor> = reply
+            .monitors
+            .iter()
+            .map(|m| Monitor {
+                // Prefer the first output ID since it survives monitor re-layouts
+                id: m.outputs.first().copied().unwrap_or(m.name),
+                x: m.x as i32,
+                y: m.y as i32,
+                width: m.width as u32,
+                height: m.height as u32,
+                is_primary: m.primary,
+            })
+            .collect();
+
+        // Without RandR monitors (e.g. a bare Xvfb), fall back to the root window
+        if monitors.is_empty() {
+            let (width, height) = self.root_size()?;
+            monitors.push(Monitor { id: self.root, x: 0, y: 0, width, height, is_primary: true });
+        }
+
+        if !monitors.iter().any(|m| m.is_primary) {
+            monitors[0].is_primary = true;
+        }
+        monitors.sort_by_key(|m| !m.is_primary);
+        Ok(monitors)
+    }
+
+    /// Size of the root window, i.e. of the whole X screen
+    pub fn root_size(&self) -> Result<(u32, u32), CaptureError> {
+        let screen = self
+            .conn
+            .setup()
+            .roots
+            .iter()
+            .find(|s| s.root == self.root)
+            .ok_or_else(|| x11_error("root screen not found"))?;
+        Ok((screen.width_in_pixels as u32, screen.height_in_pixels as u32))
+    }
+
+    /// Look up a monitor by ID, or the primary monitor when `None`
+    pub fn monitor(&self, display_id: Option<u32>) -> Result<Monitor, CaptureError> {
+        let monitors = self.monitors()?;
+        let found = match display_id {
+            Some(id) => monitors.into_iter().find(|m| m.id == id),
+            None => monitors.into_iter().find(|m| m.is_primary),
+        };
+        found.ok_or_else(|| CaptureError::CaptureFailed(format!("Display {:?} not found", display_id)))
+    }
+
+    pub fn displays(&self) -> Result<Vec<DisplayInfo>, CaptureError> {
+        Ok(self
+            .monitors()?
+            .into_iter()
+            .map(|m| DisplayInfo {
+                id: m.id,
+                width: m.width,
+                height: m.height,
+                // X11 has no per-monitor scale; toolkits apply Xft.dpi themselves
+                scale_factor: 1.0,
+                is_primary: m.is_primary,
+            })
+            .collect())
+    }
+
+    /// Top-level client windows, front-most first
+    pub fn windows(&self) -> Result<Vec<WindowInfo>, CaptureError> {
+        // _NET_CLIENT_LIST_STACKING is bottom-to-top; fall back to mapping order
+        let mut clients = self.window_list_property(self.atoms._NET_CLIENT_LIST_STACKING)?;
+        if clients.is_empty() {
+            clients = self.window_list_property(self.atoms._NET_CLIENT_LIST)?;
+        }
+
+        let own_pid = std::process::id();
+        let mut windows = Vec::new();
+
+        for &window in clients.iter().rev() {
+            // Windows can disappear between listing and querying; just skip them
+            let Ok(geometry) = self.conn.get_geometry(window).map_err(x11_error)?.reply() else {
+                continue;
+            };
+
+            // Skip docks, desktops, menus and minimised windows
+            if !self.is_normal_window(window)? || self.is_hidden(window)? {
+                continue;
+            }
+
+            // Skip our own app
+            if self.cardinal_property(window, self.atoms._NET_WM_PID)? == Some(own_pid) {
+                continue;
+            }
+
+            let title = self.window_title(window)?;
+            if title.is_empty() {
+                continue;
+            }
+
+            let (width, height) = (geometry.width as u32, geometry.height as u32);
+
+            // Skip tiny windows
+            if width < 50 || height < 50 {
+                continue;
+            }
+
+            windows.push(WindowInfo {
+                id: window,
+                title,
+                app_name: self.window_class(window)?,
+                width,
+                height,
+            });
+        }
+
+        Ok(windows)
+    }
+
+    /// Read a region of a drawable (root or window) as RGBA.
+    /// Coordinates are relative to the drawable's origin.
+    pub fn grab(
+        &self,
+        drawable: Window,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<RgbaImage, CaptureError> {
+        let mut pixels = self.grab_bgra(drawable, x, y, width, height)?;
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| CaptureError::CaptureFailed("Pixel buffer size mismatch".into()))
+    }
+
+    /// Read a region of a drawable as tightly packed BGRA (alpha forced opaque)
+    pub fn grab_bgra(
+        &self,
+        drawable: Window,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, CaptureError> {
+        let reply = self
+            .conn
+            .get_image(ImageFormat::Z_PIXMAP, drawable, x as i16, y as i16, width as u16, height as u16, !0)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+
+        let setup = self.conn.setup();
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == reply.depth)
+            .map(|f| f.bits_per_pixel)
+            .unwrap_or(0);
+        if bits_per_pixel != 32 {
+            return Err(x11_error(format!(
+                "unsupported visual depth {} ({} bpp)",
+                reply.depth, bits_per_pixel
+            )));
+        }
+
+        // 32bpp ZPixmap rows are never padded; pixels are BGRX (LSB) or XRGB (MSB)
+        let mut data = reply.data;
+        if setup.image_byte_order == ImageOrder::MSB_FIRST {
+            for px in data.chunks_exact_mut(4) {
+                px.reverse();
+            }
+        }
+        for px in data.chunks_exact_mut(4) {
+            px[3] = 255;
+        }
+        Ok(data)
+    }
+
+    /// A window's size, for grabbing its drawable
+    pub fn window_size(&self, window: Window) -> Result<(u32, u32), CaptureError> {
+        let geometry = self
+            .conn
+            .get_geometry(window)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(|_| CaptureError::CaptureFailed(format!("Window {} not found", window)))?;
+        Ok((geometry.width as u32, geometry.height as u32))
+    }
+
+    fn property(
+        &self,
+        window: Window,
+        property: Atom,
+        ty: impl Into<Atom>,
+    ) -> Result<Option<GetPropertyReply>, CaptureError> {
+        let cookie = self
+            .conn
+            .get_property(false, window, property, ty, 0, u32::MAX / 4)
+            .map_err(x11_error)?;
+        // A BadWindow here means the client went away; treat it as "no property"
+        Ok(cookie.reply().ok().filter(|r| r.type_ != x11rb::NONE))
+    }
+
+    fn window_list_property(&self, property: Atom) -> Result<Vec<Window>, CaptureError> {
+        Ok(self
+            .property(self.root, property, AtomEnum::WINDOW)?
+            .and_then(|r| r.value32().map(|v| v.collect()))
+            .unwrap_or_default())
+    }
+
+    fn atom_list_property(&self, window: Window, property: Atom) -> Result<Vec<Atom>, CaptureError> {
+        Ok(self
+            .property(window, property, AtomEnum::ATOM)?
+            .and_then(|r| r.value32().map(|v| v.collect()))
+            .unwrap_or_default())
+    }
+
+    fn cardinal_property(&self, window: Window, property: Atom) -> Result<Option<u32>, CaptureError> {
+        Ok(self
+            .property(window, property, AtomEnum::CARDINAL)?
+            .and_then(|r| r.value32().and_then(|mut v| v.next())))
+    }
+
+    fn is_normal_window(&self, window: Window) -> Result<bool, CaptureError> {
+        let types = self.atom_list_property(window, self.atoms._NET_WM_WINDOW_TYPE)?;
+        // Windows without a type hint are treated as normal per EWMH
+        Ok(types.is_empty()
+            || types.iter().any(|&t| {
+                t == self.atoms._NET_WM_WINDOW_TYPE_NORMAL || t == self.atoms._NET_WM_WINDOW_TYPE_DIALOG
+            }))
+    }
+
+    pub(crate) fn is_hidden(&self, window: Window) -> Result<bool, CaptureError> {
+        let states = self.atom_list_property(window, self.atoms._NET_WM_STATE)?;
+        Ok(states.contains(&self.atoms._NET_WM_STATE_HIDDEN))
+    }
+
+    /// `_NET_WM_NAME` (UTF-8), falling back to the legacy `WM_NAME`
+    pub fn window_title(&self, window: Window) -> Result<String, CaptureError> {
+        if let Some(reply) = self.property(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)? {
+            return Ok(String::from_utf8_lossy(&reply.value).into_owned());
+        }
+        Ok(self
+            .property(window, AtomEnum::WM_NAME.into(), AtomEnum::ANY)?
+            .map(|r| String::from_utf8_lossy(&r.value).into_owned())
+            .unwrap_or_default())
+    }
+
+    /// The class half of `WM_CLASS` (`instance\0class\0`), e.g. "Firefox"
+    fn window_class(&self, window: Window) -> Result<String, CaptureError> {
+        let Some(reply) = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING)? else {
+            return Ok(String::new());
+        };
+        let mut parts = reply.value.split(|&b| b == 0).filter(|p| !p.is_empty());
+        let instance = parts.next();
+        let class = parts.next().or(instance).unwrap_or_default();
+        Ok(String::from_utf8_lossy(class).into_owned())
+    }
+}
+
+/// These run against a private Xvfb server: `cargo test -- --ignored x11`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use x11rb::protocol::xproto::{CreateWindowAux, PropMode, WindowClass};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    /// An Xvfb server, killed when dropped
+    struct Xvfb {
+        child: Child,
+        display: String,
+    }
+
+    impl Xvfb {
+        fn start() -> Self {
+            // -displayfd picks a free display and writes its number once it accepts clients
+            let mut child = Command::new("Xvfb")
+                .args(["-displayfd", "1", "-screen", "0", "1280x720x24", "-nolisten", "tcp"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("Xvfb is not installed");
+            let mut line = String::new();
+            BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+            Self { child, display: format!(":{}", line.trim()) }
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// Map a top-level test client with a title and class
+    fn client(session: &X11Session, title: &str, (x, y, width, height): (i16, i16, u16, u16)) -> Window {
+        let conn = &session.conn;
+        let window = conn.generate_id().unwrap();
+        let aux = CreateWindowAux::new().background_pixel(0x00ff_0000);
+        conn.create_window(0, window, session.root, x, y, width, height, 0, WindowClass::INPUT_OUTPUT, 0, &aux)
+            .unwrap();
+        let utf8 = session.atoms.UTF8_STRING;
+        conn.change_property8(PropMode::REPLACE, window, session.atoms._NET_WM_NAME, utf8, title.as_bytes())
+            .unwrap();
+        conn.change_property8(PropMode::REPLACE, window, AtomEnum::WM_CLASS, AtomEnum::STRING, b"test\0TestApp\0")
+            .unwrap();
+        conn.map_window(window).unwrap();
+        window
+    }
+
+    /// Publish `windows` (bottom-to-top) the way a window manager would
+    fn set_stacking(session: &X11Session, windows: &[Window]) {
+        let atom = session.atoms._NET_CLIENT_LIST_STACKING;
+        session
+            .conn
+            .change_property32(PropMode::REPLACE, session.root, atom, AtomEnum::WINDOW, windows)
+            .unwrap();
+        session.conn.sync().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb"]
+    fn x11_enumerates_the_xvfb_screen_as_one_primary_display() {
+        let xvfb = Xvfb::start();
+        let session = X11Session::connect_to(Some(&xvfb.display)).unwrap();
+
+        let displays = session.displays().unwrap();
+        assert_eq!(displays.len(), 1);
+        assert_eq!((displays[0].width, displays[0].height), (1280, 720));
+        assert!(displays[0].is_primary);
+        assert_eq!(session.monitor(None).unwrap().id, displays[0].id);
+        assert!(session.monitor(Some(displays[0].id + 1000)).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb"]
+    fn x11_lists_client_windows_front_most_first() {
+        let xvfb = Xvfb::start();
+        let session = X11Session::connect_to(Some(&xvfb.display)).unwrap();
+
+        let editor = client(&session, "Editor", (10, 20, 640, 480));
+        let terminal = client(&session, "Terminal", (200, 100, 300, 200));
+        let tiny = client(&session, "Tooltip", (0, 0, 20, 20));
+        let hidden = client(&session, "Minimised", (0, 0, 400, 300));
+        let hidden_state = [session.atoms._NET_WM_STATE_HIDDEN];
+        session
+            .conn
+            .change_property32(PropMode::REPLACE, hidden, session.atoms._NET_WM_STATE, AtomEnum::ATOM, &hidden_state)
+            .unwrap();
+        let own = client(&session, "Own window", (0, 0, 400, 300));
+        let pid = [std::process::id()];
+        session
+            .conn
+            .change_property32(PropMode::REPLACE, own, session.atoms._NET_WM_PID, AtomEnum::CARDINAL, &pid)
+            .unwrap();
+        set_stacking(&session, &[hidden, editor, own, tiny, terminal]);
+
+        let windows = session.windows().unwrap();
+        let listed: Vec<(Window, &str, u32, u32)> =
+            windows.iter().map(|w| (w.id, w.title.as_str(), w.width, w.height)).collect();
+        assert_eq!(listed, [(terminal, "Terminal", 300, 200), (editor, "Editor", 640, 480)]);
+        assert!(windows.iter().all(|w| w.app_name == "TestApp"));
+
+        assert_eq!(session.window_size(editor).unwrap(), (640, 480));
+        let image = session.grab(session.root, 10, 20, 64, 48).unwrap();
+        assert_eq!(image.dimensions(), (64, 48));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+}
@@ -11,9 +11,18 @@ pub enum CaptureError {
     #[error("Recording not active")]
     RecordingNotActive,
 
+    #[error("Invalid recording state transition: {from} -> {to}")]
+    InvalidTransition { from: &'static str, to: &'static str },
+
     #[error("Storage error: {0}")]
     StorageError(String),
 
+    #[error("Export failed: {0}")]
+    ExportFailed(String),
+
+    #[error("Export cancelled")]
+    ExportCancelled,
+
     #[error("OCR failed: {0}")]
     OcrFailed(String),
 
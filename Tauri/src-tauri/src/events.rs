@@ -2,8 +2,15 @@
 pub const RECORDING_STATE_CHANGED: &str = "recording:state-changed";
 pub const RECORDING_DURATION: &str = "recording:duration";
 pub const CAPTURE_COMPLETED: &str = "capture:completed";
+pub const CAPTURE_PREVIEW_READY: &str = "capture:preview-ready";
 pub const RECORDING_COMPLETED: &str = "recording:completed";
 pub const RECORDING_FAILED: &str = "recording:failed";
+pub const RECORDING_SOURCE_STATUS: &str = "recording:source-status";
+pub const RECORDING_MARKER_ADDED: &str = "recording:marker-added";
+pub const RECORDING_STATS: &str = "recording:stats";
+pub const RECORDING_AUDIO_LEVEL: &str = "recording:audio-level";
+pub const REPLAY_STATE_CHANGED: &str = "replay:state-changed";
+pub const EXPORT_PROGRESS: &str = "export:progress";
 pub const PERMISSION_CHANGED: &str = "permission:changed";
 pub const TRAY_ACTION: &str = "tray:action";
 pub const SHORTCUT_TRIGGERED: &str = "shortcut:triggered";
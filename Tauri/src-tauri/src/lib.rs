@@ -23,6 +23,29 @@ pub fn run() {
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
+            // Broadcast every recording state change to the frontend
+            {
+                use tauri::{Emitter, Manager};
+                let handle = app.handle().clone();
+                let state = app.state::<state::app_state::AppState>();
+                state.recording_coordinator.lock().unwrap().set_listener(move |s| {
+                    let _ = handle.emit(events::RECORDING_STATE_CHANGED, s);
+                });
+            }
+
+            // Recordings interrupted by a crash or power loss; the history window
+            // lists them with list_orphaned_recordings when it opens
+            {
+                use tauri::Manager;
+                let state = app.state::<state::app_state::AppState>();
+                let dir = state.storage.lock().unwrap().screenshots_dir();
+                services::storage::recovery::remove_stale_renders(&dir);
+                let orphans = services::storage::recovery::find_orphans(&dir);
+                if !orphans.is_empty() {
+                    log::warn!("Found {} interrupted recording(s) in {}", orphans.len(), dir.display());
+                }
+            }
+
             // Set up system tray icon with menu
             tray::menu::setup_tray(app.handle())?;
 
@@ -37,15 +60,43 @@ pub fn run() {
             capture::commands::capture_window,
             capture::commands::list_displays,
             capture::commands::list_windows,
+            capture::commands::list_audio_devices,
+            capture::commands::list_output_formats,
             capture::commands::start_recording,
             capture::commands::stop_recording,
+            capture::commands::pause_recording,
+            capture::commands::resume_recording,
             capture::commands::cancel_recording,
+            capture::commands::add_recording_marker,
             capture::commands::get_recording_state,
+            capture::commands::start_replay_buffer,
+            capture::commands::stop_replay_buffer,
+            capture::commands::save_replay,
+            capture::commands::get_replay_status,
             services::storage::commands::get_history,
             services::storage::commands::delete_capture,
             services::storage::commands::toggle_favorite,
             services::storage::commands::get_storage_info,
             services::storage::commands::set_storage_location,
+            services::storage::commands::list_orphaned_recordings,
+            services::storage::commands::recover_orphaned_recording,
+            services::storage::commands::discard_orphaned_recording,
+            services::export::commands::export_gif,
+            services::export::commands::export_animated_image,
+            services::export::commands::suggest_zoom_segments,
+            services::export::commands::export_auto_zoom,
+            services::export::commands::export_speed,
+            services::export::commands::concat_recordings,
+            services::export::commands::export_compressed,
+            services::export::commands::get_framing_presets,
+            services::export::commands::save_framing_preset,
+            services::export::commands::delete_framing_preset,
+            services::export::commands::export_framed,
+            services::export::commands::cancel_export,
+            services::editor::commands::trim_recording,
+            services::overlays::commands::get_recording_overlays,
+            services::overlays::commands::set_recording_overlays,
+            services::overlays::commands::export_recording_overlays,
             services::ocr::commands::recognize_text,
             services::permissions::commands::check_screen_recording_permission,
             services::permissions::commands::check_microphone_permission,
@@ -0,0 +1,120 @@
+use super::{trim, EditMode, RecordingEdit, TrimEdit};
+use crate::capture::recording::{markers, track};
+use crate::error::CaptureError;
+use crate::services::export::{ensure_video, ExportJob};
+use crate::services::ffmpeg;
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::services::storage::{preview, recovery};
+use crate::state::app_state::AppState;
+use tauri::Manager;
+
+/// Trim a recording from history. Times are on the timeline of the unedited
+/// recording, so re-editing an edited item starts again from its source.
+/// Progress is reported as an export of kind `trim`, cancellable by `job_id`.
+#[tauri::command]
+pub async fn trim_recording(
+    id: String,
+    edit: TrimEdit,
+    mode: Option<EditMode>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let mode = mode.unwrap_or_default();
+    let state = app.state::<AppState>();
+    let (item, current) = crate::services::export::source_path(&state, &id)?;
+    if !matches!(item.capture_type, CaptureType::Recording) {
+        return Err(CaptureError::InvalidConfig("Only recordings can be trimmed".into()));
+    }
+    ensure_video(&item)?;
+
+    // Edited items are re-rendered from their source; others are their own source
+    let (dir, source, source_markers) = {
+        let storage = state.storage.lock().unwrap();
+        let (source, source_markers) = match &item.edit {
+            Some(e) => (e.source.clone(), e.source_markers.clone()),
+            None => (item.filename.clone(), item.markers.clone()),
+        };
+        (storage.screenshots_dir(), source, source_markers)
+    };
+    let input = dir.join(&source);
+    if !input.is_file() {
+        return Err(CaptureError::StorageError(format!(
+            "The original of {} is no longer available",
+            item.filename
+        )));
+    }
+    let extension = input.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_string();
+
+    let job = ExportJob::start(&app, job_id, &id, "trim")?;
+    let render_input = input.clone();
+    // Rendered beside the history so it is renamed, not copied, into place
+    let rendered = recovery::render_path(&dir, &extension);
+    let render_output = rendered.clone();
+    let discard = |e: CaptureError| {
+        let _ = std::fs::remove_file(&rendered);
+        e
+    };
+    let trim_edit = edit.clone();
+    let render_markers = source_markers.clone();
+    let kept_markers = tokio::task::spawn_blocking(move || {
+        let info = ffmpeg::probe(&render_input)?;
+        let keep = trim_edit.keep_ranges(info.duration)?;
+        trim::render(&render_input, &render_output, &info, &keep, job.cancel_flag(), |p| job.progress(p))?;
+        let kept = markers::remap(&render_markers, &keep);
+        if !kept.is_empty() {
+            let duration = keep.iter().map(|r| r.duration()).sum();
+            if let Err(e) = markers::write_chapters(&render_output, &kept, duration) {
+                log::warn!("Trimmed recording saved without chapters: {}", e);
+            }
+        }
+        Ok::<_, CaptureError>(kept)
+    })
+    .await
+    .map_err(|e| CaptureError::ExportFailed(e.to_string()))?
+    .map_err(discard)?;
+    let recording_edit = RecordingEdit { source: source.clone(), trim: edit, source_markers };
+
+    let mut storage = state.storage.lock().unwrap();
+    let saved = match mode {
+        EditMode::NewItem => {
+            let filename = storage.generate_filename(&CaptureType::Recording, &extension);
+            std::fs::rename(&rendered, dir.join(&filename)).map_err(|e| discard(e.into()))?;
+            let mut new_item = CaptureItem::new_recording(filename);
+            new_item.edit = Some(recording_edit);
+            new_item.markers = kept_markers;
+            // Tracks stay on the source timeline, which the new item shares
+            track::copy(&id, &new_item.id);
+            storage.history.add(new_item.clone());
+            new_item
+        }
+        EditMode::Replace => {
+            // The first edit moves the recording aside so it can still be re-edited
+            let source = if source == item.filename {
+                let relative = super::preserve_original(&mut storage, &item.filename).map_err(discard)?;
+                if let Err(e) = std::fs::rename(&rendered, &current) {
+                    if let Err(e) = super::restore_original(&mut storage, &relative, &item.filename) {
+                        log::error!("Could not move {} back after a failed trim: {}", item.filename, e);
+                    }
+                    return Err(discard(e.into()));
+                }
+                relative
+            } else {
+                std::fs::rename(&rendered, &current).map_err(|e| discard(e.into()))?;
+                source
+            };
+            let entry = storage
+                .history
+                .items
+                .iter_mut()
+                .find(|i| i.id == id)
+                .ok_or_else(|| CaptureError::StorageError(format!("No capture with id {}", id)))?;
+            entry.edit = Some(RecordingEdit { source, ..recording_edit });
+            entry.markers = kept_markers;
+            entry.clone()
+        }
+    };
+    storage.save_history()?;
+    drop(storage);
+    preview::generate_in_background(&app, saved.id.clone());
+    Ok(saved)
+}
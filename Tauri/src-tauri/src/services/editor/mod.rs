@@ -0,0 +1,146 @@
+//! Non-destructive trimming and cutting of recordings.
+//!
+//! Edits are described on the timeline of the untouched source file and stored on
+//! the resulting `CaptureItem`, so an edit can always be re-opened and changed.
+//! When an edit replaces an item in place, the original is kept under
+//! `ORIGINALS_DIR` in the storage directory.
+pub mod commands;
+pub mod trim;
+
+use crate::capture::recording::markers::RecordingMarker;
+use crate::error::CaptureError;
+use crate::services::storage::manager::StorageManager;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Storage subdirectory holding sources of edited recordings
+pub const ORIGINALS_DIR: &str = ".originals";
+
+/// Cuts shorter than this are ignored, and kept ranges must be at least this long
+const MIN_RANGE_SECONDS: f64 = 0.1;
+
+/// A span of the source timeline, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// Trim points on the source timeline: keep `start..end`, minus `cuts`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrimEdit {
+    #[serde(default)]
+    pub start: f64,
+    /// `None` keeps everything to the end
+    #[serde(default)]
+    pub end: Option<f64>,
+    /// Middle segments to remove; may overlap and be in any order
+    #[serde(default)]
+    pub cuts: Vec<TimeRange>,
+}
+
+impl TrimEdit {
+    /// The ranges of a `duration`-second source that remain after this edit, in order
+    pub fn keep_ranges(&self, duration: f64) -> Result<Vec<TimeRange>, CaptureError> {
+        let end = self.end.unwrap_or(duration).min(duration);
+        if !self.start.is_finite() || self.start < 0.0 || !end.is_finite() || end - self.start < MIN_RANGE_SECONDS {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Trim range {:.2}s..{:.2}s is empty or outside the recording ({:.2}s)",
+                self.start, end, duration
+            )));
+        }
+
+        let mut cuts = self.cuts.clone();
+        if cuts.iter().any(|c| !c.start.is_finite() || !c.end.is_finite() || c.end < c.start) {
+            return Err(CaptureError::InvalidConfig("Cut ranges must have start <= end".into()));
+        }
+        cuts.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut ranges = Vec::new();
+        let mut position = self.start;
+        for cut in cuts.iter().filter(|c| c.duration() >= MIN_RANGE_SECONDS) {
+            if cut.start > position {
+                ranges.push(TimeRange { start: position, end: cut.start.min(end) });
+            }
+            position = position.max(cut.end);
+            if position >= end {
+                break;
+            }
+        }
+        if position < end {
+            ranges.push(TimeRange { start: position, end });
+        }
+        ranges.retain(|r| r.duration() >= MIN_RANGE_SECONDS);
+
+        if ranges.is_empty() {
+            return Err(CaptureError::InvalidConfig("The cuts remove the whole recording".into()));
+        }
+        Ok(ranges)
+    }
+}
+
+/// How an item was produced by the editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingEdit {
+    /// Unedited source, relative to the storage directory
+    pub source: String,
+    pub trim: TrimEdit,
+    /// Markers of the source, on its timeline; the item's own are remapped from these
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_markers: Vec<RecordingMarker>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EditMode {
+    /// Add the result to history next to the original
+    #[default]
+    NewItem,
+    /// Swap the item's file for the result, keeping its id
+    Replace,
+}
+
+/// Move the file of history item `filename` into `ORIGINALS_DIR` so it survives its
+/// item being replaced or deleted, and point every edit that used it there.
+/// Returns the new storage-relative path.
+pub fn preserve_original(storage: &mut StorageManager, filename: &str) -> Result<String, CaptureError> {
+    let dir = storage.screenshots_dir();
+    let relative = Path::new(ORIGINALS_DIR).join(filename).to_string_lossy().into_owned();
+    std::fs::create_dir_all(dir.join(ORIGINALS_DIR))?;
+    std::fs::rename(dir.join(filename), dir.join(&relative))?;
+
+    for edit in storage.history.items.iter_mut().filter_map(|i| i.edit.as_mut()) {
+        if edit.source == filename {
+            edit.source = relative.clone();
+        }
+    }
+    Ok(relative)
+}
+
+/// Undo `preserve_original`: move `relative` back to `filename` and repoint its edits
+pub fn restore_original(storage: &mut StorageManager, relative: &str, filename: &str) -> Result<(), CaptureError> {
+    let dir = storage.screenshots_dir();
+    std::fs::rename(dir.join(relative), dir.join(filename))?;
+    for edit in storage.history.items.iter_mut().filter_map(|i| i.edit.as_mut()) {
+        if edit.source == relative {
+            edit.source = filename.to_string();
+        }
+    }
+    Ok(())
+}
+
+/// Whether any item other than `except_id` is an edit of `source`
+pub fn is_source_in_use(storage: &StorageManager, source: &str, except_id: &str) -> bool {
+    storage
+        .history
+        .items
+        .iter()
+        .filter(|i| i.id != except_id)
+        .any(|i| i.edit.as_ref().is_some_and(|e| e.source == source))
+}
@@ -0,0 +1,297 @@
+//! Smart cutting: whole GOPs inside each kept range are stream-copied and only the
+//! partial GOPs at its edges are re-encoded, then the pieces are joined with the
+//! concat demuxer.
+//!
+//! The re-encoded edges can't share the copied GOPs' parameter sets (SPS/PPS for
+//! H.264, VPS/SPS/PPS for HEVC), and MP4 keeps those once per track, from the first
+//! part. So H.264 and HEVC parts are written as MPEG-TS, which repeats them before
+//! every keyframe, and the joined track is tagged `avc3`/`hev1` to say they stay in band.
+//! VP9 has no parameter sets and AV1 repeats its sequence header on keyframes anyway.
+use super::TimeRange;
+use crate::error::CaptureError;
+use crate::services::export::{join, ScratchDir};
+use crate::services::ffmpeg::{self, MediaInfo};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+
+/// Edges closer than this to a keyframe are treated as on it
+const KEYFRAME_TOLERANCE: f64 = 0.02;
+
+/// One piece of the output, on the source timeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    range: TimeRange,
+    /// Starts on a keyframe and ends before the next one, so no decoding is needed
+    copy: bool,
+}
+
+/// Source stream facts the re-encoded edges have to match
+#[derive(Debug, Default)]
+struct StreamParams {
+    profile: Option<String>,
+    /// ffprobe's integer form, e.g. 41 for level 4.1
+    level: Option<i64>,
+    pix_fmt: Option<String>,
+}
+
+/// Render the `keep` ranges of `input` into `output`. `on_progress` receives the
+/// fraction done; setting `cancel` stops with `ExportCancelled`.
+pub fn render(
+    input: &Path,
+    output: &Path,
+    info: &MediaInfo,
+    keep: &[TimeRange],
+    cancel: &AtomicBool,
+    on_progress: impl Fn(f64),
+) -> Result<(), CaptureError> {
+    let encoder = encoder_args(&info.video_codec, &stream_params(input)?)?;
+    let keyframes = keyframes(input)?;
+    let segments: Vec<Segment> = keep.iter().flat_map(|r| split(*r, &keyframes)).collect();
+    let total: f64 = segments.iter().map(|s| s.range.duration()).sum();
+    let frame = if info.fps > 0.0 { 1.0 / info.fps } else { 1.0 / 30.0 };
+
+    let scratch = ScratchDir::new("trim")?;
+    let source_extension = input.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    let in_band = matches!(info.video_codec.as_str(), "h264" | "hevc");
+    let extension = if in_band { "ts" } else { source_extension };
+    let mut parts = Vec::with_capacity(segments.len());
+    let mut done = 0.0;
+
+    for (i, segment) in segments.iter().enumerate() {
+        let part = scratch.join(&format!("part-{i}.{extension}"));
+        let TimeRange { start, end } = segment.range;
+        let mut args: Vec<String> = vec!["-ss".into(), format!("{start:.6}"), "-i".into()];
+        args.push(input.to_string_lossy().into_owned());
+        if segment.copy {
+            // Stop half a frame early so the next keyframe lands in the following part
+            args.extend(["-t".into(), format!("{:.6}", (end - start - frame / 2.0).max(frame))]);
+            args.extend(["-map", "0:v:0", "-map", "0:a?", "-c", "copy"].map(String::from));
+        } else {
+            args.extend(["-t".into(), format!("{:.6}", end - start)]);
+            args.extend(["-map", "0:v:0", "-map", "0:a?"].map(String::from));
+            args.extend(encoder.iter().cloned());
+            let audio = if source_extension == "webm" {
+                ["-c:a", "libopus", "-b:a", "128k"]
+            } else {
+                ["-c:a", "aac", "-b:a", "160k"]
+            };
+            args.extend(audio.map(String::from));
+        }
+        args.extend(["-avoid_negative_ts", "make_zero"].map(String::from));
+        if matches!(extension, "mp4" | "mov") {
+            // The concat demuxer needs every part on the same timescale
+            args.extend(["-video_track_timescale", "90000"].map(String::from));
+        }
+        args.push(part.to_string_lossy().into_owned());
+
+        let weight = segment.range.duration() / total;
+        ffmpeg::run(&args, segment.range.duration(), cancel, |p| on_progress(0.95 * (done + p * weight)))?;
+        done += weight;
+        parts.push(part);
+    }
+
+    // Sample entries that declare the parameter sets as in band
+    let tag: &[&str] = match (source_extension, info.video_codec.as_str()) {
+        ("mp4" | "m4v", "h264") => &["-tag:v", "avc3"],
+        ("mp4" | "m4v", "hevc") => &["-tag:v", "hev1"],
+        _ => &[],
+    };
+    join(&parts, &scratch, tag, output, total, cancel, |p| on_progress(0.95 + 0.05 * p))
+}
+
+/// Split a kept range at its first and last keyframe into re-encoded head and tail
+/// GOPs and a stream-copied middle
+fn split(range: TimeRange, keyframes: &[f64]) -> Vec<Segment> {
+    let first = keyframes.iter().copied().find(|k| *k >= range.start - KEYFRAME_TOLERANCE);
+    let last = keyframes.iter().copied().rev().find(|k| *k <= range.end + KEYFRAME_TOLERANCE);
+
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) if last > first => (first.max(range.start), last.min(range.end)),
+        // No whole GOP inside the range
+        _ => return vec![Segment { range, copy: false }],
+    };
+
+    let mut segments = Vec::new();
+    if first - range.start > KEYFRAME_TOLERANCE {
+        segments.push(Segment { range: TimeRange { start: range.start, end: first }, copy: false });
+    }
+    segments.push(Segment { range: TimeRange { start: first, end: last }, copy: true });
+    if range.end - last > KEYFRAME_TOLERANCE {
+        segments.push(Segment { range: TimeRange { start: last, end: range.end }, copy: false });
+    }
+    segments
+}
+
+/// Presentation times of the video keyframes in `path`, ascending
+fn keyframes(path: &Path) -> Result<Vec<f64>, CaptureError> {
+    let output = Command::new(ffmpeg::ffprobe_path()?)
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "packet=pts_time,flags", "-of", "csv=p=0"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(CaptureError::ExportFailed(format!(
+            "Could not read keyframes of {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Lines look like "12.345000,K__"
+    let mut times: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (time, flags) = line.split_once(',')?;
+            flags.starts_with('K').then(|| time.parse().ok()).flatten()
+        })
+        .collect();
+    times.sort_by(f64::total_cmp);
+    Ok(times)
+}
+
+/// Profile, level and pixel format of the first video stream of `path`
+fn stream_params(path: &Path) -> Result<StreamParams, CaptureError> {
+    let output = Command::new(ffmpeg::ffprobe_path()?)
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=profile,level,pix_fmt", "-of", "json"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(CaptureError::ExportFailed(format!(
+            "Could not read the video stream of {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let stream = &json["streams"][0];
+    let text = |key: &str| stream[key].as_str().filter(|v| !v.is_empty() && *v != "unknown").map(String::from);
+    Ok(StreamParams {
+        profile: text("profile"),
+        level: stream["level"].as_i64().filter(|l| *l >= 10),
+        pix_fmt: text("pix_fmt"),
+    })
+}
+
+/// libx264's `-profile:v` name for an H.264 profile as ffprobe reports it
+fn x264_profile(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Baseline" | "Constrained Baseline" => "baseline",
+        "Main" => "main",
+        "High" => "high",
+        "High 10" => "high10",
+        "High 4:2:2" => "high422",
+        "High 4:4:4 Predictive" => "high444",
+        _ => return None,
+    })
+}
+
+/// Encoder matching the source stream, so re-encoded GOPs can be joined to copied ones
+fn encoder_args(codec: &str, source: &StreamParams) -> Result<Vec<String>, CaptureError> {
+    let mut args: Vec<String> = match codec {
+        "h264" => {
+            let mut args = vec!["-c:v", "libx264", "-preset", "veryfast", "-crf", "18"];
+            args.extend(["-x264-params", "repeat-headers=1"]);
+            if let Some(profile) = source.profile.as_deref().and_then(x264_profile) {
+                args.extend(["-profile:v", profile]);
+            }
+            args.into_iter().map(String::from).collect()
+        }
+        "hevc" => ["-c:v", "libx265", "-preset", "fast", "-crf", "20", "-x265-params", "repeat-headers=1"]
+            .map(String::from)
+            .to_vec(),
+        "vp9" => ["-c:v", "libvpx-vp9", "-crf", "30", "-b:v", "0", "-row-mt", "1"].map(String::from).to_vec(),
+        "av1" => ["-c:v", "libsvtav1", "-crf", "30"].map(String::from).to_vec(),
+        other => {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Recordings encoded as {other} cannot be trimmed"
+            )))
+        }
+    };
+    if let (Some(level), "h264") = (source.level, codec) {
+        args.extend(["-level:v".into(), format!("{}.{}", level / 10, level % 10)]);
+    }
+    let pix_fmt = source.pix_fmt.as_deref().unwrap_or("yuv420p");
+    args.extend(["-pix_fmt".into(), pix_fmt.into()]);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: f64, end: f64) -> TimeRange {
+        TimeRange { start, end }
+    }
+
+    #[test]
+    fn split_copies_whole_gops_and_reencodes_the_edges() {
+        let keyframes = [0.0, 2.0, 4.0, 6.0];
+        let segments = split(range(1.0, 5.0), &keyframes);
+        assert_eq!(
+            segments,
+            [
+                Segment { range: range(1.0, 2.0), copy: false },
+                Segment { range: range(2.0, 4.0), copy: true },
+                Segment { range: range(4.0, 5.0), copy: false },
+            ]
+        );
+        // Inside one GOP everything is re-encoded
+        assert_eq!(split(range(2.5, 3.5), &keyframes), [Segment { range: range(2.5, 3.5), copy: false }]);
+        // Edges on keyframes need no re-encode
+        assert_eq!(split(range(2.01, 6.0), &keyframes), [Segment { range: range(2.01, 6.0), copy: true }]);
+    }
+
+    #[test]
+    fn encoder_matches_the_source_profile_level_and_pixel_format() {
+        let source = StreamParams {
+            profile: Some("Constrained Baseline".into()),
+            level: Some(31),
+            pix_fmt: Some("yuvj420p".into()),
+        };
+        let args = encoder_args("h264", &source).unwrap().join(" ");
+        assert!(args.contains("-profile:v baseline"), "{args}");
+        assert!(args.contains("-level:v 3.1"), "{args}");
+        assert!(args.contains("-pix_fmt yuvj420p"), "{args}");
+        assert!(args.contains("repeat-headers=1"), "{args}");
+        assert!(encoder_args("mjpeg", &source).is_err());
+    }
+
+    /// Runs ffmpeg: `cargo test -- --ignored trim`
+    #[test]
+    #[ignore = "needs ffmpeg with libx264"]
+    fn trim_output_decodes_cleanly_across_copied_and_reencoded_parts() {
+        let scratch = ScratchDir::new("trim-test").unwrap();
+        let input = scratch.join("source.mp4");
+        let idle = AtomicBool::new(false);
+        // Settings unlike the edge re-encode, so the parts' parameter sets differ
+        let generate = [
+            "-f", "lavfi", "-i", "testsrc2=size=320x240:rate=30:duration=4",
+            "-f", "lavfi", "-i", "sine=frequency=440:duration=4",
+            "-c:v", "libx264", "-preset", "ultrafast", "-g", "30",
+            "-c:a", "aac", "-shortest",
+        ]
+        .map(String::from)
+        .into_iter()
+        .chain([input.to_string_lossy().into_owned()]);
+        ffmpeg::run(generate, 4.0, &idle, |_| {}).unwrap();
+
+        let info = ffmpeg::probe(&input).unwrap();
+        let output = scratch.join("trimmed.mp4");
+        let keep = [range(0.4, 1.7), range(2.3, 3.6)];
+        render(&input, &output, &info, &keep, &idle, |_| {}).unwrap();
+
+        // -xerror stops at the first decode error
+        let decode = Command::new(ffmpeg::ffmpeg_path().unwrap())
+            .args(["-v", "error", "-xerror", "-i"])
+            .arg(&output)
+            .args(["-f", "null", "-"])
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&decode.stderr);
+        assert!(decode.status.success() && log.trim().is_empty(), "{log}");
+        let trimmed = ffmpeg::probe(&output).unwrap();
+        assert!((trimmed.duration - 2.6).abs() < 0.15, "duration {}", trimmed.duration);
+    }
+}
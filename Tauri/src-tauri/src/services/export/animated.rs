@@ -0,0 +1,85 @@
+//! Animated WebP and APNG export from finished recordings.
+//!
+//! Both formats carry full colour, so unlike GIF there is no palette pass: one
+//! `ffmpeg` run scales, drops duplicate frames and encodes. Sizing and frame rate
+//! follow the same presets as GIF export.
+use super::gif::GifExportOptions;
+use super::ExportJob;
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimatedFormat {
+    Webp,
+    Apng,
+}
+
+impl AnimatedFormat {
+    /// Export kind reported in progress events
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Webp => "webp",
+            AnimatedFormat::Apng => "apng",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Webp => "webp",
+            AnimatedFormat::Apng => "png",
+        }
+    }
+
+    fn encoder_args(&self) -> &'static [&'static str] {
+        match self {
+            AnimatedFormat::Webp => &["-c:v", "libwebp_anim", "-lossless", "0", "-quality", "80", "-loop", "0"],
+            AnimatedFormat::Apng => &["-c:v", "apng", "-plays", "0", "-pred", "mixed", "-f", "apng"],
+        }
+    }
+
+    /// `InvalidConfig` if the installed ffmpeg lacks the encoder
+    pub fn check_available(&self) -> Result<(), CaptureError> {
+        let encoder = self.encoder_args()[1];
+        if ffmpeg::has_encoder(encoder) {
+            Ok(())
+        } else {
+            Err(CaptureError::InvalidConfig(format!(
+                "{} export is not available: ffmpeg was built without {}",
+                self.kind().to_uppercase(),
+                encoder
+            )))
+        }
+    }
+}
+
+/// Export `input` as an animated image at `output`, reporting progress on `job`
+pub fn export(
+    input: &Path,
+    output: &Path,
+    format: AnimatedFormat,
+    options: &GifExportOptions,
+    job: &ExportJob,
+) -> Result<(), CaptureError> {
+    options.validate()?;
+    format.check_available()?;
+    let source = ffmpeg::probe(input)?;
+    let (fps, width) = options.resolve(&source);
+    let decimate = if options.remove_duplicates { ",mpdecimate" } else { "" };
+    log::info!("Exporting {} at {fps} fps, {width}px wide", format.kind());
+
+    let mut args: Vec<String> = vec!["-i".into(), input.to_string_lossy().into_owned(), "-an".into()];
+    args.extend(["-vf".into(), format!("fps={fps},scale='min({width},iw)':-1:flags=lanczos{decimate}")]);
+    // Variable frame rate keeps decimated frames out instead of re-duplicating them
+    args.extend(["-vsync".into(), "vfr".into()]);
+    args.extend(format.encoder_args().iter().map(|a| a.to_string()));
+    args.push(output.to_string_lossy().into_owned());
+
+    let result = ffmpeg::run(&args, source.duration, job.cancel_flag(), |p| job.progress(p));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
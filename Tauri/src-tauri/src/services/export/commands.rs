@@ -0,0 +1,220 @@
+use super::animated::{self, AnimatedFormat};
+use super::compress::{self, CompressOptions};
+use super::concat::{self, ConcatClip, ConcatOptions};
+use super::framing::{self, FramingOptions, FramingPreset};
+use super::gif::{self, GifExportOptions};
+use super::speed::{self, SpeedExportOptions};
+use super::zoom::{self, ZoomExportOptions, ZoomSegment};
+use super::{run_export, run_recording_export, ExportTarget};
+use crate::capture::recording::track::{self, CursorTrack};
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::state::app_state::AppState;
+use tauri::Manager;
+
+/// Export a recording from history as a GIF and add it to history.
+/// Pass `job_id` to be able to cancel before the first progress event arrives.
+#[tauri::command]
+pub async fn export_gif(
+    id: String,
+    options: Option<GifExportOptions>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = options.unwrap_or_default();
+    run_recording_export(&app, &id, job_id, ExportTarget::animation("gif", "gif"), move |_, input, output, job| {
+        gif::export(input, output, &options, job).map(|()| None)
+    })
+    .await
+}
+
+/// Export a recording from history as an animated WebP or APNG and add it to history.
+/// Takes the same sizing options as GIF export; palette and dither settings are ignored.
+#[tauri::command]
+pub async fn export_animated_image(
+    id: String,
+    format: AnimatedFormat,
+    options: Option<GifExportOptions>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = options.unwrap_or_default();
+    format.check_available()?;
+    let target = ExportTarget::animation(format.kind(), format.extension());
+    run_recording_export(&app, &id, job_id, target, move |_, input, output, job| {
+        animated::export(input, output, format, &options, job).map(|()| None)
+    })
+    .await
+}
+
+/// Cursor track of recording `item`, moved onto its edited timeline if it is an edit
+fn item_track(state: &AppState, item: &CaptureItem) -> Result<CursorTrack, CaptureError> {
+    let track = track::load(&item.id)?.ok_or_else(|| {
+        CaptureError::InvalidConfig(format!("No cursor track was recorded with {}", item.filename))
+    })?;
+    let Some(edit) = &item.edit else {
+        return Ok(track);
+    };
+    let source = state.storage.lock().unwrap().screenshots_dir().join(&edit.source);
+    let keep = edit.trim.keep_ranges(ffmpeg::probe(&source)?.duration)?;
+    Ok(track.remap(&keep))
+}
+
+/// Zoom segments derived from a recording's clicks, as a starting point for editing
+#[tauri::command]
+pub async fn suggest_zoom_segments(
+    id: String,
+    zoom: Option<f64>,
+    app: tauri::AppHandle,
+) -> Result<Vec<ZoomSegment>, CaptureError> {
+    let state = app.state::<AppState>();
+    let (source, _) = super::source_path(&state, &id)?;
+    let track = tokio::task::spawn_blocking({
+        let app = app.clone();
+        move || item_track(&app.state::<AppState>(), &source)
+    })
+    .await
+    .map_err(|e| CaptureError::ExportFailed(e.to_string()))??;
+    zoom::suggest_segments(&track, zoom.unwrap_or(ZoomExportOptions::default().zoom))
+}
+
+/// Export a recording from history with the camera zooming in on its clicks (or on
+/// `options.segments`), and add the result to history as a new recording
+#[tauri::command]
+pub async fn export_auto_zoom(
+    id: String,
+    options: Option<ZoomExportOptions>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = options.unwrap_or_default();
+    let render_app = app.clone();
+    // Timing is unchanged, so the markers (and chapters, copied by the export) still fit
+    run_recording_export(&app, &id, job_id, ExportTarget::recording("zoom"), move |source, input, output, job| {
+        let track = item_track(&render_app.state::<AppState>(), source)?;
+        zoom::export(input, output, &track, &options, job).map(|()| None)
+    })
+    .await
+}
+
+/// Export a recording from history at a new speed, per clip, per segment or over
+/// idle stretches, and add the result to history as a new recording
+#[tauri::command]
+pub async fn export_speed(
+    id: String,
+    options: Option<SpeedExportOptions>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = options.unwrap_or_default();
+    run_recording_export(&app, &id, job_id, ExportTarget::recording("speed"), move |source, input, output, job| {
+        speed::export(input, output, &options, &source.markers, job).map(Some)
+    })
+    .await
+}
+
+/// Join recordings from history in the order given into one new recording, with
+/// optional crossfades or title cards between them, and add it to history
+#[tauri::command]
+pub async fn concat_recordings(
+    ids: Vec<String>,
+    options: Option<ConcatOptions>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = options.unwrap_or_default();
+    let state = app.state::<AppState>();
+    let Some(first) = ids.first() else {
+        return Err(CaptureError::InvalidConfig("Pick at least two recordings to join".into()));
+    };
+    let mut clips = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let (source, path) = super::source_path(&state, id)?;
+        if !matches!(source.capture_type, CaptureType::Recording) {
+            return Err(CaptureError::InvalidConfig(format!("{} is not a recording", source.filename)));
+        }
+        super::ensure_video(&source)?;
+        clips.push(ConcatClip { path, markers: source.markers });
+    }
+
+    run_export(&app, first, job_id, ExportTarget::recording("concat"), Vec::new(), move |output, job| {
+        concat::export(&clips, output, &options, job).map(Some)
+    })
+    .await
+}
+
+/// Export a recording re-encoded to fit under `options.target_mb`, lowering its
+/// resolution or frame rate if needed, and add it to history
+#[tauri::command]
+pub async fn export_compressed(
+    id: String,
+    options: CompressOptions,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    // Chapters are carried over by ffmpeg; timing is unchanged
+    run_recording_export(&app, &id, job_id, ExportTarget::recording("compress"), move |_, input, output, job| {
+        compress::export(input, output, &options, job).map(|()| None)
+    })
+    .await
+}
+
+/// Saved framing presets, or the built-in ones if none have been saved
+#[tauri::command]
+pub fn get_framing_presets() -> Vec<FramingPreset> {
+    framing::load_presets()
+}
+
+/// Save a framing preset, replacing one with the same name. Returns all presets.
+#[tauri::command]
+pub fn save_framing_preset(preset: FramingPreset) -> Result<Vec<FramingPreset>, CaptureError> {
+    framing::save_preset(preset)
+}
+
+/// Returns false if there was no preset called `name`
+#[tauri::command]
+pub fn delete_framing_preset(name: String) -> Result<bool, CaptureError> {
+    framing::delete_preset(&name)
+}
+
+/// Export a screenshot or recording at a fixed aspect ratio on a background, using
+/// `options` or the saved preset called `preset`, and add the result to history
+#[tauri::command]
+pub async fn export_framed(
+    id: String,
+    options: Option<FramingOptions>,
+    preset: Option<String>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let options = match (options, preset) {
+        (Some(options), _) => options,
+        (None, Some(name)) => framing::find_preset(&name)?,
+        (None, None) => FramingOptions::default(),
+    };
+    let (source, input) = super::source_path(&app.state::<AppState>(), &id)?;
+    let (is_video, extension) = match source.capture_type {
+        CaptureType::Recording => {
+            super::ensure_video(&source)?;
+            (true, "mp4")
+        }
+        CaptureType::Screenshot => (false, "png"),
+        CaptureType::Gif => {
+            return Err(CaptureError::InvalidConfig("Only screenshots and recordings can be framed".into()))
+        }
+    };
+
+    // Chapters are carried over by ffmpeg; timing is unchanged
+    let target = ExportTarget::new("framing", source.capture_type, extension);
+    run_export(&app, &id, job_id, target, source.markers, move |output, job| {
+        framing::export(&input, output, is_video, &options, job).map(|()| None)
+    })
+    .await
+}
+
+/// Cancel a running export. Returns false if it already finished.
+#[tauri::command]
+pub fn cancel_export(job_id: String, state: tauri::State<'_, AppState>) -> bool {
+    state.exports.cancel(&job_id)
+}
@@ -0,0 +1,275 @@
+//! Target-size export for sites with upload limits.
+//!
+//! The bitrate is worked out from the target size and the duration, and the
+//! video is encoded in two passes so it lands close to that size. When the
+//! budget is too thin for the source resolution or frame rate, those are lowered
+//! first so the result stays legible instead of turning to blocks.
+use super::{ExportJob, ScratchDir};
+use crate::error::CaptureError;
+use crate::services::ffmpeg::{self, MediaInfo};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const BYTES_PER_MB: f64 = 1_000_000.0;
+const MIN_TARGET_MB: f64 = 1.0;
+const MAX_TARGET_MB: f64 = 4000.0;
+/// Share of the target left for container overhead and rate-control error
+const HEADROOM: f64 = 0.96;
+/// Below this much video bitrate nothing is readable at any size
+const MIN_VIDEO_BITRATE: f64 = 100_000.0;
+/// Bits per pixel per frame that still keep screen text sharp with H.264
+const MIN_BITS_PER_PIXEL: f64 = 0.03;
+/// Shorter-side sizes tried, largest first, when the source doesn't fit
+const SIZE_STEPS: [u32; 5] = [1080, 720, 540, 480, 360];
+/// Reruns pass 2 at a lower bitrate while the output overshoots the target
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressOptions {
+    /// Largest allowed file size in megabytes (10⁶ bytes)
+    pub target_mb: f64,
+    #[serde(default = "super::default_true")]
+    pub keep_audio: bool,
+}
+
+impl CompressOptions {
+    fn validate(&self) -> Result<(), CaptureError> {
+        if !(MIN_TARGET_MB..=MAX_TARGET_MB).contains(&self.target_mb) {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Target size must be between {MIN_TARGET_MB} MB and {MAX_TARGET_MB} MB"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// How a recording is encoded to fit its budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plan {
+    width: u32,
+    height: u32,
+    fps: f64,
+    /// Bits per second
+    video_bitrate: f64,
+    audio_bitrate: Option<u32>,
+}
+
+/// `width` x `height` scaled so the shorter side is `short`, rounded to even sizes
+fn scaled(width: u32, height: u32, short: u32) -> (u32, u32) {
+    let even = |v: f64| ((v / 2.0).round() as u32 * 2).max(2);
+    if width >= height {
+        (even(width as f64 * short as f64 / height as f64), short)
+    } else {
+        (short, even(height as f64 * short as f64 / width as f64))
+    }
+}
+
+/// The largest size and frame rate whose bitrate fits `target_bytes`
+fn plan(info: &MediaInfo, target_bytes: f64, keep_audio: bool) -> Result<Plan, CaptureError> {
+    let budget = target_bytes * 8.0 * HEADROOM / info.duration;
+    let audio_bitrate = (keep_audio && info.has_audio).then_some(if budget >= 600_000.0 { 96_000 } else { 48_000 });
+    let video_bitrate = budget - audio_bitrate.unwrap_or(0) as f64;
+    if video_bitrate < MIN_VIDEO_BITRATE {
+        return Err(CaptureError::InvalidConfig(format!(
+            "A {:.0}s recording can't fit in {:.1} MB; pick a larger target or trim it first",
+            info.duration,
+            target_bytes / BYTES_PER_MB
+        )));
+    }
+
+    let fps = if info.fps > 0.0 { info.fps } else { 30.0 };
+    let short = info.width.min(info.height);
+    let mut candidates = vec![(info.width, info.height, fps), (info.width, info.height, fps.min(30.0))];
+    for step in SIZE_STEPS.into_iter().filter(|&s| s < short) {
+        let (width, height) = scaled(info.width, info.height, step);
+        candidates.push((width, height, fps.min(30.0)));
+    }
+    let (width, height, _) = candidates[candidates.len() - 1];
+    candidates.push((width, height, fps.min(15.0)));
+
+    // Fall back to the smallest candidate when even that is starved
+    let (width, height, fps) = candidates
+        .iter()
+        .copied()
+        .find(|&(w, h, f)| video_bitrate / (w as f64 * h as f64 * f) >= MIN_BITS_PER_PIXEL)
+        .unwrap_or(candidates[candidates.len() - 1]);
+    Ok(Plan { width, height, fps, video_bitrate, audio_bitrate })
+}
+
+/// Arguments both passes share. The analysis of pass 1 depends on the size and
+/// frame rate only, so pass 2 can be rerun at another bitrate against the same log.
+fn video_args(input: &Path, info: &MediaInfo, plan: &Plan, log: &Path, pass: &str) -> Vec<String> {
+    let mut filter = format!("fps={}", plan.fps);
+    if (plan.width, plan.height) != (info.width, info.height) {
+        filter = format!("scale={}:{}:flags=lanczos,{filter}", plan.width, plan.height);
+    }
+    vec![
+        "-i".into(),
+        input.to_string_lossy().into_owned(),
+        "-map".into(),
+        "0:v:0".into(),
+        "-vf".into(),
+        filter,
+        "-c:v".into(),
+        "libx264".into(),
+        "-preset".into(),
+        "medium".into(),
+        "-pix_fmt".into(),
+        "yuv420p".into(),
+        "-b:v".into(),
+        (plan.video_bitrate.round() as u64).to_string(),
+        "-pass".into(),
+        pass.into(),
+        "-passlogfile".into(),
+        log.to_string_lossy().into_owned(),
+    ]
+}
+
+/// Pass 1: analyse the video into `log`, reporting progress in `from..to`
+fn first_pass(
+    input: &Path,
+    info: &MediaInfo,
+    plan: &Plan,
+    log: &Path,
+    job: &ExportJob,
+    (from, to): (f64, f64),
+) -> Result<(), CaptureError> {
+    let mut args = video_args(input, info, plan, log, "1");
+    args.extend(["-an", "-f", "null", "-"].map(String::from));
+    ffmpeg::run(&args, info.duration, job.cancel_flag(), |p| job.progress(from + (to - from) * p))
+}
+
+/// Pass 2: encode `output` at `plan` from the pass-1 `log`, reporting progress in `from..to`
+fn second_pass(
+    input: &Path,
+    output: &Path,
+    info: &MediaInfo,
+    plan: &Plan,
+    log: &Path,
+    job: &ExportJob,
+    (from, to): (f64, f64),
+) -> Result<(), CaptureError> {
+    let bitrate = plan.video_bitrate.round() as u64;
+    let mut args = video_args(input, info, plan, log, "2");
+    // Cap peaks so the second pass can't spend the budget on one busy scene
+    args.extend(["-maxrate".into(), (bitrate * 3 / 2).to_string(), "-bufsize".into(), (bitrate * 2).to_string()]);
+    match plan.audio_bitrate {
+        Some(audio) => {
+            args.extend(["-map", "0:a:0", "-c:a", "aac", "-ac", "2", "-b:a", &audio.to_string()].map(String::from))
+        }
+        None => args.push("-an".into()),
+    }
+    args.extend(["-map_chapters", "0", "-movflags", "+faststart"].map(String::from));
+    args.push(output.to_string_lossy().into_owned());
+    ffmpeg::run(&args, info.duration, job.cancel_flag(), |p| job.progress(from + (to - from) * p))
+}
+
+/// Encode `input` into `output` (MP4, H.264) no larger than the target size
+pub fn export(input: &Path, output: &Path, options: &CompressOptions, job: &ExportJob) -> Result<(), CaptureError> {
+    options.validate()?;
+    if !ffmpeg::has_encoder("libx264") {
+        return Err(CaptureError::ExportFailed("Target-size export needs ffmpeg with libx264".into()));
+    }
+    let info = ffmpeg::probe(input)?;
+    if info.duration <= 0.0 || info.width == 0 || info.height == 0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no frames", input.display())));
+    }
+    let target_bytes = options.target_mb * BYTES_PER_MB;
+    let mut plan = plan(&info, target_bytes, options.keep_audio)?;
+    log::info!(
+        "Target-size export: {:.1} MB -> {}x{} @ {} fps, {:.0} kbps video",
+        options.target_mb,
+        plan.width,
+        plan.height,
+        plan.fps,
+        plan.video_bitrate / 1000.0
+    );
+
+    let scratch = ScratchDir::new("compress")?;
+    let log = scratch.join("passlog");
+    first_pass(input, &info, &plan, &log, job, (0.0, 0.4))?;
+    let mut span = (0.4, 0.9);
+    for attempt in 1..=MAX_ATTEMPTS {
+        if let Err(e) = second_pass(input, output, &info, &plan, &log, job, span) {
+            let _ = std::fs::remove_file(output);
+            return Err(e);
+        }
+        let size = std::fs::metadata(output)?.len() as f64;
+        if size <= target_bytes {
+            job.progress(1.0);
+            return Ok(());
+        }
+        log::warn!(
+            "Target-size export overshot on attempt {attempt}: {:.2} MB for a {:.1} MB target",
+            size / BYTES_PER_MB,
+            options.target_mb
+        );
+        // Shrink the video by the overshoot, plus a little more
+        let excess = (size - target_bytes) * 8.0 / info.duration;
+        plan.video_bitrate -= excess * 1.1;
+        if plan.video_bitrate < MIN_VIDEO_BITRATE {
+            break;
+        }
+        span = (span.1, span.1 + (0.99 - span.1) / 2.0);
+    }
+    let _ = std::fs::remove_file(output);
+    Err(CaptureError::ExportFailed(format!(
+        "Couldn't get the recording under {:.1} MB; pick a larger target",
+        options.target_mb
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(width: u32, height: u32, fps: f64, duration: f64) -> MediaInfo {
+        MediaInfo { duration, width, height, fps, video_codec: "h264".into(), has_audio: true }
+    }
+
+    #[test]
+    fn roomy_budget_keeps_the_source() {
+        let plan = plan(&info(1920, 1080, 60.0, 60.0), 50.0 * BYTES_PER_MB, true).unwrap();
+        assert_eq!((plan.width, plan.height, plan.fps), (1920, 1080, 60.0));
+        assert_eq!(plan.audio_bitrate, Some(96_000));
+        assert_eq!(plan.video_bitrate, 50.0 * BYTES_PER_MB * 8.0 * HEADROOM / 60.0 - 96_000.0);
+    }
+
+    #[test]
+    fn tight_budget_lowers_frame_rate_then_size() {
+        let tight = plan(&info(1920, 1080, 60.0, 60.0), 10.0 * BYTES_PER_MB, true).unwrap();
+        assert_eq!((tight.width, tight.height, tight.fps), (1280, 720, 30.0));
+        assert_eq!(tight.audio_bitrate, Some(96_000));
+
+        let thin = plan(&info(1920, 1080, 60.0, 60.0), 4.0 * BYTES_PER_MB, true).unwrap();
+        assert_eq!((thin.width, thin.height, thin.fps), (854, 480, 30.0));
+        assert_eq!(thin.audio_bitrate, Some(48_000));
+
+        let starved = plan(&info(1920, 1080, 60.0, 60.0), BYTES_PER_MB, false).unwrap();
+        assert_eq!((starved.width, starved.height, starved.fps), (640, 360, 15.0));
+        assert_eq!(starved.audio_bitrate, None);
+    }
+
+    #[test]
+    fn target_below_the_minimum_bitrate_is_rejected() {
+        assert!(matches!(
+            plan(&info(1920, 1080, 30.0, 600.0), BYTES_PER_MB, true),
+            Err(CaptureError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn silent_sources_spend_everything_on_video() {
+        let silent = MediaInfo { has_audio: false, ..info(1280, 720, 30.0, 30.0) };
+        let plan = plan(&silent, 20.0 * BYTES_PER_MB, true).unwrap();
+        assert_eq!(plan.audio_bitrate, None);
+        assert_eq!(plan.video_bitrate, 20.0 * BYTES_PER_MB * 8.0 * HEADROOM / 30.0);
+    }
+
+    #[test]
+    fn scaled_keeps_aspect_on_even_sizes() {
+        assert_eq!(scaled(1920, 1080, 720), (1280, 720));
+        assert_eq!(scaled(1080, 1920, 720), (720, 1280));
+        assert_eq!(scaled(1366, 768, 360), (640, 360));
+    }
+}
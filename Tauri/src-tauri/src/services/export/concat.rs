@@ -0,0 +1,398 @@
+//! Joins several recordings into one.
+//!
+//! Every clip (and every title card) is first re-encoded to the same size, frame
+//! rate and audio layout, so the parts can be joined with the concat demuxer, as
+//! trimming does, or blended into each other with `xfade`/`acrossfade`.
+use super::{part_args, ExportJob, ScratchDir};
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::capture::recording::encoder::format::{self, FormatArgs};
+use crate::capture::recording::markers::RecordingMarker;
+use crate::capture::recording::overlay::load_font;
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use crate::services::overlays::{render, OverlayShape};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const MAX_FPS: f64 = 60.0;
+const MAX_CROSSFADE_SECONDS: f64 = 5.0;
+/// Sample format every part's audio is converted to
+const AUDIO_FORMAT: &str = "aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo";
+const SILENCE: &str = "anullsrc=r=48000:cl=stereo";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConcatTransition {
+    /// Hard cut from one clip to the next
+    #[default]
+    Cut,
+    /// Blend the end of each part into the start of the next
+    Crossfade { seconds: f64 },
+}
+
+fn default_title_seconds() -> f64 {
+    2.5
+}
+
+fn default_title_background() -> [u8; 4] {
+    [17, 17, 17, 255]
+}
+
+fn default_title_color() -> [u8; 4] {
+    [255, 255, 255, 255]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcatOptions {
+    /// Output size; defaults to the first clip's. Other clips are letterboxed into it.
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Output frame rate; defaults to the highest of the clips
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(default)]
+    pub transition: ConcatTransition,
+    /// Title card text shown before each clip, by clip index; empty entries get no card
+    #[serde(default)]
+    pub titles: Vec<String>,
+    #[serde(default = "default_title_seconds")]
+    pub title_seconds: f64,
+    #[serde(default = "default_title_background")]
+    pub title_background: [u8; 4],
+    #[serde(default = "default_title_color")]
+    pub title_color: [u8; 4],
+}
+
+impl Default for ConcatOptions {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            fps: None,
+            transition: ConcatTransition::default(),
+            titles: Vec::new(),
+            title_seconds: default_title_seconds(),
+            title_background: default_title_background(),
+            title_color: default_title_color(),
+        }
+    }
+}
+
+impl ConcatOptions {
+    fn validate(&self, clips: usize) -> Result<(), CaptureError> {
+        if clips < 2 {
+            return Err(CaptureError::InvalidConfig("Pick at least two recordings to join".into()));
+        }
+        if self.width.is_some() != self.height.is_some() {
+            return Err(CaptureError::InvalidConfig("Set both width and height, or neither".into()));
+        }
+        if matches!((self.width, self.height), (Some(w), Some(h)) if w < 16 || h < 16) {
+            return Err(CaptureError::InvalidConfig("Output size must be at least 16x16".into()));
+        }
+        if matches!(self.fps, Some(fps) if !(1.0..=MAX_FPS).contains(&fps)) {
+            return Err(CaptureError::InvalidConfig(format!("Frame rate must be between 1 and {MAX_FPS}")));
+        }
+        if let ConcatTransition::Crossfade { seconds } = self.transition {
+            if !(seconds > 0.0 && seconds <= MAX_CROSSFADE_SECONDS) {
+                return Err(CaptureError::InvalidConfig(format!(
+                    "Crossfades must be longer than 0s and at most {MAX_CROSSFADE_SECONDS}s"
+                )));
+            }
+        }
+        if self.titles.len() > clips {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Got {} titles for {clips} recordings",
+                self.titles.len()
+            )));
+        }
+        if self.titles.iter().any(|t| !t.trim().is_empty())
+            && !(self.title_seconds.is_finite() && self.title_seconds >= 0.5)
+        {
+            return Err(CaptureError::InvalidConfig("Title cards must show for at least half a second".into()));
+        }
+        Ok(())
+    }
+
+    fn crossfade(&self) -> f64 {
+        match self.transition {
+            ConcatTransition::Cut => 0.0,
+            ConcatTransition::Crossfade { seconds } => seconds,
+        }
+    }
+}
+
+/// One recording to join, with its chapter markers
+pub struct ConcatClip {
+    pub path: PathBuf,
+    pub markers: Vec<RecordingMarker>,
+}
+
+/// A normalised piece of the output
+struct Part {
+    path: PathBuf,
+    duration: f64,
+    markers: Vec<RecordingMarker>,
+}
+
+fn even(value: u32) -> u32 {
+    (value & !1).max(2)
+}
+
+/// A full-frame card with `text` centred on `background`
+fn title_card(
+    text: &str,
+    width: u32,
+    height: u32,
+    options: &ConcatOptions,
+    font: &ab_glyph::FontVec,
+) -> Result<RgbaImage, CaptureError> {
+    let mut card = RgbaImage::from_pixel(width, height, Rgba(options.title_background));
+    let shape = OverlayShape::Text {
+        x: 0.0,
+        y: 0.0,
+        text: text.trim().to_string(),
+        font_size: (height as f64 / 12.0).max(12.0),
+        color: options.title_color,
+        background: None,
+    };
+    if let Some((label, _, _)) = render::draw(&shape, Some(font), width, height)? {
+        let x = (width.saturating_sub(label.width()) / 2) as i64;
+        let y = (height.saturating_sub(label.height()) / 2) as i64;
+        image::imageops::overlay(&mut card, &label, x, y);
+    }
+    Ok(card)
+}
+
+/// Join `clips` in order into `output` (MP4). Returns the clips' markers moved onto
+/// the new timeline, plus one per title card.
+pub fn export(
+    clips: &[ConcatClip],
+    output: &Path,
+    options: &ConcatOptions,
+    job: &ExportJob,
+) -> Result<Vec<RecordingMarker>, CaptureError> {
+    options.validate(clips.len())?;
+    let infos = clips.iter().map(|c| ffmpeg::probe(&c.path)).collect::<Result<Vec<_>, _>>()?;
+    if let Some((clip, _)) = clips.iter().zip(&infos).find(|(_, i)| i.duration <= 0.0 || i.width == 0) {
+        return Err(CaptureError::ExportFailed(format!("{} has no frames", clip.path.display())));
+    }
+
+    let (width, height) = match (options.width, options.height) {
+        (Some(w), Some(h)) => (even(w), even(h)),
+        _ => (even(infos[0].width), even(infos[0].height)),
+    };
+    let fps = options
+        .fps
+        .unwrap_or_else(|| infos.iter().map(|i| i.fps).fold(0.0, f64::max))
+        .clamp(0.0, MAX_FPS);
+    let fps = if fps > 0.0 { fps } else { 30.0 };
+    let with_audio = infos.iter().any(|i| i.has_audio);
+    let fade = options.crossfade();
+    let titled = |i: usize| options.titles.get(i).map(|t| t.trim()).filter(|t| !t.is_empty());
+    let font = (0..clips.len()).any(|i| titled(i).is_some()).then(load_font);
+
+    let codec = format::resolve(OutputFormat::Mp4, with_audio, &QualityPreset::High, fps.round() as u32, 2.0)?;
+    let scratch = ScratchDir::new("concat")?;
+    let video_filter = format!(
+        "scale={width}:{height}:force_original_aspect_ratio=decrease,\
+         pad={width}:{height}:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1,fps={fps},format=yuv420p"
+    );
+    let total: f64 = infos.iter().map(|i| i.duration).sum::<f64>()
+        + (0..clips.len()).filter(|&i| titled(i).is_some()).count() as f64 * options.title_seconds;
+    let normalise_share = if fade > 0.0 { 0.6 } else { 0.95 };
+    let mut done = 0.0;
+    let mut parts: Vec<Part> = Vec::new();
+
+    for (i, (clip, info)) in clips.iter().zip(&infos).enumerate() {
+        if let (Some(text), Some(font)) = (titled(i), &font) {
+            let image = scratch.join(&format!("card-{i}.png"));
+            title_card(text, width, height, options, font)?.save(&image)?;
+            let path = scratch.join(&format!("part-{}.mp4", parts.len()));
+            let seconds = format!("{:.3}", options.title_seconds);
+            let mut args: Vec<String> = vec![
+                "-loop".into(),
+                "1".into(),
+                "-framerate".into(),
+                fps.to_string(),
+                "-t".into(),
+                seconds.clone(),
+                "-i".into(),
+                image.to_string_lossy().into_owned(),
+            ];
+            if with_audio {
+                args.extend(["-f".into(), "lavfi".into(), "-t".into(), seconds, "-i".into(), SILENCE.into()]);
+                args.extend(["-map".into(), "0:v".into(), "-map".into(), "1:a".into()]);
+            }
+            args.extend(["-vf".into(), "setsar=1,format=yuv420p".into()]);
+            args.extend(part_args(&codec, &path));
+            let weight = options.title_seconds / total;
+            ffmpeg::run(&args, options.title_seconds, job.cancel_flag(), |p| {
+                job.progress(normalise_share * (done + p * weight))
+            })?;
+            done += weight;
+            parts.push(Part {
+                duration: ffmpeg::probe(&path)?.duration,
+                path,
+                markers: vec![RecordingMarker { time: 0.0, name: text.to_string() }],
+            });
+        }
+
+        let path = scratch.join(&format!("part-{}.mp4", parts.len()));
+        let mut args: Vec<String> = vec!["-i".into(), clip.path.to_string_lossy().into_owned()];
+        if with_audio && !info.has_audio {
+            // Silence keeps the audio layout identical across parts
+            let seconds = format!("{:.6}", info.duration);
+            args.extend(["-f".into(), "lavfi".into(), "-t".into(), seconds, "-i".into(), SILENCE.into()]);
+        }
+        args.extend(["-map".into(), "0:v:0".into(), "-vf".into(), video_filter.clone()]);
+        if with_audio {
+            let source = if info.has_audio { "0:a:0" } else { "1:a:0" };
+            args.extend(["-map".into(), source.into(), "-af".into(), AUDIO_FORMAT.into()]);
+        } else {
+            args.push("-an".into());
+        }
+        args.extend(part_args(&codec, &path));
+        let weight = info.duration / total;
+        ffmpeg::run(&args, info.duration, job.cancel_flag(), |p| {
+            job.progress(normalise_share * (done + p * weight))
+        })?;
+        done += weight;
+        parts.push(Part { duration: ffmpeg::probe(&path)?.duration, path, markers: clip.markers.clone() });
+    }
+
+    if fade > 0.0 {
+        // Each part is blended into at both ends, so both fades must fit
+        if let Some(short) = parts.iter().find(|p| p.duration < 2.0 * fade) {
+            return Err(CaptureError::InvalidConfig(format!(
+                "A {:.1}s part is too short for {fade:.1}s crossfades",
+                short.duration
+            )));
+        }
+    }
+
+    let (starts, length) = timeline(&parts, fade);
+
+    let joined = if fade > 0.0 {
+        crossfade(&parts, &starts, fade, &codec, output, length, job)
+    } else {
+        let paths: Vec<PathBuf> = parts.iter().map(|p| p.path.clone()).collect();
+        super::join(&paths, &scratch, &[], output, length, job.cancel_flag(), |p| job.progress(0.95 + 0.05 * p))
+    };
+    if let Err(e) = joined {
+        let _ = std::fs::remove_file(output);
+        return Err(e);
+    }
+
+    Ok(remap_markers(&parts, &starts))
+}
+
+/// Where each part starts on the output timeline, overlapping by `fade`, and the output length
+fn timeline(parts: &[Part], fade: f64) -> (Vec<f64>, f64) {
+    let mut starts = Vec::with_capacity(parts.len());
+    let mut length = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        starts.push(if i == 0 { 0.0 } else { length - fade });
+        length = starts[i] + part.duration;
+    }
+    (starts, length)
+}
+
+/// Every part's markers moved onto the output timeline, kept inside their part
+fn remap_markers(parts: &[Part], starts: &[f64]) -> Vec<RecordingMarker> {
+    parts
+        .iter()
+        .zip(starts)
+        .flat_map(|(part, start)| {
+            part.markers.iter().map(move |m| RecordingMarker {
+                time: start + m.time.clamp(0.0, part.duration),
+                name: m.name.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Blend the parts into each other with `xfade`/`acrossfade` and encode once more
+fn crossfade(
+    parts: &[Part],
+    starts: &[f64],
+    fade: f64,
+    codec: &FormatArgs,
+    output: &Path,
+    length: f64,
+    job: &ExportJob,
+) -> Result<(), CaptureError> {
+    let with_audio = !codec.audio.is_empty();
+    let mut args: Vec<String> = Vec::new();
+    for part in parts {
+        args.extend(["-i".into(), part.path.to_string_lossy().into_owned()]);
+    }
+    let mut graph = String::new();
+    let (mut video, mut audio) = ("0:v".to_string(), "0:a".to_string());
+    for (i, start) in starts.iter().enumerate().skip(1) {
+        let _ = write!(graph, "[{video}][{i}:v]xfade=transition=fade:duration={fade:.3}:offset={start:.3}[v{i}];");
+        video = format!("v{i}");
+        if with_audio {
+            let _ = write!(graph, "[{audio}][{i}:a]acrossfade=d={fade:.3}[a{i}];");
+            audio = format!("a{i}");
+        }
+    }
+    graph.pop();
+    args.extend(["-filter_complex".into(), graph, "-map".into(), format!("[{video}]")]);
+    if with_audio {
+        args.extend(["-map".into(), format!("[{audio}]")]);
+    }
+    args.extend(codec.video.iter().cloned());
+    args.extend(codec.audio.iter().map(|a| a.to_string()));
+    args.extend(["-movflags".into(), "+faststart".into(), output.to_string_lossy().into_owned()]);
+    ffmpeg::run(&args, length, job.cancel_flag(), |p| job.progress(0.6 + 0.4 * p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(time: f64, name: &str) -> RecordingMarker {
+        RecordingMarker { time, name: name.into() }
+    }
+
+    fn part(duration: f64, markers: Vec<RecordingMarker>) -> Part {
+        Part { path: PathBuf::new(), duration, markers }
+    }
+
+    #[test]
+    fn cuts_lay_parts_end_to_end() {
+        let parts = [part(10.0, vec![]), part(5.0, vec![]), part(2.5, vec![])];
+        assert_eq!(timeline(&parts, 0.0), (vec![0.0, 10.0, 15.0], 17.5));
+    }
+
+    #[test]
+    fn crossfades_overlap_each_part_with_the_previous() {
+        let parts = [part(10.0, vec![]), part(5.0, vec![]), part(2.5, vec![])];
+        assert_eq!(timeline(&parts, 1.0), (vec![0.0, 9.0, 13.0], 15.5));
+    }
+
+    #[test]
+    fn markers_follow_their_part() {
+        let parts = [
+            part(2.5, vec![marker(0.0, "Intro")]),
+            part(10.0, vec![marker(1.0, "Setup"), marker(8.0, "Demo")]),
+            part(5.0, vec![marker(4.0, "Wrap-up")]),
+        ];
+        let (starts, _) = timeline(&parts, 0.5);
+        assert_eq!(
+            remap_markers(&parts, &starts),
+            vec![marker(0.0, "Intro"), marker(3.0, "Setup"), marker(10.0, "Demo"), marker(15.5, "Wrap-up")]
+        );
+    }
+
+    #[test]
+    fn markers_outside_their_part_are_clamped_to_it() {
+        let parts = [part(4.0, vec![marker(-1.0, "Early"), marker(6.0, "Late")]), part(3.0, vec![])];
+        let (starts, _) = timeline(&parts, 0.0);
+        assert_eq!(remap_markers(&parts, &starts), vec![marker(0.0, "Early"), marker(4.0, "Late")]);
+    }
+}
@@ -0,0 +1,399 @@
+//! Aspect-ratio ("social") export: a capture placed on a background at a fixed
+//! aspect ratio and resolution, with padding, rounded corners and a drop shadow.
+//!
+//! The static layers (background, shadow and the rounded-corner mask) are drawn
+//! here as PNGs; ffmpeg composites them with the capture, so screenshots and
+//! recordings go through the same filter graph. Named presets of the settings are
+//! kept next to the history.
+use super::{ExportJob, ScratchDir};
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::capture::recording::encoder::format;
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use crate::services::storage::manager::StorageManager;
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const MIN_SIZE: u32 = 320;
+const MAX_SIZE: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AspectRatio {
+    #[default]
+    #[serde(rename = "16:9")]
+    Landscape,
+    #[serde(rename = "1:1")]
+    Square,
+    #[serde(rename = "9:16")]
+    Portrait,
+    #[serde(rename = "4:5")]
+    Tall,
+}
+
+impl AspectRatio {
+    fn ratio(&self) -> (u32, u32) {
+        match self {
+            AspectRatio::Landscape => (16, 9),
+            AspectRatio::Square => (1, 1),
+            AspectRatio::Portrait => (9, 16),
+            AspectRatio::Tall => (4, 5),
+        }
+    }
+
+    /// Canvas size with the longer side `size` pixels, both sides even
+    fn canvas(&self, size: u32) -> (u32, u32) {
+        let (w, h) = self.ratio();
+        let even = |v: f64| ((v / 2.0).round() as u32 * 2).max(2);
+        if w >= h {
+            (even(size as f64), even(size as f64 * h as f64 / w as f64))
+        } else {
+            (even(size as f64 * w as f64 / h as f64), even(size as f64))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FramingBackground {
+    /// RGBA colour
+    Solid { color: [u8; 4] },
+    /// Linear gradient; `angle` in degrees, 0 runs left to right, 90 top to bottom
+    Gradient { from: [u8; 4], to: [u8; 4], angle: f64 },
+    /// The capture itself, scaled to fill the canvas and blurred
+    Blur { radius: u32 },
+}
+
+impl Default for FramingBackground {
+    fn default() -> Self {
+        FramingBackground::Gradient { from: [91, 66, 243, 255], to: [175, 64, 255, 255], angle: 45.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FramingShadow {
+    /// Blur radius in output pixels
+    pub blur: u32,
+    /// 0..1
+    pub opacity: f64,
+    /// Downward offset in output pixels
+    pub offset: i32,
+}
+
+impl Default for FramingShadow {
+    fn default() -> Self {
+        Self { blur: 30, opacity: 0.45, offset: 12 }
+    }
+}
+
+fn default_size() -> u32 {
+    1920
+}
+
+fn default_padding() -> u32 {
+    80
+}
+
+fn default_corner_radius() -> u32 {
+    16
+}
+
+fn default_shadow() -> Option<FramingShadow> {
+    Some(FramingShadow::default())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FramingOptions {
+    #[serde(default)]
+    pub aspect: AspectRatio,
+    /// Length of the canvas's longer side
+    #[serde(default = "default_size")]
+    pub size: u32,
+    #[serde(default)]
+    pub background: FramingBackground,
+    /// Minimum space between the capture and the canvas edge
+    #[serde(default = "default_padding")]
+    pub padding: u32,
+    #[serde(default = "default_corner_radius")]
+    pub corner_radius: u32,
+    #[serde(default = "default_shadow")]
+    pub shadow: Option<FramingShadow>,
+}
+
+impl Default for FramingOptions {
+    fn default() -> Self {
+        Self {
+            aspect: AspectRatio::default(),
+            size: default_size(),
+            background: FramingBackground::default(),
+            padding: default_padding(),
+            corner_radius: default_corner_radius(),
+            shadow: default_shadow(),
+        }
+    }
+}
+
+impl FramingOptions {
+    fn validate(&self) -> Result<(), CaptureError> {
+        if !(MIN_SIZE..=MAX_SIZE).contains(&self.size) {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Output size must be between {MIN_SIZE} and {MAX_SIZE} pixels"
+            )));
+        }
+        let (width, height) = self.aspect.canvas(self.size);
+        if self.padding * 2 >= width.min(height) {
+            return Err(CaptureError::InvalidConfig("Padding leaves no room for the capture".into()));
+        }
+        if let Some(shadow) = &self.shadow {
+            if !(0.0..=1.0).contains(&shadow.opacity) {
+                return Err(CaptureError::InvalidConfig("Shadow opacity must be between 0 and 1".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Framing settings saved under a name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FramingPreset {
+    pub name: String,
+    pub options: FramingOptions,
+}
+
+/// Presets offered before the user has saved any
+fn builtin_presets() -> Vec<FramingPreset> {
+    [("Landscape 16:9", AspectRatio::Landscape), ("Square 1:1", AspectRatio::Square), ("Portrait 9:16", AspectRatio::Portrait)]
+        .into_iter()
+        .map(|(name, aspect)| FramingPreset { name: name.into(), options: FramingOptions { aspect, ..Default::default() } })
+        .collect()
+}
+
+/// Saved presets, or the built-in ones if none have been saved
+pub fn load_presets() -> Vec<FramingPreset> {
+    std::fs::read_to_string(StorageManager::framing_presets_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_else(builtin_presets)
+}
+
+fn save_presets(presets: &[FramingPreset]) -> Result<(), CaptureError> {
+    let path = StorageManager::framing_presets_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(presets)?)?;
+    Ok(())
+}
+
+/// Add `preset`, replacing any preset with the same name. Returns all presets.
+pub fn save_preset(preset: FramingPreset) -> Result<Vec<FramingPreset>, CaptureError> {
+    let name = preset.name.trim().to_string();
+    if name.is_empty() {
+        return Err(CaptureError::InvalidConfig("Presets need a name".into()));
+    }
+    preset.options.validate()?;
+    let mut presets = load_presets();
+    let preset = FramingPreset { name, ..preset };
+    match presets.iter_mut().find(|p| p.name == preset.name) {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+    save_presets(&presets)?;
+    Ok(presets)
+}
+
+/// Remove the preset called `name`. Returns false if there was none.
+pub fn delete_preset(name: &str) -> Result<bool, CaptureError> {
+    let mut presets = load_presets();
+    let before = presets.len();
+    presets.retain(|p| p.name != name);
+    if presets.len() == before {
+        return Ok(false);
+    }
+    save_presets(&presets)?;
+    Ok(true)
+}
+
+/// The preset called `name`
+pub fn find_preset(name: &str) -> Result<FramingOptions, CaptureError> {
+    load_presets()
+        .into_iter()
+        .find(|p| p.name == name)
+        .map(|p| p.options)
+        .ok_or_else(|| CaptureError::InvalidConfig(format!("No framing preset called \"{name}\"")))
+}
+
+/// Where the capture sits on the canvas
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    content_width: u32,
+    content_height: u32,
+}
+
+fn layout(options: &FramingOptions, source_width: u32, source_height: u32) -> Layout {
+    let (width, height) = options.aspect.canvas(options.size);
+    let room_w = (width - 2 * options.padding) as f64;
+    let room_h = (height - 2 * options.padding) as f64;
+    let scale = (room_w / source_width as f64).min(room_h / source_height as f64);
+    let even = |v: f64| ((v / 2.0).floor() as u32 * 2).max(2);
+    let content_width = even(source_width as f64 * scale);
+    let content_height = even(source_height as f64 * scale);
+    Layout {
+        width,
+        height,
+        x: (width - content_width) / 2,
+        y: (height - content_height) / 2,
+        content_width,
+        content_height,
+    }
+}
+
+/// Coverage (0..1) of pixel (`px`, `py`) by a `w` x `h` rectangle with corners of `radius`
+fn rounded_coverage(px: u32, py: u32, w: u32, h: u32, radius: f64) -> f64 {
+    let radius = radius.min(w.min(h) as f64 / 2.0);
+    let (x, y) = (px as f64 + 0.5, py as f64 + 0.5);
+    // Distance into the corner region, if the pixel is in one
+    let cx = if x < radius { radius - x } else if x > w as f64 - radius { x - (w as f64 - radius) } else { 0.0 };
+    let cy = if y < radius { radius - y } else if y > h as f64 - radius { y - (h as f64 - radius) } else { 0.0 };
+    if cx == 0.0 || cy == 0.0 {
+        return 1.0;
+    }
+    (radius - cx.hypot(cy) + 0.5).clamp(0.0, 1.0)
+}
+
+fn mask(layout: &Layout, radius: u32) -> GrayImage {
+    GrayImage::from_fn(layout.content_width, layout.content_height, |x, y| {
+        let coverage = rounded_coverage(x, y, layout.content_width, layout.content_height, radius as f64);
+        Luma([(coverage * 255.0).round() as u8])
+    })
+}
+
+fn shadow(layout: &Layout, radius: u32, shadow: &FramingShadow) -> RgbaImage {
+    let mut image = RgbaImage::new(layout.width, layout.height);
+    let top = layout.y as i64 + shadow.offset as i64;
+    for y in 0..layout.content_height {
+        let Ok(py) = u32::try_from(top + y as i64) else { continue };
+        if py >= layout.height {
+            continue;
+        }
+        for x in 0..layout.content_width {
+            let coverage = rounded_coverage(x, y, layout.content_width, layout.content_height, radius as f64);
+            let alpha = (coverage * shadow.opacity * 255.0).round() as u8;
+            image.put_pixel(layout.x + x, py, Rgba([0, 0, 0, alpha]));
+        }
+    }
+    if shadow.blur > 0 {
+        // fast_blur's sigma is about a third of the visible spread
+        image = image::imageops::fast_blur(&image, shadow.blur as f32 / 3.0);
+    }
+    image
+}
+
+fn gradient(layout: &Layout, from: [u8; 4], to: [u8; 4], angle: f64) -> RgbaImage {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (w, h) = (layout.width as f64, layout.height as f64);
+    // Project onto the gradient direction, normalised so the corners reach 0 and 1
+    let extent = (w * cos).abs() + (h * sin).abs();
+    RgbaImage::from_fn(layout.width, layout.height, |x, y| {
+        let along = ((x as f64 - w / 2.0) * cos + (y as f64 - h / 2.0) * sin) / extent + 0.5;
+        let t = along.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgba([mix(from[0], to[0]), mix(from[1], to[1]), mix(from[2], to[2]), mix(from[3], to[3])])
+    })
+}
+
+/// Render `input` framed by `options` into `output`. Recordings become MP4 with the
+/// source audio; screenshots become PNG.
+pub fn export(
+    input: &Path,
+    output: &Path,
+    is_video: bool,
+    options: &FramingOptions,
+    job: &ExportJob,
+) -> Result<(), CaptureError> {
+    options.validate()?;
+    let info = ffmpeg::probe(input)?;
+    if info.width == 0 || info.height == 0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no picture", input.display())));
+    }
+    let layout = layout(options, info.width, info.height);
+    let scratch = ScratchDir::new("framing")?;
+    let fps = if info.fps > 0.0 { info.fps } else { 30.0 };
+
+    let mask_path = scratch.join("mask.png");
+    mask(&layout, options.corner_radius).save(&mask_path)?;
+    let shadow_path = scratch.join("shadow.png");
+    match &options.shadow {
+        Some(s) => shadow(&layout, options.corner_radius, s),
+        None => RgbaImage::new(layout.width, layout.height),
+    }
+    .save(&shadow_path)?;
+    let background_path = scratch.join("background.png");
+    match options.background {
+        FramingBackground::Solid { color } => {
+            RgbaImage::from_pixel(layout.width, layout.height, Rgba(color)).save(&background_path)?
+        }
+        FramingBackground::Gradient { from, to, angle } => gradient(&layout, from, to, angle).save(&background_path)?,
+        FramingBackground::Blur { .. } => {}
+    }
+
+    // Stills are looped at the recording's rate so they don't set the output's
+    let still = |path: &Path| -> Vec<String> {
+        let mut args = Vec::new();
+        if is_video {
+            args.extend(["-framerate".into(), format!("{fps}"), "-loop".into(), "1".into()]);
+        }
+        args.extend(["-i".into(), path.to_string_lossy().into_owned()]);
+        args
+    };
+    let mut args: Vec<String> = vec!["-i".into(), input.to_string_lossy().into_owned()];
+    args.extend(still(&shadow_path));
+    args.extend(still(&mask_path));
+
+    let (w, h) = (layout.width, layout.height);
+    let background = match options.background {
+        FramingBackground::Blur { radius } => format!(
+            "[0:v]split=2[src][under];\
+             [under]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},gblur=sigma={:.2},format=rgba[bg]",
+            (radius as f64 / 3.0).max(0.5)
+        ),
+        _ => {
+            args.extend(still(&background_path));
+            "[0:v]null[src];[3:v]format=rgba[bg]".into()
+        }
+    };
+    let finish = if is_video { "format=yuv420p" } else { "format=rgba" };
+    let graph = format!(
+        "{background};\
+         [bg][1:v]overlay=0:0:shortest=1[shadowed];\
+         [src]scale={cw}:{ch}:flags=lanczos,format=rgba[scaled];[2:v]format=gray[mask];\
+         [scaled][mask]alphamerge[content];\
+         [shadowed][content]overlay={x}:{y}:shortest=1,{finish}[out]",
+        cw = layout.content_width,
+        ch = layout.content_height,
+        x = layout.x,
+        y = layout.y,
+    );
+    args.extend(["-filter_complex".into(), graph, "-map".into(), "[out]".into()]);
+
+    if is_video {
+        let codec = format::resolve(OutputFormat::Mp4, false, &QualityPreset::High, fps.round() as u32, 2.0)?;
+        args.extend(["-map", "0:a?", "-c:a", "copy"].map(String::from));
+        args.extend(codec.video);
+        args.extend(["-movflags", "+faststart"].map(String::from));
+    } else {
+        args.extend(["-frames:v", "1", "-update", "1"].map(String::from));
+    }
+    args.push(output.to_string_lossy().into_owned());
+
+    let duration = if is_video { info.duration } else { 0.0 };
+    let result = ffmpeg::run(&args, duration, job.cancel_flag(), |p| job.progress(p));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
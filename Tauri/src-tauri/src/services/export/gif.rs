@@ -0,0 +1,303 @@
+//! GIF export from finished recordings.
+//!
+//! Three `ffmpeg` passes over the source video:
+//! 1. scene detection, so each scene gets a palette tuned to its own colours
+//! 2. one `palettegen` per scene, all from a single decode
+//! 3. `paletteuse` per scene with the chosen dither, concatenated into one GIF
+//!
+//! Duplicate frames are dropped with `mpdecimate`; the GIF keeps the previous frame
+//! on screen for longer instead of storing it again.
+use super::{ExportJob, ScratchDir};
+use crate::error::CaptureError;
+use crate::services::ffmpeg::{self, MediaInfo};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Scene score (0..1) above which a frame starts a new palette
+const SCENE_THRESHOLD: f64 = 0.3;
+/// Scenes shorter than this share the previous scene's palette
+const MIN_SCENE_SECONDS: f64 = 1.0;
+const MAX_SCENES: usize = 16;
+/// Guardrail for `Original`, which otherwise follows the source
+const ORIGINAL_MAX_WIDTH: u32 = 1920;
+const ORIGINAL_MAX_FPS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GifQuality {
+    Low,    // 10 fps, 640 wide
+    #[default]
+    Medium, // 15 fps, 960 wide
+    High,   // 20 fps, 1280 wide
+    Original,
+}
+
+impl GifQuality {
+    pub fn fps(&self) -> u32 {
+        match self {
+            GifQuality::Low => 10,
+            GifQuality::Medium => 15,
+            GifQuality::High => 20,
+            GifQuality::Original => ORIGINAL_MAX_FPS,
+        }
+    }
+
+    pub fn max_width(&self) -> u32 {
+        match self {
+            GifQuality::Low => 640,
+            GifQuality::Medium => 960,
+            GifQuality::High => 1280,
+            GifQuality::Original => ORIGINAL_MAX_WIDTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GifDither {
+    None,
+    /// Ordered dither; `scale` 0..=5, higher is less visible but larger
+    Bayer { scale: u8 },
+    FloydSteinberg,
+    Sierra2_4a,
+}
+
+impl Default for GifDither {
+    fn default() -> Self {
+        GifDither::Bayer { scale: 3 }
+    }
+}
+
+impl GifDither {
+    fn filter_args(&self) -> String {
+        match self {
+            GifDither::None => "dither=none".into(),
+            GifDither::Bayer { scale } => format!("dither=bayer:bayer_scale={}", scale.min(&5)),
+            GifDither::FloydSteinberg => "dither=floyd_steinberg".into(),
+            GifDither::Sierra2_4a => "dither=sierra2_4a".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GifExportOptions {
+    #[serde(default)]
+    pub quality: GifQuality,
+    /// Overrides the preset's frame rate
+    #[serde(default)]
+    pub fps: Option<u32>,
+    /// Overrides the preset's width limit; never upscales
+    #[serde(default)]
+    pub max_width: Option<u32>,
+    #[serde(default)]
+    pub dither: GifDither,
+    #[serde(default = "super::default_true")]
+    pub remove_duplicates: bool,
+    #[serde(default = "super::default_true")]
+    pub per_scene_palette: bool,
+}
+
+impl Default for GifExportOptions {
+    fn default() -> Self {
+        Self {
+            quality: GifQuality::default(),
+            fps: None,
+            max_width: None,
+            dither: GifDither::default(),
+            remove_duplicates: true,
+            per_scene_palette: true,
+        }
+    }
+}
+
+impl GifExportOptions {
+    pub(super) fn validate(&self) -> Result<(), CaptureError> {
+        if matches!(self.fps, Some(fps) if !(1..=50).contains(&fps)) {
+            return Err(CaptureError::InvalidConfig("GIF frame rate must be between 1 and 50".into()));
+        }
+        if matches!(self.max_width, Some(w) if w < 16) {
+            return Err(CaptureError::InvalidConfig("GIF width must be at least 16 pixels".into()));
+        }
+        Ok(())
+    }
+
+    /// Frame rate and width for a given source, never exceeding it
+    pub(super) fn resolve(&self, source: &MediaInfo) -> (u32, u32) {
+        let mut fps = self.fps.unwrap_or_else(|| self.quality.fps());
+        if source.fps >= 1.0 {
+            fps = fps.min(source.fps.round() as u32);
+        }
+        let mut width = self.max_width.unwrap_or_else(|| self.quality.max_width());
+        if source.width > 0 {
+            width = width.min(source.width);
+        }
+        (fps.max(1), width)
+    }
+}
+
+/// Export `input` as a GIF at `output`, reporting progress on `job`
+pub fn export(input: &Path, output: &Path, options: &GifExportOptions, job: &ExportJob) -> Result<(), CaptureError> {
+    options.validate()?;
+    let source = ffmpeg::probe(input)?;
+    let (fps, width) = options.resolve(&source);
+    let base = format!("fps={fps},scale='min({width},iw)':-1:flags=lanczos");
+    let scratch = ScratchDir::new("gif")?;
+
+    // Progress budget: scene detection, palettes, encode
+    let (detect_end, palette_end) = if options.per_scene_palette { (0.15, 0.35) } else { (0.0, 0.25) };
+
+    let scenes = if options.per_scene_palette {
+        detect_scenes(input, &source, &base, &scratch, job, 0.0, detect_end)?
+    } else {
+        vec![(0.0, source.duration)]
+    };
+    log::info!("Exporting GIF at {fps} fps, {width}px wide, {} palette(s)", scenes.len());
+
+    // Stage A: one palette per scene from a single decode
+    let mut graph = format!("[0:v]{base},split={}", scenes.len());
+    graph.extend((0..scenes.len()).map(|i| format!("[s{i}]")));
+    let mut args: Vec<String> = vec!["-i".into(), input.to_string_lossy().into_owned()];
+    let mut palettes = Vec::with_capacity(scenes.len());
+    for (i, (start, end)) in scenes.iter().enumerate() {
+        graph.push_str(&format!(";[s{i}]{},palettegen=stats_mode=diff[p{i}]", trim(*start, *end)));
+        let palette = scratch.join(&format!("palette-{i}.png"));
+        args.extend(["-map".into(), format!("[p{i}]"), "-update".into(), "1".into()]);
+        args.push(palette.to_string_lossy().into_owned());
+        palettes.push(palette);
+    }
+    args.splice(2..2, ["-filter_complex".to_string(), graph]);
+    ffmpeg::run(&args, source.duration, job.cancel_flag(), |p| {
+        job.progress(detect_end + p * (palette_end - detect_end))
+    })?;
+
+    // Stage B: map each scene through its palette, then join the scenes back up
+    let mut args: Vec<String> = vec!["-i".into(), input.to_string_lossy().into_owned()];
+    for palette in &palettes {
+        args.extend(["-i".into(), palette.to_string_lossy().into_owned()]);
+    }
+    let mut graph = format!("[0:v]{base},split={}", scenes.len());
+    graph.extend((0..scenes.len()).map(|i| format!("[s{i}]")));
+    let decimate = if options.remove_duplicates { ",mpdecimate" } else { "" };
+    for (i, (start, end)) in scenes.iter().enumerate() {
+        graph.push_str(&format!(
+            ";[s{i}]{}{decimate}[t{i}];[t{i}][{}:v]paletteuse={}:diff_mode=rectangle[g{i}]",
+            trim(*start, *end),
+            i + 1,
+            options.dither.filter_args()
+        ));
+    }
+    graph.extend((0..scenes.len()).map(|i| format!("[g{i}]")));
+    graph.push_str(&format!("concat=n={}:v=1:a=0[out]", scenes.len()));
+    args.extend(["-filter_complex".into(), graph, "-map".into(), "[out]".into()]);
+    // Variable frame rate keeps decimated frames out instead of re-duplicating them
+    args.extend(["-vsync".into(), "vfr".into(), "-loop".into(), "0".into()]);
+    args.push(output.to_string_lossy().into_owned());
+
+    let result = ffmpeg::run(&args, source.duration, job.cancel_flag(), |p| {
+        job.progress(palette_end + p * (1.0 - palette_end))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
+
+/// `trim` + timestamp reset for one scene; the last scene runs to the end
+fn trim(start: f64, end: f64) -> String {
+    if end.is_finite() {
+        format!("trim=start={start:.3}:end={end:.3},setpts=PTS-STARTPTS")
+    } else {
+        format!("trim=start={start:.3},setpts=PTS-STARTPTS")
+    }
+}
+
+/// Split the video into `(start, end)` scenes at strong content changes
+fn detect_scenes(
+    input: &Path,
+    source: &MediaInfo,
+    base: &str,
+    scratch: &ScratchDir,
+    job: &ExportJob,
+    progress_start: f64,
+    progress_end: f64,
+) -> Result<Vec<(f64, f64)>, CaptureError> {
+    let cuts_file = scratch.join("scenes.txt");
+    // `metadata=print` writes "frame:N pts:X pts_time:T" for every selected frame.
+    // The path is escaped for the filtergraph parser.
+    let escaped = cuts_file.to_string_lossy().replace('\\', "/").replace(':', "\\\\:").replace('\'', "\\\\'");
+    let filter = format!("{base},select='gt(scene,{SCENE_THRESHOLD})',metadata=print:file={escaped}");
+
+    let args: Vec<std::ffi::OsString> = vec![
+        "-i".into(),
+        input.into(),
+        "-vf".into(),
+        filter.into(),
+        "-an".into(),
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ];
+    ffmpeg::run(&args, source.duration, job.cancel_flag(), |p| {
+        job.progress(progress_start + p * (progress_end - progress_start))
+    })?;
+
+    let cuts: Vec<f64> = std::fs::read_to_string(&cuts_file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_whitespace().find_map(|f| f.strip_prefix("pts_time:")))
+        .filter_map(|t| t.parse().ok())
+        .collect();
+
+    Ok(scenes_from_cuts(&cuts, source.duration))
+}
+
+fn scenes_from_cuts(cuts: &[f64], duration: f64) -> Vec<(f64, f64)> {
+    let mut starts = vec![0.0];
+    for &cut in cuts {
+        let last = *starts.last().unwrap();
+        let long_enough_before = cut - last >= MIN_SCENE_SECONDS;
+        let long_enough_after = duration <= 0.0 || duration - cut >= MIN_SCENE_SECONDS;
+        if long_enough_before && long_enough_after {
+            starts.push(cut);
+        }
+    }
+
+    // Too many scenes: keep evenly spaced cuts so the palettes still cover the whole video
+    if starts.len() > MAX_SCENES {
+        let step = starts.len() as f64 / MAX_SCENES as f64;
+        starts = (0..MAX_SCENES).map(|i| starts[(i as f64 * step) as usize]).collect();
+    }
+
+    let ends = starts.iter().skip(1).copied().chain(std::iter::once(f64::INFINITY));
+    starts.iter().copied().zip(ends).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_split_the_video_into_scenes() {
+        assert_eq!(scenes_from_cuts(&[], 10.0), vec![(0.0, f64::INFINITY)]);
+        assert_eq!(scenes_from_cuts(&[4.0, 7.0], 10.0), vec![(0.0, 4.0), (4.0, 7.0), (7.0, f64::INFINITY)]);
+    }
+
+    #[test]
+    fn short_scenes_share_a_palette() {
+        // 0.5s after the start, 0.2s after a cut, and 0.5s before the end
+        assert_eq!(scenes_from_cuts(&[0.5, 3.0, 3.2, 9.5], 10.0), vec![(0.0, 3.0), (3.0, f64::INFINITY)]);
+        // An unknown duration keeps cuts near the end
+        assert_eq!(scenes_from_cuts(&[3.0, 9.5], 0.0), vec![(0.0, 3.0), (3.0, 9.5), (9.5, f64::INFINITY)]);
+    }
+
+    #[test]
+    fn many_cuts_are_thinned_evenly() {
+        let cuts: Vec<f64> = (1..=40).map(|i| i as f64 * 1.5).collect();
+        let scenes = scenes_from_cuts(&cuts, 100.0);
+        assert_eq!(scenes.len(), MAX_SCENES);
+        assert_eq!(scenes[0].0, 0.0);
+        assert_eq!(scenes[MAX_SCENES - 1].1, f64::INFINITY);
+        assert!(scenes.iter().all(|&(start, end)| end - start >= MIN_SCENE_SECONDS));
+        assert!(scenes[MAX_SCENES - 1].0 > 50.0);
+    }
+}
@@ -0,0 +1,320 @@
+//! Post-processing exports derived from finished captures.
+//!
+//! Each export runs as a job with an id the frontend can cancel by, and reports
+//! progress through `EXPORT_PROGRESS` while it runs.
+pub mod animated;
+pub mod commands;
+pub mod compress;
+pub mod concat;
+pub mod framing;
+pub mod gif;
+pub mod speed;
+pub mod zoom;
+
+use crate::capture::config::OutputFormat;
+use crate::capture::recording::encoder::format::FormatArgs;
+use crate::capture::recording::markers::{self, RecordingMarker};
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::services::storage::preview;
+use crate::state::app_state::AppState;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub job_id: String,
+    pub source_id: String,
+    pub kind: &'static str,
+    /// 0.0 ..= 1.0
+    pub progress: f64,
+}
+
+/// Cancellation flags of running exports, by job id
+#[derive(Default)]
+pub struct ExportJobs {
+    jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ExportJobs {
+    /// Returns false if no job with that id is running
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Release);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A running export. Unregisters itself when dropped.
+pub struct ExportJob {
+    pub id: String,
+    source_id: String,
+    kind: &'static str,
+    cancel: Arc<AtomicBool>,
+    app: tauri::AppHandle,
+}
+
+impl ExportJob {
+    pub fn start(
+        app: &tauri::AppHandle,
+        job_id: Option<String>,
+        source_id: &str,
+        kind: &'static str,
+    ) -> Result<Self, CaptureError> {
+        let id = job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let cancel = Arc::new(AtomicBool::new(false));
+        let state = app.state::<AppState>();
+        let mut jobs = state.exports.jobs.lock().unwrap();
+        if jobs.contains_key(&id) {
+            return Err(CaptureError::InvalidConfig(format!("Export {} is already running", id)));
+        }
+        jobs.insert(id.clone(), cancel.clone());
+
+        Ok(Self { id, source_id: source_id.to_string(), kind, cancel, app: app.clone() })
+    }
+
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancel
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Acquire)
+    }
+
+    pub fn progress(&self, progress: f64) {
+        let _ = self.app.emit(
+            crate::events::EXPORT_PROGRESS,
+            ExportProgress {
+                job_id: self.id.clone(),
+                source_id: self.source_id.clone(),
+                kind: self.kind,
+                progress: progress.clamp(0.0, 1.0),
+            },
+        );
+    }
+}
+
+impl Drop for ExportJob {
+    fn drop(&mut self) {
+        let state = self.app.state::<AppState>();
+        state.exports.jobs.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Path of the history item `id` in the storage directory
+pub fn source_path(state: &AppState, id: &str) -> Result<(CaptureItem, PathBuf), CaptureError> {
+    let storage = state.storage.lock().unwrap();
+    let item = storage
+        .history
+        .items
+        .iter()
+        .find(|i| i.id == id)
+        .cloned()
+        .ok_or_else(|| CaptureError::StorageError(format!("No capture with id {}", id)))?;
+    let path = storage.screenshots_dir().join(&item.filename);
+    if !path.is_file() {
+        return Err(CaptureError::StorageError(format!("{} is missing", path.display())));
+    }
+    Ok((item, path))
+}
+
+/// Refuse recordings saved as animated images, before ffmpeg fails on them deep in an edit
+pub fn ensure_video(source: &CaptureItem) -> Result<(), CaptureError> {
+    let extension = Path::new(&source.filename).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    match OutputFormat::from_extension(extension) {
+        Some(format) if !format.is_video() => Err(CaptureError::InvalidConfig(format!(
+            "{} is an {}; only video recordings can be edited or exported",
+            source.filename,
+            format.label()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Serde default for option flags that are on unless turned off
+pub(crate) fn default_true() -> bool {
+    true
+}
+
+/// Markers of an export that changed the recording's timing, or `None` when it kept
+/// the source's timeline (ffmpeg copies the chapters across)
+pub type Retimed = Option<Vec<RecordingMarker>>;
+
+/// What an export produces: its progress `kind` and the history item it adds
+pub struct ExportTarget {
+    kind: &'static str,
+    capture_type: CaptureType,
+    extension: &'static str,
+}
+
+impl ExportTarget {
+    pub fn new(kind: &'static str, capture_type: CaptureType, extension: &'static str) -> Self {
+        Self { kind, capture_type, extension }
+    }
+
+    /// A new MP4 recording
+    pub fn recording(kind: &'static str) -> Self {
+        Self::new(kind, CaptureType::Recording, "mp4")
+    }
+
+    /// A GIF, WebP or APNG animation
+    pub fn animation(kind: &'static str, extension: &'static str) -> Self {
+        Self::new(kind, CaptureType::Gif, extension)
+    }
+}
+
+/// Export into a new history item described by `target`.
+///
+/// `export(output, job)` runs on a blocking thread as a job of `source_id`. A new
+/// recording keeps `markers` unless the export returns retimed ones, which are then
+/// also written into the file as chapters.
+pub async fn run_export<F>(
+    app: &tauri::AppHandle,
+    source_id: &str,
+    job_id: Option<String>,
+    target: ExportTarget,
+    markers: Vec<RecordingMarker>,
+    export: F,
+) -> Result<CaptureItem, CaptureError>
+where
+    F: FnOnce(&Path, &ExportJob) -> Result<Retimed, CaptureError> + Send + 'static,
+{
+    let ExportTarget { kind, capture_type, extension } = target;
+    let state = app.state::<AppState>();
+    let (filename, output) = {
+        let storage = state.storage.lock().unwrap();
+        let filename = storage.generate_filename(&capture_type, extension);
+        let output = storage.screenshots_dir().join(&filename);
+        (filename, output)
+    };
+    if output.exists() {
+        return Err(CaptureError::StorageError(format!("{} already exists", output.display())));
+    }
+
+    let job = ExportJob::start(app, job_id, source_id, kind)?;
+    let retimed = tokio::task::spawn_blocking(move || {
+        let retimed = export(&output, &job)?;
+        if let Some(retimed) = retimed.as_deref().filter(|m| !m.is_empty()) {
+            let written =
+                ffmpeg::probe(&output).and_then(|info| markers::write_chapters(&output, retimed, info.duration));
+            if let Err(e) = written {
+                log::warn!("{} export saved without chapters: {}", kind, e);
+            }
+        }
+        Ok::<_, CaptureError>(retimed)
+    })
+    .await
+    .map_err(|e| CaptureError::ExportFailed(e.to_string()))??;
+
+    let item = match capture_type {
+        CaptureType::Recording => {
+            let mut item = CaptureItem::new_recording(filename);
+            item.markers = retimed.unwrap_or(markers);
+            item
+        }
+        CaptureType::Gif => CaptureItem::new_gif(filename),
+        CaptureType::Screenshot => CaptureItem::new_screenshot(filename),
+    };
+    {
+        let mut storage = state.storage.lock().unwrap();
+        storage.history.add(item.clone());
+        storage.save_history()?;
+    }
+    if matches!(item.capture_type, CaptureType::Recording) {
+        preview::generate_in_background(app, item.id.clone());
+    }
+    Ok(item)
+}
+
+/// `run_export` of the recording `id`, refusing screenshots, GIFs and animated images.
+/// `export(source, input, output, job)` gets the history item and its file.
+pub async fn run_recording_export<F>(
+    app: &tauri::AppHandle,
+    id: &str,
+    job_id: Option<String>,
+    target: ExportTarget,
+    export: F,
+) -> Result<CaptureItem, CaptureError>
+where
+    F: FnOnce(&CaptureItem, &Path, &Path, &ExportJob) -> Result<Retimed, CaptureError> + Send + 'static,
+{
+    let (source, input) = source_path(&app.state::<AppState>(), id)?;
+    if !matches!(source.capture_type, CaptureType::Recording) {
+        return Err(CaptureError::InvalidConfig(format!("{} is not a recording", source.filename)));
+    }
+    ensure_video(&source)?;
+    let markers = source.markers.clone();
+    run_export(app, id, job_id, target, markers, move |output, job| export(&source, &input, output, job)).await
+}
+
+/// Scratch directory for intermediate files, removed when dropped
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new(prefix: &str) -> Result<Self, CaptureError> {
+        let dir = std::env::temp_dir().join(format!("screencapture-{}-{}", prefix, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// `codec`'s encoder arguments, then `output`, for a part that `join` can join to
+/// others encoded the same way
+pub fn part_args(codec: &FormatArgs, output: &Path) -> Vec<String> {
+    let mut args: Vec<String> = codec.video.clone();
+    args.extend(codec.audio.iter().map(|a| a.to_string()));
+    // The concat demuxer needs every part on the same timescale
+    args.extend(["-video_track_timescale", "90000", "-avoid_negative_ts", "make_zero"].map(String::from));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// Join `parts` end to end into `output` with the concat demuxer, without
+/// re-encoding. `output_args` go before the output; `length` is the joined duration.
+pub fn join(
+    parts: &[PathBuf],
+    scratch: &ScratchDir,
+    output_args: &[&str],
+    output: &Path,
+    length: f64,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(f64),
+) -> Result<(), CaptureError> {
+    let mut list = String::new();
+    for part in parts {
+        let _ = writeln!(list, "file '{}'", part.to_string_lossy().replace('\'', r"'\''"));
+    }
+    let list_path = scratch.join("parts.txt");
+    std::fs::write(&list_path, list)?;
+
+    let mut args: Vec<String> = ["-f", "concat", "-safe", "0", "-i"].map(String::from).to_vec();
+    args.push(list_path.to_string_lossy().into_owned());
+    args.extend(["-map", "0", "-c", "copy"].map(String::from));
+    args.extend(output_args.iter().map(|a| a.to_string()));
+    args.extend(["-movflags".into(), "+faststart".into(), output.to_string_lossy().into_owned()]);
+    ffmpeg::run(args, length, cancel, on_progress)
+}
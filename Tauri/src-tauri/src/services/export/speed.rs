@@ -0,0 +1,366 @@
+//! Speed-change and time-lapse export.
+//!
+//! The recording is split into pieces of constant speed: the whole-clip speed,
+//! overridden by idle stretches (found with ffmpeg's `freezedetect`) and then by
+//! explicit segments. Each piece is re-timed and re-encoded on its own and the
+//! pieces are joined with the concat demuxer, as trimming does.
+use super::{join, part_args, ExportJob, ScratchDir};
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::capture::recording::encoder::format;
+use crate::capture::recording::markers::RecordingMarker;
+use crate::error::CaptureError;
+use crate::services::editor::TimeRange;
+use crate::services::ffmpeg::{self, MediaInfo};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 64.0;
+/// Pieces shorter than this are folded into their neighbour
+const MIN_PIECE_SECONDS: f64 = 0.1;
+/// Frame difference below which frames count as unchanged (`freezedetect` noise)
+const IDLE_NOISE: &str = "-50dB";
+/// Idle stretches are shrunk by this much at each end so motion starts at normal speed
+const IDLE_MARGIN_SECONDS: f64 = 0.25;
+
+/// A span of the recording played at its own speed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedSegment {
+    pub start: f64,
+    pub end: f64,
+    /// Playback rate; 2.0 plays twice as fast
+    pub speed: f64,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+fn default_idle_speed() -> f64 {
+    4.0
+}
+
+fn default_idle_seconds() -> f64 {
+    2.0
+}
+
+/// Speed up stretches where the screen doesn't change
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct IdleSpeedup {
+    #[serde(default = "default_idle_speed")]
+    pub speed: f64,
+    /// Shortest stretch without change that counts as idle
+    #[serde(default = "default_idle_seconds")]
+    pub min_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedExportOptions {
+    /// Speed of everything not covered by idle stretches or `segments`
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// Spans at their own speed; these win over idle detection
+    #[serde(default)]
+    pub segments: Vec<SpeedSegment>,
+    #[serde(default)]
+    pub idle: Option<IdleSpeedup>,
+    /// Keep the audio, pitch-corrected to the new speed; dropped otherwise
+    #[serde(default = "super::default_true")]
+    pub keep_audio: bool,
+}
+
+impl Default for SpeedExportOptions {
+    fn default() -> Self {
+        Self { speed: default_speed(), segments: Vec::new(), idle: None, keep_audio: true }
+    }
+}
+
+fn validate_speed(speed: f64) -> Result<(), CaptureError> {
+    if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        return Err(CaptureError::InvalidConfig(format!(
+            "Speed must be between {MIN_SPEED}× and {MAX_SPEED}×, got {speed}"
+        )));
+    }
+    Ok(())
+}
+
+impl SpeedExportOptions {
+    fn validate(&self) -> Result<(), CaptureError> {
+        validate_speed(self.speed)?;
+        for segment in &self.segments {
+            validate_speed(segment.speed)?;
+            if !segment.start.is_finite() || !segment.end.is_finite() || segment.end < segment.start {
+                return Err(CaptureError::InvalidConfig("Speed segments must have start <= end".into()));
+            }
+        }
+        if let Some(idle) = &self.idle {
+            validate_speed(idle.speed)?;
+            if !idle.min_seconds.is_finite() || idle.min_seconds < 1.0 {
+                return Err(CaptureError::InvalidConfig("Idle stretches must be at least a second long".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A span of the source played at one speed
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Piece {
+    range: TimeRange,
+    speed: f64,
+}
+
+/// Cover `0..duration` with constant-speed pieces; later layers win over earlier ones.
+/// Pieces that would play for less than one `frame` (seconds) are folded into a neighbour.
+fn plan(duration: f64, base: f64, layers: &[&[SpeedSegment]], frame: f64) -> Vec<Piece> {
+    let too_short =
+        |range: TimeRange, speed: f64| range.duration() < MIN_PIECE_SECONDS || range.duration() / speed < frame;
+    let mut cuts = vec![0.0, duration];
+    for segment in layers.iter().flat_map(|l| l.iter()) {
+        cuts.extend([segment.start.clamp(0.0, duration), segment.end.clamp(0.0, duration)]);
+    }
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup();
+
+    let mut pieces: Vec<Piece> = Vec::new();
+    for pair in cuts.windows(2) {
+        let range = TimeRange { start: pair[0], end: pair[1] };
+        let middle = (range.start + range.end) / 2.0;
+        let speed = layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.iter().rev().find(|s| middle >= s.start && middle < s.end))
+            .map_or(base, |s| s.speed);
+        match pieces.last_mut() {
+            Some(last) if last.speed == speed || too_short(range, speed) => last.range.end = range.end,
+            _ => pieces.push(Piece { range, speed }),
+        }
+    }
+    // A short first piece has nothing before it to join
+    if pieces.len() > 1 && too_short(pieces[0].range, pieces[0].speed) {
+        let first = pieces.remove(0);
+        pieces[0].range.start = first.range.start;
+    }
+    pieces
+}
+
+/// Where source time `time` lands on the output timeline
+fn output_time(pieces: &[Piece], time: f64) -> f64 {
+    pieces
+        .iter()
+        .map(|p| (time.min(p.range.end) - p.range.start).max(0.0) / p.speed)
+        .sum()
+}
+
+/// Stretches of `input` with no visible change for at least `min_seconds`
+fn idle_ranges(input: &Path, info: &MediaInfo, idle: &IdleSpeedup, job: &ExportJob) -> Result<Vec<SpeedSegment>, CaptureError> {
+    let scratch = ScratchDir::new("idle")?;
+    let log = scratch.join("freeze.txt");
+    // The metadata filter writes the detector's findings to a file we can parse
+    let filter = format!(
+        "freezedetect=n={IDLE_NOISE}:d={:.3},metadata=mode=print:file='{}'",
+        idle.min_seconds,
+        log.to_string_lossy().replace('\\', "/").replace('\'', r"\'").replace(':', r"\:")
+    );
+    let args = [
+        "-i".into(),
+        input.to_string_lossy().into_owned(),
+        "-map".into(),
+        "0:v:0".into(),
+        "-vf".into(),
+        filter,
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ];
+    ffmpeg::run(args, info.duration, job.cancel_flag(), |p| job.progress(0.2 * p))?;
+
+    // Lines look like "lavfi.freezedetect.freeze_start=12.3"
+    let text = std::fs::read_to_string(&log).unwrap_or_default();
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("lavfi.freezedetect.freeze_start=") {
+            start = value.trim().parse::<f64>().ok();
+        } else if let Some(value) = line.strip_prefix("lavfi.freezedetect.freeze_end=") {
+            if let (Some(from), Ok(to)) = (start.take(), value.trim().parse::<f64>()) {
+                ranges.push((from, to));
+            }
+        }
+    }
+    // Still frozen at the end of the file
+    if let Some(from) = start {
+        ranges.push((from, info.duration));
+    }
+
+    Ok(ranges
+        .into_iter()
+        .map(|(from, to)| SpeedSegment {
+            start: from + IDLE_MARGIN_SECONDS,
+            end: to - IDLE_MARGIN_SECONDS,
+            speed: idle.speed,
+        })
+        .filter(|s| s.end - s.start >= MIN_PIECE_SECONDS)
+        .collect())
+}
+
+/// `atempo` steps for `speed`; each instance is limited to 0.5..2
+fn atempo(speed: f64) -> String {
+    let mut steps = Vec::new();
+    let mut rest = speed;
+    while rest > 2.0 {
+        steps.push("atempo=2".to_string());
+        rest /= 2.0;
+    }
+    while rest < 0.5 {
+        steps.push("atempo=0.5".to_string());
+        rest /= 0.5;
+    }
+    steps.push(format!("atempo={rest:.6}"));
+    steps.join(",")
+}
+
+/// Render `input` into `output` (MP4) with `options` applied. Returns `markers`
+/// moved onto the new timeline.
+pub fn export(
+    input: &Path,
+    output: &Path,
+    options: &SpeedExportOptions,
+    markers: &[RecordingMarker],
+    job: &ExportJob,
+) -> Result<Vec<RecordingMarker>, CaptureError> {
+    options.validate()?;
+    let info = ffmpeg::probe(input)?;
+    if info.duration <= 0.0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no frames", input.display())));
+    }
+    let with_audio = options.keep_audio && info.has_audio;
+    let fps = if info.fps > 0.0 { info.fps } else { 30.0 };
+
+    let idle = match &options.idle {
+        Some(idle) => idle_ranges(input, &info, idle, job)?,
+        None => Vec::new(),
+    };
+    let detect_share = if options.idle.is_some() { 0.2 } else { 0.0 };
+    let pieces = plan(info.duration, options.speed, &[&idle, &options.segments], 1.0 / fps);
+    let total = output_time(&pieces, info.duration);
+    log::info!("Speed export: {} pieces, {:.1}s -> {:.1}s", pieces.len(), info.duration, total);
+
+    let codec = format::resolve(OutputFormat::Mp4, with_audio, &QualityPreset::High, fps.round() as u32, 2.0)?;
+    let scratch = ScratchDir::new("speed")?;
+    let mut parts = Vec::with_capacity(pieces.len());
+    let mut done = 0.0;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        let part = scratch.join(&format!("part-{i}.mp4"));
+        let TimeRange { start, end } = piece.range;
+        let mut args: Vec<String> = vec![
+            "-ss".into(),
+            format!("{start:.6}"),
+            "-t".into(),
+            format!("{:.6}", end - start),
+            "-i".into(),
+            input.to_string_lossy().into_owned(),
+            "-map".into(),
+            "0:v:0".into(),
+            "-vf".into(),
+            // Resample to the source rate so fast pieces don't carry every frame
+            format!("setpts=(PTS-STARTPTS)/{:.6},fps={fps}", piece.speed),
+        ];
+        if with_audio {
+            args.extend(["-map".into(), "0:a:0".into(), "-af".into(), format!("asetpts=PTS-STARTPTS,{}", atempo(piece.speed))]);
+        } else {
+            args.push("-an".into());
+        }
+        args.extend(part_args(&codec, &part));
+
+        let length = piece.range.duration() / piece.speed;
+        let weight = length / total;
+        ffmpeg::run(&args, length, job.cancel_flag(), |p| {
+            job.progress(detect_share + (0.95 - detect_share) * (done + p * weight))
+        })?;
+        done += weight;
+        parts.push(part);
+    }
+
+    let joined = join(&parts, &scratch, &[], output, total, job.cancel_flag(), |p| job.progress(0.95 + 0.05 * p));
+    if let Err(e) = joined {
+        let _ = std::fs::remove_file(output);
+        return Err(e);
+    }
+
+    Ok(markers
+        .iter()
+        .map(|m| RecordingMarker { time: output_time(&pieces, m.time), name: m.name.clone() })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f64 = 1.0 / 30.0;
+
+    fn segment(start: f64, end: f64, speed: f64) -> SpeedSegment {
+        SpeedSegment { start, end, speed }
+    }
+
+    fn spans(pieces: &[Piece]) -> Vec<(f64, f64, f64)> {
+        pieces.iter().map(|p| (p.range.start, p.range.end, p.speed)).collect()
+    }
+
+    #[test]
+    fn plan_layers_segments_over_idle_over_the_base_speed() {
+        let idle = [segment(1.0, 6.0, 4.0)];
+        let segments = [segment(2.0, 4.0, 2.0)];
+        let pieces = plan(10.0, 1.0, &[&idle, &segments], FRAME);
+        assert_eq!(
+            spans(&pieces),
+            [(0.0, 1.0, 1.0), (1.0, 2.0, 4.0), (2.0, 4.0, 2.0), (4.0, 6.0, 4.0), (6.0, 10.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn plan_folds_pieces_that_would_last_less_than_a_frame() {
+        // Half a second at 64x plays for 8 ms, less than a frame at 30 fps
+        let segments = [segment(5.0, 5.5, 64.0)];
+        let pieces = plan(10.0, 1.0, &[&segments], FRAME);
+        assert_eq!(spans(&pieces), [(0.0, 10.0, 1.0)]);
+
+        // At 4x the same span still gets several frames
+        let segments = [segment(5.0, 5.5, 4.0)];
+        let pieces = plan(10.0, 1.0, &[&segments], FRAME);
+        assert_eq!(spans(&pieces), [(0.0, 5.0, 1.0), (5.0, 5.5, 4.0), (5.5, 10.0, 1.0)]);
+
+        // A short first piece joins the one after it
+        let segments = [segment(0.0, 0.05, 2.0)];
+        let pieces = plan(10.0, 1.0, &[&segments], FRAME);
+        assert_eq!(spans(&pieces), [(0.0, 10.0, 1.0)]);
+        assert!(pieces.iter().all(|p| p.range.duration() / p.speed >= FRAME));
+    }
+
+    #[test]
+    fn output_time_sums_retimed_pieces_up_to_the_source_time() {
+        let pieces = plan(10.0, 1.0, &[&[segment(2.0, 6.0, 2.0)]], FRAME);
+        assert_eq!(output_time(&pieces, 0.0), 0.0);
+        assert_eq!(output_time(&pieces, 1.0), 1.0);
+        assert_eq!(output_time(&pieces, 4.0), 3.0);
+        assert_eq!(output_time(&pieces, 6.0), 4.0);
+        assert_eq!(output_time(&pieces, 10.0), 8.0);
+    }
+
+    #[test]
+    fn atempo_chains_steps_within_the_filter_limits() {
+        assert_eq!(atempo(1.5), "atempo=1.500000");
+        assert_eq!(atempo(8.0), "atempo=2,atempo=2,atempo=2.000000");
+        assert_eq!(atempo(0.25), "atempo=0.5,atempo=0.500000");
+        for speed in [MIN_SPEED, 0.3, 3.0, 10.0, MAX_SPEED] {
+            let steps: Vec<f64> = atempo(speed)
+                .split(',')
+                .map(|s| s.trim_start_matches("atempo=").parse().unwrap())
+                .collect();
+            assert!(steps.iter().all(|s| (0.5..=2.0).contains(s)), "{speed}: {steps:?}");
+            assert!((steps.iter().product::<f64>() - speed).abs() < 1e-4, "{speed}: {steps:?}");
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Auto-zoom export: re-renders a recording with the camera easing in on the
+//! activity in its cursor track.
+//!
+//! ffmpeg decodes the source to raw BGRA frames; each frame is cropped to the
+//! camera's viewport and scaled back to full size here, an optional synthetic
+//! cursor is drawn on top, and a second ffmpeg encodes the result with the source's
+//! audio and chapters copied across.
+use super::ExportJob;
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::capture::recording::encoder::format;
+use crate::capture::recording::track::CursorTrack;
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+const MAX_ZOOM: f64 = 5.0;
+/// How long the camera takes to zoom in before a segment and out after it
+const TRANSITION_SECONDS: f64 = 0.6;
+/// Time constant of the camera following the cursor while zoomed in
+const FOLLOW_SECONDS: f64 = 0.35;
+/// Time constant of cursor smoothing
+const CURSOR_SMOOTHING_SECONDS: f64 = 0.08;
+/// Derived segments start this long before a click and hold this long after it
+const CLICK_LEAD_SECONDS: f64 = 0.4;
+const CLICK_HOLD_SECONDS: f64 = 2.0;
+/// Derived segments closer together than this are merged instead of zooming out between them
+const MERGE_GAP_SECONDS: f64 = 1.0;
+/// Cursor height at 1080p and scale 1
+const CURSOR_HEIGHT: f64 = 22.0;
+
+/// A span the camera zooms in for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZoomSegment {
+    /// Seconds on the recording's timeline; the zoom is fully in from `start` to `end`
+    pub start: f64,
+    pub end: f64,
+    /// Magnification, 1 to 5
+    pub zoom: f64,
+    /// Fixed point to centre on, in video pixels; follows the cursor when unset
+    #[serde(default)]
+    pub center: Option<(f64, f64)>,
+}
+
+fn default_zoom() -> f64 {
+    2.0
+}
+
+fn default_cursor_scale() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoomExportOptions {
+    /// Segments to zoom on; derived from the clicks in the track when unset
+    #[serde(default)]
+    pub segments: Option<Vec<ZoomSegment>>,
+    /// Magnification of derived segments
+    #[serde(default = "default_zoom")]
+    pub zoom: f64,
+    /// Draw the cursor with its jitter smoothed out
+    #[serde(default)]
+    pub smooth_cursor: bool,
+    /// Draw the cursor at this multiple of its normal size
+    #[serde(default = "default_cursor_scale")]
+    pub cursor_scale: f64,
+}
+
+impl Default for ZoomExportOptions {
+    fn default() -> Self {
+        Self { segments: None, zoom: default_zoom(), smooth_cursor: false, cursor_scale: default_cursor_scale() }
+    }
+}
+
+impl ZoomExportOptions {
+    /// Whether the cursor is drawn by the export rather than taken from the video
+    fn draws_cursor(&self) -> bool {
+        self.smooth_cursor || self.cursor_scale != 1.0
+    }
+}
+
+fn validate_zoom(zoom: f64) -> Result<(), CaptureError> {
+    if !(1.0..=MAX_ZOOM).contains(&zoom) {
+        return Err(CaptureError::InvalidConfig(format!("Zoom must be between 1 and {MAX_ZOOM}, got {zoom}")));
+    }
+    Ok(())
+}
+
+/// Zoom segments around the clicks of `track`, for the user to start editing from
+pub fn suggest_segments(track: &CursorTrack, zoom: f64) -> Result<Vec<ZoomSegment>, CaptureError> {
+    validate_zoom(zoom)?;
+    let mut clicks: Vec<f64> = track.clicks.iter().map(|c| c.time).collect();
+    clicks.sort_by(f64::total_cmp);
+
+    let mut segments: Vec<ZoomSegment> = Vec::new();
+    for time in clicks {
+        let start = (time - CLICK_LEAD_SECONDS).max(0.0);
+        let end = time + CLICK_HOLD_SECONDS;
+        match segments.last_mut() {
+            Some(last) if start - last.end < MERGE_GAP_SECONDS => last.end = last.end.max(end),
+            _ => segments.push(ZoomSegment { start, end, zoom, center: None }),
+        }
+    }
+    Ok(segments)
+}
+
+/// Eased 0..1 weight of `segment` at `time`, ramping over the transitions either side
+fn weight(segment: &ZoomSegment, time: f64) -> f64 {
+    let ramp = if time < segment.start {
+        1.0 - (segment.start - time) / TRANSITION_SECONDS
+    } else if time > segment.end {
+        1.0 - (time - segment.end) / TRANSITION_SECONDS
+    } else {
+        1.0
+    };
+    let t = ramp.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Follows the segments over time, keeping its own state so motion is continuous
+struct Camera {
+    width: f64,
+    height: f64,
+    focus: Option<(f64, f64)>,
+}
+
+/// Visible part of the frame
+#[derive(Debug, Clone, Copy)]
+struct Viewport {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Camera {
+    fn viewport(&mut self, segments: &[ZoomSegment], cursor: Option<(f64, f64)>, time: f64, dt: f64) -> Viewport {
+        let strongest = segments
+            .iter()
+            .map(|s| (s, weight(s, time)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let (zoom, target) = match strongest {
+            Some((segment, w)) if w > 0.0 => {
+                (1.0 + (segment.zoom - 1.0) * w, segment.center.or(cursor))
+            }
+            _ => (1.0, cursor),
+        };
+        let target = target.unwrap_or((self.width / 2.0, self.height / 2.0));
+
+        // Zoomed out, the focus is invisible, so jump to the target and ease in from there
+        let focus = match self.focus {
+            Some((x, y)) if zoom > 1.01 => {
+                let k = 1.0 - (-dt / FOLLOW_SECONDS).exp();
+                (x + (target.0 - x) * k, y + (target.1 - y) * k)
+            }
+            _ => target,
+        };
+        self.focus = Some(focus);
+
+        let width = self.width / zoom;
+        let height = self.height / zoom;
+        Viewport {
+            x: (focus.0 - width / 2.0).clamp(0.0, self.width - width),
+            y: (focus.1 - height / 2.0).clamp(0.0, self.height - height),
+            width,
+            height,
+        }
+    }
+}
+
+/// Fill `dst` with the `view` of `src` scaled to the full frame, bilinearly
+fn render_view(src: &[u8], dst: &mut [u8], width: usize, height: usize, view: Viewport) {
+    if view.width >= width as f64 - 0.5 {
+        dst.copy_from_slice(src);
+        return;
+    }
+    let sx = view.width / width as f64;
+    let sy = view.height / height as f64;
+    let (max_x, max_y) = (width - 1, height - 1);
+    for oy in 0..height {
+        let fy = (view.y + (oy as f64 + 0.5) * sy - 0.5).clamp(0.0, max_y as f64);
+        let y0 = fy as usize;
+        let y1 = (y0 + 1).min(max_y);
+        let wy = fy - y0 as f64;
+        for ox in 0..width {
+            let fx = (view.x + (ox as f64 + 0.5) * sx - 0.5).clamp(0.0, max_x as f64);
+            let x0 = fx as usize;
+            let x1 = (x0 + 1).min(max_x);
+            let wx = fx - x0 as f64;
+            let out = (oy * width + ox) * 4;
+            for c in 0..4 {
+                let p = |x: usize, y: usize| src[(y * width + x) * 4 + c] as f64;
+                let top = p(x0, y0) + (p(x1, y0) - p(x0, y0)) * wx;
+                let bottom = p(x0, y1) + (p(x1, y1) - p(x0, y1)) * wx;
+                dst[out + c] = (top + (bottom - top) * wy).round() as u8;
+            }
+        }
+    }
+}
+
+/// Arrow pointer outline, tip at the origin, `CURSOR_UNITS` tall
+const CURSOR_SHAPE: [(f64, f64); 7] =
+    [(0.0, 0.0), (0.0, 16.0), (4.0, 12.5), (6.8, 19.0), (9.3, 18.0), (6.6, 11.8), (11.5, 11.8)];
+const CURSOR_UNITS: f64 = 19.0;
+const CURSOR_BORDER_UNITS: f64 = 1.3;
+
+fn inside_cursor(x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for (i, &(xi, yi)) in CURSOR_SHAPE.iter().enumerate() {
+        let (xj, yj) = CURSOR_SHAPE[(i + CURSOR_SHAPE.len() - 1) % CURSOR_SHAPE.len()];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn distance_to_cursor_edge(x: f64, y: f64) -> f64 {
+    let mut nearest = f64::MAX;
+    for (i, &(ax, ay)) in CURSOR_SHAPE.iter().enumerate() {
+        let (bx, by) = CURSOR_SHAPE[(i + 1) % CURSOR_SHAPE.len()];
+        let (dx, dy) = (bx - ax, by - ay);
+        let t = (((x - ax) * dx + (y - ay) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+        nearest = nearest.min((x - ax - t * dx).hypot(y - ay - t * dy));
+    }
+    nearest
+}
+
+/// Draw a white arrow with a dark border, its tip at (`x`, `y`) and `size` pixels tall
+fn draw_cursor(frame: &mut [u8], width: usize, height: usize, x: f64, y: f64, size: f64) {
+    let scale = size / CURSOR_UNITS;
+    let x0 = x.floor().max(0.0) as usize;
+    let y0 = y.floor().max(0.0) as usize;
+    let x1 = ((x + 12.0 * scale).ceil() as usize).min(width);
+    let y1 = ((y + size).ceil() as usize).min(height);
+    // 4x4 samples per pixel for smooth edges
+    const SAMPLES: usize = 4;
+    for py in y0..y1 {
+        for px in x0..x1 {
+            let (mut covered, mut white) = (0usize, 0usize);
+            for s in 0..SAMPLES * SAMPLES {
+                let ux = (px as f64 + ((s % SAMPLES) as f64 + 0.5) / SAMPLES as f64 - x) / scale;
+                let uy = (py as f64 + ((s / SAMPLES) as f64 + 0.5) / SAMPLES as f64 - y) / scale;
+                if inside_cursor(ux, uy) {
+                    covered += 1;
+                    if distance_to_cursor_edge(ux, uy) > CURSOR_BORDER_UNITS {
+                        white += 1;
+                    }
+                }
+            }
+            if covered == 0 {
+                continue;
+            }
+            let total = (SAMPLES * SAMPLES) as f64;
+            let alpha = covered as f64 / total;
+            let colour = 255.0 * white as f64 / covered as f64;
+            let i = (py * width + px) * 4;
+            for c in 0..3 {
+                frame[i + c] = (frame[i + c] as f64 * (1.0 - alpha) + colour * alpha).round() as u8;
+            }
+        }
+    }
+}
+
+fn spawn(args: &[String], stdin: Stdio, stdout: Stdio) -> Result<Child, CaptureError> {
+    Command::new(ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner", "-loglevel", "error", "-nostats", "-y"])
+        .args(args)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CaptureError::ExportFailed(format!("Failed to start ffmpeg: {e}")))
+}
+
+fn stderr_of(child: &mut Child) -> String {
+    let mut log = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut log);
+    }
+    log.trim().to_string()
+}
+
+/// Render `input` into `output` (MP4) with the camera following `track`
+pub fn export(
+    input: &Path,
+    output: &Path,
+    track: &CursorTrack,
+    options: &ZoomExportOptions,
+    job: &ExportJob,
+) -> Result<(), CaptureError> {
+    let segments = match &options.segments {
+        Some(segments) => segments.clone(),
+        None => suggest_segments(track, options.zoom)?,
+    };
+    for segment in &segments {
+        validate_zoom(segment.zoom)?;
+        if !segment.start.is_finite() || !segment.end.is_finite() || segment.end < segment.start {
+            return Err(CaptureError::InvalidConfig("Zoom segments must have start <= end".into()));
+        }
+    }
+    if options.draws_cursor() {
+        if track.cursor_in_video {
+            return Err(CaptureError::InvalidConfig(
+                "Cursor smoothing and enlargement need a recording made without the cursor".into(),
+            ));
+        }
+        if !(0.5..=4.0).contains(&options.cursor_scale) {
+            return Err(CaptureError::InvalidConfig("Cursor scale must be between 0.5 and 4".into()));
+        }
+    }
+
+    let info = ffmpeg::probe(input)?;
+    if info.width == 0 || info.height == 0 || info.duration <= 0.0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no frames", input.display())));
+    }
+    let (width, height) = (info.width as usize, info.height as usize);
+    let fps = if info.fps > 0.0 { info.fps } else { 30.0 };
+    let total_frames = (info.duration * fps).ceil().max(1.0);
+    // The track is in the frame size captured; the file may have been scaled down since
+    let track_scale = if track.width > 0 { info.width as f64 / track.width as f64 } else { 1.0 };
+    let cursor_at = |time: f64| track.position_at(time).map(|(x, y)| (x * track_scale, y * track_scale));
+    let segments: Vec<ZoomSegment> = segments
+        .into_iter()
+        .map(|s| ZoomSegment { center: s.center.map(|(x, y)| (x * track_scale, y * track_scale)), ..s })
+        .collect();
+
+    let codec = format::resolve(OutputFormat::Mp4, false, &QualityPreset::High, fps.round() as u32, 2.0)?;
+    let input_arg = input.to_string_lossy().into_owned();
+    let mut decoder = spawn(
+        &["-i", &input_arg, "-map", "0:v:0", "-f", "rawvideo", "-pix_fmt", "bgra", "pipe:1"].map(String::from),
+        Stdio::null(),
+        Stdio::piped(),
+    )?;
+    let mut encode_args: Vec<String> = [
+        "-f".into(),
+        "rawvideo".into(),
+        "-pix_fmt".into(),
+        "bgra".into(),
+        "-s".into(),
+        format!("{width}x{height}"),
+        "-r".into(),
+        format!("{fps}"),
+        "-i".into(),
+        "pipe:0".into(),
+        "-i".into(),
+        input_arg,
+        "-map".into(),
+        "0:v".into(),
+        "-map".into(),
+        "1:a?".into(),
+        "-map_chapters".into(),
+        "1".into(),
+        "-c:a".into(),
+        "copy".into(),
+    ]
+    .into();
+    encode_args.extend(codec.video);
+    encode_args.extend(["-movflags", "+faststart", "-f", "mp4"].map(String::from));
+    encode_args.push(output.to_string_lossy().into_owned());
+    let mut encoder = match spawn(&encode_args, Stdio::piped(), Stdio::null()) {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = decoder.kill();
+            let _ = decoder.wait();
+            return Err(e);
+        }
+    };
+
+    let result = (|| -> Result<(), CaptureError> {
+        let mut frames_in = decoder.stdout.take().ok_or_else(|| CaptureError::ExportFailed("No decoder output".into()))?;
+        let mut frames_out = encoder.stdin.take().ok_or_else(|| CaptureError::ExportFailed("No encoder input".into()))?;
+        let mut source = vec![0u8; width * height * 4];
+        let mut frame = vec![0u8; width * height * 4];
+        let mut camera = Camera { width: width as f64, height: height as f64, focus: None };
+        let mut cursor: Option<(f64, f64)> = None;
+        let dt = 1.0 / fps;
+
+        for index in 0u64.. {
+            if job.is_cancelled() {
+                return Err(CaptureError::ExportCancelled);
+            }
+            match frames_in.read_exact(&mut source) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let time = index as f64 * dt;
+
+            let raw = cursor_at(time);
+            cursor = match (cursor, raw) {
+                (Some((x, y)), Some((tx, ty))) if options.smooth_cursor => {
+                    let k = 1.0 - (-dt / CURSOR_SMOOTHING_SECONDS).exp();
+                    Some((x + (tx - x) * k, y + (ty - y) * k))
+                }
+                _ => raw,
+            };
+
+            let view = camera.viewport(&segments, cursor, time, dt);
+            render_view(&source, &mut frame, width, height, view);
+            if options.draws_cursor() {
+                if let Some((x, y)) = cursor {
+                    let zoom = width as f64 / view.width;
+                    let size = CURSOR_HEIGHT * height as f64 / 1080.0 * options.cursor_scale * zoom;
+                    draw_cursor(&mut frame, width, height, (x - view.x) * zoom, (y - view.y) * zoom, size);
+                }
+            }
+
+            frames_out
+                .write_all(&frame)
+                .map_err(|e| CaptureError::ExportFailed(format!("Encoder stopped accepting frames: {e}")))?;
+            if index % 10 == 0 {
+                job.progress(0.99 * index as f64 / total_frames);
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        let _ = decoder.kill();
+        let _ = encoder.kill();
+        let _ = decoder.wait();
+        let _ = encoder.wait();
+        let _ = std::fs::remove_file(output);
+        return Err(match e {
+            CaptureError::ExportFailed(message) => {
+                CaptureError::ExportFailed(format!("{message}: {}", stderr_of(&mut encoder)))
+            }
+            other => other,
+        });
+    }
+
+    let decoded = decoder.wait()?;
+    let encoded = encoder.wait()?;
+    if !decoded.success() || !encoded.success() {
+        let log = if encoded.success() { stderr_of(&mut decoder) } else { stderr_of(&mut encoder) };
+        let _ = std::fs::remove_file(output);
+        return Err(CaptureError::ExportFailed(format!("ffmpeg failed: {log}")));
+    }
+    job.progress(1.0);
+    Ok(())
+}
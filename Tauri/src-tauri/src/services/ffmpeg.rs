@@ -0,0 +1,195 @@
+//! Locating and driving the `ffmpeg` binaries used for encoding and export
+use crate::error::CaptureError;
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Locations GUI apps commonly miss because they don't inherit the login shell's PATH
+const FALLBACK_DIRS: &[&str] = &["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"];
+
+/// Resolve `ffmpeg`: `$SCREENCAPTURE_FFMPEG`, then `$PATH`, then well-known install dirs
+pub fn ffmpeg_path() -> Result<PathBuf, CaptureError> {
+    find_binary("ffmpeg", "SCREENCAPTURE_FFMPEG").ok_or_else(|| {
+        CaptureError::RecordingFailed(
+            "ffmpeg not found. Install it (e.g. `brew install ffmpeg` or your distribution's ffmpeg package) \
+             or set SCREENCAPTURE_FFMPEG to its path"
+                .into(),
+        )
+    })
+}
+
+pub(crate) fn find_binary(name: &str, override_var: &str) -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(override_var).map(PathBuf::from) {
+        if path.is_file() {
+            return Some(path);
+        }
+        log::warn!("{} points to {}, which does not exist", override_var, path.display());
+    }
+
+    let path_dirs = std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    path_dirs
+        .into_iter()
+        .chain(FALLBACK_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Resolve `ffprobe`, preferring the one installed next to `ffmpeg`
+pub fn ffprobe_path() -> Result<PathBuf, CaptureError> {
+    find_binary("ffprobe", "SCREENCAPTURE_FFPROBE")
+        .or_else(|| {
+            let sibling = ffmpeg_path().ok()?.with_file_name("ffprobe");
+            sibling.is_file().then_some(sibling)
+        })
+        .ok_or_else(|| CaptureError::ExportFailed("ffprobe not found (it ships with ffmpeg)".into()))
+}
+
+/// Whether the installed ffmpeg was built with encoder `name` (e.g. `libx265`).
+/// The list is read once per run.
+pub fn has_encoder(name: &str) -> bool {
+    static ENCODERS: OnceLock<HashSet<String>> = OnceLock::new();
+    ENCODERS
+        .get_or_init(|| {
+            let Ok(ffmpeg) = ffmpeg_path() else { return HashSet::new() };
+            let Ok(output) = Command::new(ffmpeg).args(["-hide_banner", "-encoders"]).output() else {
+                return HashSet::new();
+            };
+            // Rows look like " V....D libx264   libx264 H.264 / AVC ...", after a "------" separator
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .skip_while(|line| !line.trim_start().starts_with("---"))
+                .skip(1)
+                .filter_map(|line| line.split_whitespace().nth(1).map(String::from))
+                .collect()
+        })
+        .contains(name)
+}
+
+/// Stream and container facts about a media file
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    pub duration: f64,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub video_codec: String,
+    pub has_audio: bool,
+}
+
+pub fn probe(path: &Path) -> Result<MediaInfo, CaptureError> {
+    let output = Command::new(ffprobe_path()?)
+        .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(CaptureError::ExportFailed(format!(
+            "Could not read {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let streams = json["streams"].as_array().cloned().unwrap_or_default();
+    let video = streams
+        .iter()
+        .find(|s| s["codec_type"] == "video")
+        .ok_or_else(|| CaptureError::ExportFailed(format!("{} has no video stream", path.display())))?;
+
+    let parse_f64 = |v: &serde_json::Value| v.as_str().and_then(|s| s.parse::<f64>().ok());
+    // Frame rates are rationals like "60/1" or "30000/1001"
+    let fps = video["avg_frame_rate"]
+        .as_str()
+        .and_then(|r| r.split_once('/'))
+        .and_then(|(n, d)| Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok().filter(|d| *d > 0.0)?))
+        .unwrap_or(0.0);
+
+    Ok(MediaInfo {
+        duration: parse_f64(&json["format"]["duration"])
+            .or_else(|| parse_f64(&video["duration"]))
+            .unwrap_or(0.0),
+        width: video["width"].as_u64().unwrap_or(0) as u32,
+        height: video["height"].as_u64().unwrap_or(0) as u32,
+        fps,
+        video_codec: video["codec_name"].as_str().unwrap_or_default().to_string(),
+        has_audio: streams.iter().any(|s| s["codec_type"] == "audio"),
+    })
+}
+
+/// Run ffmpeg to completion with `-progress` reporting.
+///
+/// `on_progress` receives the fraction of `duration` processed so far. Setting
+/// `cancel` kills the process and returns `ExportCancelled`.
+pub fn run<I, S>(
+    args: I,
+    duration: f64,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(f64),
+) -> Result<(), CaptureError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = Command::new(ffmpeg_path()?)
+        .args(["-hide_banner", "-loglevel", "error", "-nostats", "-progress", "pipe:1", "-y"])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CaptureError::ExportFailed(format!("Failed to start ffmpeg: {e}")))?;
+
+    let stderr = child.stderr.take().map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut log = String::new();
+            let _ = pipe.read_to_string(&mut log);
+            log
+        })
+    });
+
+    // Progress lines arrive as key=value blocks; out_time_us is the output position
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if let Some(us) = line.strip_prefix("out_time_us=").and_then(|v| v.trim().parse::<i64>().ok()) {
+                    if tx.send(us.max(0) as f64 / 1_000_000.0).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    let status = loop {
+        if cancel.load(Ordering::Acquire) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CaptureError::ExportCancelled);
+        }
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(seconds) if duration > 0.0 => on_progress((seconds / duration).clamp(0.0, 1.0)),
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break child.wait()?,
+        }
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+    };
+
+    let log = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+    if !status.success() {
+        return Err(CaptureError::ExportFailed(format!("ffmpeg exited with {status}: {}", log.trim())));
+    }
+    on_progress(1.0);
+    Ok(())
+}
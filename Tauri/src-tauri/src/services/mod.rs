@@ -1,3 +1,7 @@
 pub mod storage;
 pub mod ocr;
 pub mod permissions;
+pub mod ffmpeg;
+pub mod export;
+pub mod editor;
+pub mod overlays;
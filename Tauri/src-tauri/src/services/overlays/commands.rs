@@ -0,0 +1,52 @@
+use super::{render, TimedOverlay};
+use crate::error::CaptureError;
+use crate::services::export::{ensure_video, run_recording_export, ExportTarget};
+use crate::services::storage::manager::{CaptureItem, CaptureType};
+use crate::state::app_state::AppState;
+
+fn recording(state: &AppState, id: &str) -> Result<(CaptureItem, std::path::PathBuf), CaptureError> {
+    let (item, path) = crate::services::export::source_path(state, id)?;
+    if !matches!(item.capture_type, CaptureType::Recording) {
+        return Err(CaptureError::InvalidConfig("Only recordings can have timed overlays".into()));
+    }
+    ensure_video(&item)?;
+    Ok((item, path))
+}
+
+/// The timed overlays of a recording, in drawing order
+#[tauri::command]
+pub fn get_recording_overlays(id: String) -> Result<Vec<TimedOverlay>, CaptureError> {
+    super::load(&id)
+}
+
+/// Replace the timed overlays of a recording. The recording itself is untouched
+/// until it is exported with `export_recording_overlays`.
+#[tauri::command]
+pub fn set_recording_overlays(
+    id: String,
+    overlays: Vec<TimedOverlay>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), CaptureError> {
+    recording(&state, &id)?;
+    super::validate(&overlays)?;
+    super::save(&id, &overlays)
+}
+
+/// Burn a recording's overlays into a copy and add it to history.
+/// Progress is reported as an export of kind `overlays`, cancellable by `job_id`.
+#[tauri::command]
+pub async fn export_recording_overlays(
+    id: String,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<CaptureItem, CaptureError> {
+    let overlays = super::load(&id)?;
+    if overlays.is_empty() {
+        return Err(CaptureError::InvalidConfig("This recording has no overlays".into()));
+    }
+    // Timing is unchanged, so the markers (and chapters, copied by ffmpeg) still fit
+    run_recording_export(&app, &id, job_id, ExportTarget::recording("overlays"), move |_, input, output, job| {
+        render::render(input, output, &overlays, job).map(|()| None)
+    })
+    .await
+}
@@ -0,0 +1,181 @@
+//! Timed text and shape overlays for recordings.
+//!
+//! Overlays are kept per item as a JSON sidecar next to the history, on the item's
+//! own timeline and in video pixels, so they can be edited any number of times
+//! before `render` burns them into an exported copy.
+pub mod commands;
+pub mod render;
+
+use crate::error::CaptureError;
+use crate::services::storage::manager::StorageManager;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A point in video pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OverlayShape {
+    /// Text callout; `x`/`y` is the top-left corner, lines split on `\n`
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        font_size: f64,
+        color: [u8; 4],
+        /// Rounded box behind the text
+        #[serde(default)]
+        background: Option<[u8; 4]>,
+    },
+    Arrow {
+        from: Point,
+        to: Point,
+        color: [u8; 4],
+        width: f64,
+    },
+    Rectangle {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: [u8; 4],
+        stroke_width: f64,
+        #[serde(default)]
+        fill: Option<[u8; 4]>,
+    },
+    /// Blurs whatever is underneath, e.g. to hide personal details
+    Blur {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        radius: f64,
+    },
+}
+
+/// A shape shown from `start` to `end` seconds; later overlays draw on top
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedOverlay {
+    pub start: f64,
+    pub end: f64,
+    #[serde(flatten)]
+    pub shape: OverlayShape,
+}
+
+impl TimedOverlay {
+    fn validate(&self) -> Result<(), CaptureError> {
+        if !self.start.is_finite() || !self.end.is_finite() || self.start < 0.0 || self.end <= self.start {
+            return Err(CaptureError::InvalidConfig(format!(
+                "Overlay times {:.2}s..{:.2}s are not a valid range",
+                self.start, self.end
+            )));
+        }
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        let valid = match &self.shape {
+            OverlayShape::Text { text, font_size, .. } => !text.trim().is_empty() && positive(*font_size),
+            OverlayShape::Arrow { from, to, width, .. } => positive(*width) && (from.x, from.y) != (to.x, to.y),
+            OverlayShape::Rectangle { width, height, stroke_width, .. } => {
+                positive(*width) && positive(*height) && stroke_width.is_finite() && *stroke_width >= 0.0
+            }
+            OverlayShape::Blur { width, height, radius, .. } => positive(*width) && positive(*height) && positive(*radius),
+        };
+        if !valid {
+            return Err(CaptureError::InvalidConfig(format!("Overlay at {:.2}s has an empty shape", self.start)));
+        }
+        Ok(())
+    }
+}
+
+pub fn validate(overlays: &[TimedOverlay]) -> Result<(), CaptureError> {
+    overlays.iter().try_for_each(TimedOverlay::validate)
+}
+
+fn path(id: &str) -> PathBuf {
+    StorageManager::overlays_dir().join(format!("{id}.json"))
+}
+
+/// The overlays of item `id`; empty when it has none
+pub fn load(id: &str) -> Result<Vec<TimedOverlay>, CaptureError> {
+    match std::fs::read(path(id)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the overlays of item `id`
+pub fn save(id: &str, overlays: &[TimedOverlay]) -> Result<(), CaptureError> {
+    if overlays.is_empty() {
+        remove(id);
+        return Ok(());
+    }
+    std::fs::create_dir_all(StorageManager::overlays_dir())?;
+    std::fs::write(path(id), serde_json::to_vec_pretty(overlays)?)?;
+    Ok(())
+}
+
+/// Drop the overlays of item `id`
+pub fn remove(id: &str) {
+    let _ = std::fs::remove_file(path(id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(start: f64, end: f64, text: &str) -> TimedOverlay {
+        let text = text.into();
+        let shape = OverlayShape::Text { x: 10.0, y: 10.0, text, font_size: 32.0, color: [255; 4], background: None };
+        TimedOverlay { start, end, shape }
+    }
+
+    fn rectangle(width: f64, height: f64, stroke_width: f64) -> TimedOverlay {
+        let color = [255; 4];
+        let shape = OverlayShape::Rectangle { x: 0.0, y: 0.0, width, height, color, stroke_width, fill: None };
+        TimedOverlay { start: 0.0, end: 1.0, shape }
+    }
+
+    #[test]
+    fn accepts_well_formed_overlays() {
+        let (from, to) = (Point { x: 0.0, y: 0.0 }, Point { x: 50.0, y: 20.0 });
+        let arrow = OverlayShape::Arrow { from, to, color: [255; 4], width: 4.0 };
+        let blur = OverlayShape::Blur { x: 0.0, y: 0.0, width: 100.0, height: 40.0, radius: 8.0 };
+        let overlays = [
+            text(0.0, 2.5, "Click here"),
+            TimedOverlay { start: 1.0, end: 2.0, shape: arrow },
+            rectangle(200.0, 100.0, 0.0),
+            TimedOverlay { start: 3.0, end: 4.0, shape: blur },
+        ];
+        assert!(validate(&overlays).is_ok());
+        assert!(validate(&[]).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_time_ranges() {
+        for (start, end) in [(-1.0, 1.0), (2.0, 2.0), (3.0, 1.0), (0.0, f64::NAN), (0.0, f64::INFINITY)] {
+            assert!(validate(&[text(start, end, "Hi")]).is_err(), "{start}..{end}");
+        }
+    }
+
+    #[test]
+    fn rejects_empty_shapes() {
+        assert!(validate(&[text(0.0, 1.0, "  \n")]).is_err());
+        assert!(validate(&[rectangle(0.0, 10.0, 2.0)]).is_err());
+        assert!(validate(&[rectangle(10.0, 10.0, -1.0)]).is_err());
+        let point = Point { x: 5.0, y: 5.0 };
+        let arrow = OverlayShape::Arrow { from: point, to: point, color: [255; 4], width: 4.0 };
+        assert!(validate(&[TimedOverlay { start: 0.0, end: 1.0, shape: arrow }]).is_err());
+        let blur = OverlayShape::Blur { x: 0.0, y: 0.0, width: 10.0, height: 10.0, radius: 0.0 };
+        assert!(validate(&[TimedOverlay { start: 0.0, end: 1.0, shape: blur }]).is_err());
+    }
+
+    #[test]
+    fn one_bad_overlay_rejects_the_list() {
+        assert!(validate(&[text(0.0, 1.0, "Ok"), text(1.0, 0.5, "Backwards")]).is_err());
+    }
+}
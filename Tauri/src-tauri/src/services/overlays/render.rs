@@ -0,0 +1,269 @@
+//! Burns timed overlays into a copy of a recording.
+//!
+//! Text, arrows and rectangles are drawn here into transparent PNGs cropped to
+//! their bounds; blur regions are done by ffmpeg. Every overlay becomes one step
+//! of a filter chain enabled only between its start and end.
+use super::{OverlayShape, Point, TimedOverlay};
+use crate::capture::config::{OutputFormat, QualityPreset};
+use crate::capture::recording::encoder::format;
+use crate::capture::recording::overlay::load_font;
+use crate::error::CaptureError;
+use crate::services::export::{ExportJob, ScratchDir};
+use crate::services::ffmpeg;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use image::RgbaImage;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// A transparent frame-sized canvas that remembers what was painted
+struct Canvas {
+    image: RgbaImage,
+    /// Painted bounds, inclusive
+    bounds: Option<(u32, u32, u32, u32)>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self { image: RgbaImage::new(width, height), bounds: None }
+    }
+
+    /// Composite `rgba` over pixel (`x`, `y`) at `coverage`
+    fn paint(&mut self, x: i64, y: i64, rgba: [u8; 4], coverage: f64) {
+        if x < 0 || y < 0 || x >= self.image.width() as i64 || y >= self.image.height() as i64 {
+            return;
+        }
+        let src_a = rgba[3] as f64 / 255.0 * coverage.clamp(0.0, 1.0);
+        if src_a <= 0.0 {
+            return;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let dst = self.image.get_pixel_mut(x, y);
+        let dst_a = dst[3] as f64 / 255.0;
+        let out_a = src_a + dst_a * (1.0 - src_a);
+        for c in 0..3 {
+            let mixed = (rgba[c] as f64 * src_a + dst[c] as f64 * dst_a * (1.0 - src_a)) / out_a;
+            dst[c] = mixed.round() as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+        self.bounds = Some(match self.bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+
+    /// Paint every pixel in the box from `coverage_at` its centre
+    fn fill(&mut self, min: Point, max: Point, rgba: [u8; 4], coverage_at: impl Fn(f64, f64) -> f64) {
+        for py in min.y.floor() as i64..=max.y.ceil() as i64 {
+            for px in min.x.floor() as i64..=max.x.ceil() as i64 {
+                let coverage = coverage_at(px as f64 + 0.5, py as f64 + 0.5);
+                if coverage > 0.0 {
+                    self.paint(px, py, rgba, coverage);
+                }
+            }
+        }
+    }
+
+    /// The painted part and where it goes, or `None` if nothing was painted
+    fn cropped(self) -> Option<(RgbaImage, u32, u32)> {
+        let (x0, y0, x1, y1) = self.bounds?;
+        let image = image::imageops::crop_imm(&self.image, x0, y0, x1 - x0 + 1, y1 - y0 + 1).to_image();
+        Some((image, x0, y0))
+    }
+}
+
+fn distance_to_segment(px: f64, py: f64, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let t = (((px - a.x) * dx + (py - a.y) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    (px - a.x - t * dx).hypot(py - a.y - t * dy)
+}
+
+fn inside_triangle(px: f64, py: f64, [a, b, c]: [Point; 3]) -> bool {
+    let side = |p: Point, q: Point| (q.x - p.x) * (py - p.y) - (q.y - p.y) * (px - p.x);
+    let (d1, d2, d3) = (side(a, b), side(b, c), side(c, a));
+    !((d1 < 0.0 || d2 < 0.0 || d3 < 0.0) && (d1 > 0.0 || d2 > 0.0 || d3 > 0.0))
+}
+
+fn draw_arrow(canvas: &mut Canvas, from: Point, to: Point, color: [u8; 4], width: f64) {
+    let length = (to.x - from.x).hypot(to.y - from.y);
+    let (ux, uy) = ((to.x - from.x) / length, (to.y - from.y) / length);
+    let head_length = (width * 4.0).min(length * 0.6);
+    let head_half = head_length * 0.6;
+    let base = Point { x: to.x - ux * head_length, y: to.y - uy * head_length };
+    let head = [
+        to,
+        Point { x: base.x - uy * head_half, y: base.y + ux * head_half },
+        Point { x: base.x + uy * head_half, y: base.y - ux * head_half },
+    ];
+
+    let reach = head_half.max(width);
+    let min = Point { x: from.x.min(to.x) - reach, y: from.y.min(to.y) - reach };
+    let max = Point { x: from.x.max(to.x) + reach, y: from.y.max(to.y) + reach };
+    // 4x4 samples per pixel for the head's edges
+    canvas.fill(min, max, color, |x, y| {
+        let shaft = (width / 2.0 - distance_to_segment(x, y, from, base) + 0.5).clamp(0.0, 1.0);
+        let samples = (0..16)
+            .filter(|s| inside_triangle(x - 0.5 + (s % 4) as f64 * 0.25 + 0.125, y - 0.5 + (s / 4) as f64 * 0.25 + 0.125, head))
+            .count();
+        shaft.max(samples as f64 / 16.0)
+    });
+}
+
+fn draw_rectangle(canvas: &mut Canvas, min: Point, max: Point, color: [u8; 4], stroke_width: f64, fill: Option<[u8; 4]>) {
+    let (x, y, width, height) = (min.x, min.y, max.x - min.x, max.y - min.y);
+    // Coverage of the span a..b along one axis by the pixel centred at p
+    let span = |p: f64, a: f64, b: f64| ((p + 0.5).min(b) - (p - 0.5).max(a)).clamp(0.0, 1.0);
+    if let Some(fill) = fill {
+        canvas.fill(min, max, fill, |px, py| span(px, x, x + width) * span(py, y, y + height));
+    }
+    if stroke_width > 0.0 {
+        let half = stroke_width / 2.0;
+        let outer = |px: f64, py: f64| span(px, x - half, x + width + half) * span(py, y - half, y + height + half);
+        let inner = |px: f64, py: f64| span(px, x + half, x + width - half) * span(py, y + half, y + height - half);
+        let grow = Point { x: half + 1.0, y: half + 1.0 };
+        canvas.fill(
+            Point { x: min.x - grow.x, y: min.y - grow.y },
+            Point { x: max.x + grow.x, y: max.y + grow.y },
+            color,
+            |px, py| outer(px, py) - inner(px, py),
+        );
+    }
+}
+
+fn draw_text(
+    canvas: &mut Canvas,
+    font: &FontVec,
+    origin: Point,
+    text: &str,
+    font_size: f64,
+    color: [u8; 4],
+    background: Option<[u8; 4]>,
+) {
+    let Point { x, y } = origin;
+    let px = PxScale::from(font_size as f32);
+    let scaled = font.as_scaled(px);
+    let lines: Vec<&str> = text.lines().collect();
+    let line_height = scaled.height() + scaled.line_gap();
+    let widest = lines
+        .iter()
+        .map(|line| line.chars().map(|c| scaled.h_advance(scaled.glyph_id(c))).sum::<f32>())
+        .fold(0.0f32, f32::max);
+    let padding = if background.is_some() { 0.4 * font_size } else { 0.0 };
+
+    if let Some(background) = background {
+        let (w, h) = (widest as f64 + 2.0 * padding, line_height as f64 * lines.len() as f64 + padding);
+        let radius = (h * 0.2).min(font_size * 0.5);
+        canvas.fill(Point { x, y }, Point { x: x + w, y: y + h }, background, |fx, fy| {
+            // Distance outside the rounded corner, if in a corner region
+            let qx = (x + radius - fx).max(fx - (x + w - radius)).max(0.0);
+            let qy = (y + radius - fy).max(fy - (y + h - radius)).max(0.0);
+            let inside = fx >= x && fx <= x + w && fy >= y && fy <= y + h;
+            if inside { (radius - qx.hypot(qy) + 0.5).clamp(0.0, 1.0) } else { 0.0 }
+        });
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        let mut caret = (x + padding) as f32;
+        let baseline = (y + padding / 2.0) as f32 + scaled.ascent() + line_height * i as f32;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            let glyph = id.with_scale_and_position(px, ab_glyph::point(caret, baseline));
+            caret += scaled.h_advance(id);
+            let Some(outline) = font.outline_glyph(glyph) else { continue };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                canvas.paint(
+                    bounds.min.x as i64 + gx as i64,
+                    bounds.min.y as i64 + gy as i64,
+                    color,
+                    coverage as f64,
+                );
+            });
+        }
+    }
+}
+
+/// Render `shape` onto a transparent `width` x `height` canvas, cropped
+pub(crate) fn draw(
+    shape: &OverlayShape,
+    font: Option<&FontVec>,
+    width: u32,
+    height: u32,
+) -> Result<Option<(RgbaImage, u32, u32)>, CaptureError> {
+    let mut canvas = Canvas::new(width, height);
+    match shape {
+        OverlayShape::Text { x, y, text, font_size, color, background } => {
+            let font = font.ok_or_else(|| CaptureError::ExportFailed("No usable font found for text overlays".into()))?;
+            draw_text(&mut canvas, font, Point { x: *x, y: *y }, text, *font_size, *color, *background);
+        }
+        OverlayShape::Arrow { from, to, color, width } => draw_arrow(&mut canvas, *from, *to, *color, *width),
+        OverlayShape::Rectangle { x, y, width, height, color, stroke_width, fill } => {
+            let (min, max) = (Point { x: *x, y: *y }, Point { x: x + width, y: y + height });
+            draw_rectangle(&mut canvas, min, max, *color, *stroke_width, *fill)
+        }
+        OverlayShape::Blur { .. } => return Ok(None),
+    }
+    Ok(canvas.cropped())
+}
+
+/// Burn `overlays` into `input`, writing `output` (MP4 with the source audio)
+pub fn render(input: &Path, output: &Path, overlays: &[TimedOverlay], job: &ExportJob) -> Result<(), CaptureError> {
+    super::validate(overlays)?;
+    let info = ffmpeg::probe(input)?;
+    if info.width == 0 || info.height == 0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no picture", input.display())));
+    }
+    let fps = if info.fps > 0.0 { info.fps } else { 30.0 };
+    let font = overlays
+        .iter()
+        .any(|o| matches!(o.shape, OverlayShape::Text { .. }))
+        .then(load_font);
+
+    let scratch = ScratchDir::new("overlays")?;
+    let mut args: Vec<String> = vec!["-i".into(), input.to_string_lossy().into_owned()];
+    let mut graph = String::new();
+    let mut current = "0:v".to_string();
+    let mut inputs = 1;
+
+    for (i, overlay) in overlays.iter().enumerate() {
+        let enable = format!("enable='between(t,{:.3},{:.3})'", overlay.start, overlay.end);
+        let next = format!("v{i}");
+        if let OverlayShape::Blur { x, y, width, height, radius } = overlay.shape {
+            // Clamp to the frame; crop needs whole pixels inside it
+            let x0 = x.clamp(0.0, info.width as f64 - 1.0).floor() as u32;
+            let y0 = y.clamp(0.0, info.height as f64 - 1.0).floor() as u32;
+            let w = ((x + width).min(info.width as f64).ceil() as u32).saturating_sub(x0).max(1);
+            let h = ((y + height).min(info.height as f64).ceil() as u32).saturating_sub(y0).max(1);
+            let _ = write!(
+                graph,
+                "[{current}]split=2[base{i}][region{i}];\
+                 [region{i}]crop={w}:{h}:{x0}:{y0},gblur=sigma={radius:.2}[blur{i}];\
+                 [base{i}][blur{i}]overlay={x0}:{y0}:{enable}[{next}];"
+            );
+        } else {
+            let Some((image, x, y)) = draw(&overlay.shape, font.as_ref(), info.width, info.height)? else {
+                continue;
+            };
+            let path = scratch.join(&format!("overlay-{i}.png"));
+            image.save(&path)?;
+            args.extend(["-i".into(), path.to_string_lossy().into_owned()]);
+            // Still inputs repeat their one frame for as long as the overlay needs it
+            let _ = write!(graph, "[{current}][{inputs}:v]overlay={x}:{y}:{enable}[{next}];");
+            inputs += 1;
+        }
+        current = next;
+    }
+    let _ = write!(graph, "[{current}]format=yuv420p[out]");
+
+    let codec = format::resolve(OutputFormat::Mp4, false, &QualityPreset::High, fps.round() as u32, 2.0)?;
+    args.extend(["-filter_complex".into(), graph]);
+    args.extend(["-map", "[out]", "-map", "0:a?", "-c:a", "copy"].map(String::from));
+    args.extend(codec.video);
+    args.extend(["-movflags", "+faststart"].map(String::from));
+    args.push(output.to_string_lossy().into_owned());
+
+    let result = ffmpeg::run(&args, info.duration, job.cancel_flag(), |p| job.progress(p));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
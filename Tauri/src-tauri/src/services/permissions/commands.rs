@@ -15,7 +15,7 @@ extern "C" {
 }
 
 #[tauri::command]
-pub fn check_screen_recording_permission() -> PermissionStatus {
+pub async fn check_screen_recording_permission() -> PermissionStatus {
     #[cfg(target_os = "macos")]
     {
         unsafe {
@@ -26,7 +26,25 @@ pub fn check_screen_recording_permission() -> PermissionStatus {
             }
         }
     }
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    {
+        use crate::capture::backend::LinuxBackend;
+        use crate::capture::portal::Portal;
+
+        match LinuxBackend::detect() {
+            LinuxBackend::X11 => PermissionStatus::Authorized,
+            // The portal asks the user on every capture, so there is nothing to preflight
+            LinuxBackend::Portal => tokio::task::spawn_blocking(|| {
+                match Portal::session() {
+                    Ok(portal) if portal.screenshot_available() => PermissionStatus::NotDetermined,
+                    _ => PermissionStatus::Restricted,
+                }
+            })
+            .await
+            .unwrap_or(PermissionStatus::Restricted),
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
         PermissionStatus::Authorized
     }
@@ -1,4 +1,8 @@
+use crate::capture::recording::track;
+use crate::services::{editor, overlays};
 use crate::services::storage::manager::*;
+use crate::services::storage::preview;
+use crate::services::storage::recovery::{self, OrphanedRecording};
 use crate::state::app_state::AppState;
 use crate::error::CaptureError;
 
@@ -10,18 +14,31 @@ pub fn get_history(state: tauri::State<'_, AppState>) -> CaptureHistory {
 #[tauri::command]
 pub fn delete_capture(id: String, state: tauri::State<'_, AppState>) -> Result<bool, CaptureError> {
     let mut storage = state.storage.lock().unwrap();
-    let filename = storage.history.items.iter()
+    let item = storage.history.items.iter()
         .find(|i| i.id == id)
-        .map(|i| i.filename.clone());
+        .cloned();
 
-    if let Some(filename) = &filename {
-        let path = storage.screenshots_dir().join(filename);
-        let _ = std::fs::remove_file(&path);
+    if let Some(item) = &item {
+        // Edits made from this recording still need it as their source
+        if editor::is_source_in_use(&storage, &item.filename, &id) {
+            editor::preserve_original(&mut storage, &item.filename)?;
+        } else {
+            let _ = std::fs::remove_file(storage.screenshots_dir().join(&item.filename));
+        }
+
+        if let Some(edit) = &item.edit {
+            if edit.source.starts_with(editor::ORIGINALS_DIR) && !editor::is_source_in_use(&storage, &edit.source, &id) {
+                let _ = std::fs::remove_file(storage.screenshots_dir().join(&edit.source));
+            }
+        }
     }
 
     let removed = storage.history.remove(&id);
     if removed {
         storage.save_history()?;
+        preview::remove(&id);
+        track::remove(&id);
+        overlays::remove(&id);
     }
     Ok(removed)
 }
@@ -57,3 +74,65 @@ pub fn set_storage_location(location: StorageLocation, state: tauri::State<'_, A
     storage.save_settings()?;
     Ok(())
 }
+
+/// A running session writes its own `.partial` file, which must not be offered
+/// for recovery or deleted out from under the encoder
+fn recording_in_progress(state: &AppState) -> bool {
+    state.recording_coordinator.lock().unwrap().is_active()
+}
+
+fn ensure_no_recording(state: &AppState) -> Result<(), CaptureError> {
+    if recording_in_progress(state) {
+        return Err(CaptureError::RecordingFailed(
+            "Finish the current recording before recovering interrupted ones".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Interrupted recordings, or none while a session is writing its own partial file
+#[tauri::command]
+pub fn list_orphaned_recordings(state: tauri::State<'_, AppState>) -> Vec<OrphanedRecording> {
+    if recording_in_progress(&state) {
+        return vec![];
+    }
+    let dir = state.storage.lock().unwrap().screenshots_dir();
+    recovery::find_orphans(&dir)
+}
+
+#[tauri::command]
+pub async fn recover_orphaned_recording(
+    filename: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<CaptureItem, CaptureError> {
+    ensure_no_recording(&state)?;
+    let dir = state.storage.lock().unwrap().screenshots_dir();
+    let partial = recovery::orphan_path(&dir, &filename)?;
+
+    let repaired = tokio::task::spawn_blocking(move || recovery::repair(&partial))
+        .await
+        .map_err(|e| CaptureError::StorageError(e.to_string()))??;
+
+    let filename = repaired
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let item = CaptureItem::new_recording(filename);
+    {
+        let mut storage = state.storage.lock().unwrap();
+        storage.history.add(item.clone());
+        storage.save_history()?;
+    }
+    preview::generate_in_background(&app, item.id.clone());
+    Ok(item)
+}
+
+#[tauri::command]
+pub fn discard_orphaned_recording(filename: String, state: tauri::State<'_, AppState>) -> Result<(), CaptureError> {
+    ensure_no_recording(&state)?;
+    let dir = state.storage.lock().unwrap().screenshots_dir();
+    let partial = recovery::orphan_path(&dir, &filename)?;
+    std::fs::remove_file(partial)?;
+    Ok(())
+}
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::Utc;
+use crate::capture::recording::markers::RecordingMarker;
+use crate::capture::recording::stats::RecordingStats;
 use crate::error::CaptureError;
+use crate::services::editor::RecordingEdit;
+use crate::services::storage::preview::RecordingPreview;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -18,27 +22,45 @@ pub struct CaptureItem {
     pub filename: String,
     pub created_at: String,
     pub is_favorite: bool,
+    /// Trim points and source of items produced by the recording editor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit: Option<RecordingEdit>,
+    /// Poster frame and scrub sprite sheet, once generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<RecordingPreview>,
+    /// Chapter markers on this item's timeline
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<RecordingMarker>,
+    /// Health summary of the session that recorded this item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<RecordingStats>,
 }
 
 impl CaptureItem {
-    pub fn new_screenshot(filename: String) -> Self {
+    fn new(capture_type: CaptureType, filename: String) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            capture_type: CaptureType::Screenshot,
+            capture_type,
             filename,
             created_at: Utc::now().to_rfc3339(),
             is_favorite: false,
+            edit: None,
+            preview: None,
+            markers: Vec::new(),
+            stats: None,
         }
     }
 
+    pub fn new_screenshot(filename: String) -> Self {
+        Self::new(CaptureType::Screenshot, filename)
+    }
+
     pub fn new_recording(filename: String) -> Self {
-        Self {
-            id: Uuid::new_v4().to_string(),
-            capture_type: CaptureType::Recording,
-            filename,
-            created_at: Utc::now().to_rfc3339(),
-            is_favorite: false,
-        }
+        Self::new(CaptureType::Recording, filename)
+    }
+
+    pub fn new_gif(filename: String) -> Self {
+        Self::new(CaptureType::Gif, filename)
     }
 }
 
@@ -47,6 +69,12 @@ pub struct CaptureHistory {
     pub items: Vec<CaptureItem>,
 }
 
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CaptureHistory {
     pub fn new() -> Self {
         Self { items: vec![] }
@@ -93,6 +121,12 @@ pub struct StorageManager {
     pub location: StorageLocation,
 }
 
+impl Default for StorageManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StorageManager {
     pub fn new() -> Self {
         Self::load()
@@ -105,7 +139,7 @@ impl StorageManager {
         let history = std::fs::read_to_string(data_dir.join("history.json"))
             .ok()
             .and_then(|data| serde_json::from_str(&data).ok())
-            .unwrap_or_else(CaptureHistory::new);
+            .unwrap_or_default();
 
         let location = std::fs::read_to_string(data_dir.join("settings.json"))
             .ok()
@@ -159,6 +193,26 @@ impl StorageManager {
             .join("ScreenCapture")
     }
 
+    /// Cache of recording previews, kept next to the history
+    pub fn previews_dir() -> std::path::PathBuf {
+        Self::data_dir().join("Previews")
+    }
+
+    /// Cursor tracks of recordings, kept next to the history
+    pub fn tracks_dir() -> std::path::PathBuf {
+        Self::data_dir().join("Tracks")
+    }
+
+    /// Timed overlays of recordings, kept next to the history
+    pub fn overlays_dir() -> std::path::PathBuf {
+        Self::data_dir().join("Overlays")
+    }
+
+    /// Saved framing (aspect-ratio export) presets
+    pub fn framing_presets_path() -> std::path::PathBuf {
+        Self::data_dir().join("framing-presets.json")
+    }
+
     pub fn screenshots_dir(&self) -> std::path::PathBuf {
         match &self.location {
             StorageLocation::Default => {
@@ -178,7 +232,9 @@ impl StorageManager {
         let prefix = match capture_type {
             CaptureType::Screenshot => "Screenshot",
             CaptureType::Recording => "Recording",
-            CaptureType::Gif => "GIF",
+            // Animated WebP/APNG exports share the GIF type
+            CaptureType::Gif if extension == "gif" => "GIF",
+            CaptureType::Gif => "Animation",
         };
         format!("{} {}.{}", prefix, now.format("%Y-%m-%d at %H.%M.%S"), extension)
     }
@@ -1,2 +1,4 @@
 pub mod commands;
 pub mod manager;
+pub mod preview;
+pub mod recovery;
@@ -0,0 +1,153 @@
+//! Poster frames and hover-scrub sprite sheets for recordings.
+//!
+//! Previews live in a cache next to the history, one directory per item, and are
+//! generated in the background once a recording is in history. The item is updated
+//! and `CAPTURE_PREVIEW_READY` emitted when they are done.
+use crate::error::CaptureError;
+use crate::services::ffmpeg;
+use crate::services::storage::manager::{CaptureItem, StorageManager};
+use crate::state::app_state::AppState;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Thumbnails in a sprite sheet, and how many go in a row
+const SPRITE_FRAMES: u32 = 20;
+const SPRITE_COLUMNS: u32 = 5;
+const TILE_WIDTH: u32 = 240;
+const POSTER_MAX_WIDTH: u32 = 1280;
+
+/// Cached preview images of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingPreview {
+    /// Absolute path of a representative frame (JPEG)
+    pub poster: String,
+    /// Absolute path of `frame_count` evenly spaced thumbnails tiled row by row
+    pub sprite: String,
+    pub frame_count: u32,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Seconds of recording each thumbnail stands for
+    pub interval: f64,
+}
+
+/// Cache directory of item `id`
+pub fn cache_dir(id: &str) -> PathBuf {
+    StorageManager::previews_dir().join(id)
+}
+
+/// Drop the cached previews of item `id`
+pub fn remove(id: &str) {
+    let _ = std::fs::remove_dir_all(cache_dir(id));
+}
+
+/// Render the poster and sprite sheet of `input` into `dir`
+pub fn generate(input: &Path, dir: &Path) -> Result<RecordingPreview, CaptureError> {
+    let info = ffmpeg::probe(input)?;
+    if info.duration <= 0.0 || info.width == 0 || info.height == 0 {
+        return Err(CaptureError::ExportFailed(format!("{} has no frames", input.display())));
+    }
+
+    // Replace any previous generation, with new names so webviews don't show cached images
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir)?;
+    let stamp = chrono::Utc::now().timestamp_millis();
+    let poster = dir.join(format!("poster-{stamp}.jpg"));
+    let sprite = dir.join(format!("sprite-{stamp}.jpg"));
+    let never = AtomicBool::new(false);
+    let input_arg = input.to_string_lossy().into_owned();
+
+    // A little way in, past the first frame that often still shows the selection UI
+    let poster_time = (info.duration * 0.1).min(3.0);
+    let poster_args = [
+        "-ss".into(),
+        format!("{poster_time:.3}"),
+        "-i".into(),
+        input_arg.clone(),
+        "-frames:v".into(),
+        "1".into(),
+        "-vf".into(),
+        format!("scale='min({POSTER_MAX_WIDTH},iw)':-2"),
+        "-q:v".into(),
+        "3".into(),
+        poster.to_string_lossy().into_owned(),
+    ];
+    ffmpeg::run(poster_args, 0.0, &never, |_| {})?;
+
+    // One fast input seek per thumbnail instead of decoding the whole recording
+    let interval = info.duration / SPRITE_FRAMES as f64;
+    let tile_height = ((TILE_WIDTH as f64 * info.height as f64 / info.width as f64 / 2.0).round() as u32 * 2).max(2);
+    let mut args = Vec::new();
+    let mut filter = String::new();
+    for i in 0..SPRITE_FRAMES {
+        let time = (i as f64 + 0.5) * interval;
+        args.extend(["-ss".into(), format!("{time:.3}"), "-i".into(), input_arg.clone()]);
+        filter.push_str(&format!("[{i}:v]scale={TILE_WIDTH}:{tile_height},setsar=1,trim=end_frame=1[t{i}];"));
+    }
+    let rows = SPRITE_FRAMES.div_ceil(SPRITE_COLUMNS);
+    filter.extend((0..SPRITE_FRAMES).map(|i| format!("[t{i}]")));
+    filter.push_str(&format!("concat=n={SPRITE_FRAMES}:v=1:a=0,tile={SPRITE_COLUMNS}x{rows}[sprite]"));
+    args.extend([
+        "-filter_complex".into(),
+        filter,
+        "-map".into(),
+        "[sprite]".into(),
+        "-frames:v".into(),
+        "1".into(),
+        "-q:v".into(),
+        "4".into(),
+        sprite.to_string_lossy().into_owned(),
+    ]);
+    ffmpeg::run(args, 0.0, &never, |_| {})?;
+
+    Ok(RecordingPreview {
+        poster: poster.to_string_lossy().into_owned(),
+        sprite: sprite.to_string_lossy().into_owned(),
+        frame_count: SPRITE_FRAMES,
+        columns: SPRITE_COLUMNS,
+        tile_width: TILE_WIDTH,
+        tile_height,
+        interval,
+    })
+}
+
+/// Generate previews for history item `id` on a background thread. Failures are
+/// logged; the item simply stays without a preview.
+pub fn generate_in_background(app: &AppHandle, id: String) {
+    let app = app.clone();
+    let spawned = std::thread::Builder::new().name("recording-preview".into()).spawn(move || {
+        let state = app.state::<AppState>();
+        let input = {
+            let storage = state.storage.lock().unwrap();
+            let Some(item) = storage.history.items.iter().find(|i| i.id == id) else { return };
+            storage.screenshots_dir().join(&item.filename)
+        };
+
+        let preview = match generate(&input, &cache_dir(&id)) {
+            Ok(preview) => preview,
+            Err(e) => {
+                log::warn!("Failed to generate preview of {}: {}", input.display(), e);
+                return;
+            }
+        };
+
+        let mut storage = state.storage.lock().unwrap();
+        // The item may have been deleted meanwhile
+        let Some(item) = storage.history.items.iter_mut().find(|i| i.id == id) else {
+            remove(&id);
+            return;
+        };
+        item.preview = Some(preview);
+        let item: CaptureItem = item.clone();
+        if let Err(e) = storage.save_history() {
+            log::warn!("Failed to save preview of {}: {}", item.filename, e);
+        }
+        drop(storage);
+        let _ = app.emit(crate::events::CAPTURE_PREVIEW_READY, item);
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start preview generation: {}", e);
+    }
+}
@@ -0,0 +1,158 @@
+//! `.partial` recording files: naming, atomic finalisation and crash recovery.
+//!
+//! Recordings are written to `<name>.partial.<ext>` (fragmented MP4, or a streamable
+//! container for other formats) and renamed to `<name>.<ext>` only once the encoder
+//! has closed them cleanly. Anything still named
+//! `.partial` at launch was interrupted (crash, power loss) and is offered for recovery.
+//! Edits render into hidden `.partial` files next to their result instead; those are
+//! never offered, and any left at launch are deleted.
+use crate::error::CaptureError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const PARTIAL_MARKER: &str = ".partial";
+
+/// An interrupted recording found in the storage directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanedRecording {
+    pub filename: String,
+    pub size_bytes: u64,
+    pub modified_at: String,
+}
+
+/// `Recording X.mp4` -> `Recording X.partial.mp4`
+pub fn partial_path(final_path: &Path) -> PathBuf {
+    let stem = final_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match final_path.extension() {
+        Some(ext) => format!("{}{}.{}", stem, PARTIAL_MARKER, ext.to_string_lossy()),
+        None => format!("{}{}", stem, PARTIAL_MARKER),
+    };
+    final_path.with_file_name(name)
+}
+
+/// `Recording X.partial.mp4` -> `Recording X.mp4`, or `None` if not a partial file
+pub fn final_path(partial: &Path) -> Option<PathBuf> {
+    let name = partial.file_name()?.to_str()?;
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) => (stem, Some(ext)),
+        None => (name, None),
+    };
+    let base = stem.strip_suffix(PARTIAL_MARKER)?;
+    let final_name = match ext {
+        Some(ext) => format!("{}.{}", base, ext),
+        None => base.to_string(),
+    };
+    Some(partial.with_file_name(final_name))
+}
+
+pub fn is_partial(path: &Path) -> bool {
+    final_path(path).is_some()
+}
+
+/// Hidden partial file in `dir` for an edit render with a fresh name, so two renders
+/// of one item never share it
+pub fn render_path(dir: &Path, extension: &str) -> PathBuf {
+    dir.join(format!(".render-{}{}.{}", uuid::Uuid::new_v4(), PARTIAL_MARKER, extension))
+}
+
+fn is_render(path: &Path) -> bool {
+    is_partial(path) && path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'))
+}
+
+/// Delete edit renders that a previous run never finished
+pub fn remove_stale_renders(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| is_render(p)) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Could not remove unfinished render {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Atomically rename a completed partial file to its final name
+pub fn finalize(partial: &Path) -> Result<PathBuf, CaptureError> {
+    let dest = final_path(partial)
+        .ok_or_else(|| CaptureError::StorageError(format!("{} is not a partial file", partial.display())))?;
+    if dest.exists() {
+        return Err(CaptureError::StorageError(format!("{} already exists", dest.display())));
+    }
+    std::fs::rename(partial, &dest)?;
+    Ok(dest)
+}
+
+/// Partial recordings left behind in `dir`
+pub fn find_orphans(dir: &Path) -> Vec<OrphanedRecording> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut orphans: Vec<OrphanedRecording> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| is_partial(&e.path()) && !is_render(&e.path()))
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            let modified_at = metadata
+                .modified()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+                .unwrap_or_default();
+            Some(OrphanedRecording {
+                filename: e.file_name().to_string_lossy().into_owned(),
+                size_bytes: metadata.len(),
+                modified_at,
+            })
+        })
+        .collect();
+
+    orphans.sort_by(|a, b| a.modified_at.cmp(&b.modified_at));
+    orphans
+}
+
+/// Resolve an orphan's filename inside `dir`, rejecting anything that isn't a
+/// partial recording directly in that directory
+pub fn orphan_path(dir: &Path, filename: &str) -> Result<PathBuf, CaptureError> {
+    let path = dir.join(filename);
+    let is_plain_name = Path::new(filename).file_name().map(|n| n == filename).unwrap_or(false);
+    if !is_plain_name || !is_partial(&path) || is_render(&path) || !path.is_file() {
+        return Err(CaptureError::StorageError(format!("No interrupted recording named {}", filename)));
+    }
+    Ok(path)
+}
+
+/// Remux an interrupted recording into a regular file at its final name.
+/// ffmpeg drops the truncated trailing fragment or cluster; everything before it is kept.
+pub fn repair(partial: &Path) -> Result<PathBuf, CaptureError> {
+    let dest = final_path(partial)
+        .ok_or_else(|| CaptureError::StorageError(format!("{} is not a partial file", partial.display())))?;
+    if dest.exists() {
+        return Err(CaptureError::StorageError(format!("{} already exists", dest.display())));
+    }
+
+    let ffmpeg = crate::services::ffmpeg::ffmpeg_path()?;
+    let mut command = std::process::Command::new(ffmpeg);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .arg("-i")
+        .arg(partial)
+        .args(["-c", "copy"]);
+    if dest.extension().is_some_and(|ext| ext == "mp4") {
+        command.args(["-movflags", "+faststart"]);
+    }
+    let output = command.arg(&dest).output()?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&dest);
+        return Err(CaptureError::StorageError(format!(
+            "Could not repair {}: {}",
+            partial.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    std::fs::remove_file(partial)?;
+    Ok(dest)
+}
@@ -38,6 +38,8 @@ fn register_shortcuts(app: &tauri::AppHandle, mode: &ShortcutMode) -> Result<(),
         (format!("{}+5", modifier), "capture_window"),
         (format!("{}+7", modifier), "record_area"),
         (format!("{}+9", modifier), "record_fullscreen"),
+        (format!("{}+6", modifier), "save_replay"),
+        (format!("{}+m", modifier), "add_recording_marker"),
     ];
 
     let manager = app.global_shortcut();
@@ -1,18 +1,34 @@
 use std::sync::Mutex;
 use crate::services::storage::manager::StorageManager;
-use crate::capture::recording::RecordingSessionState;
+use crate::capture::recording::ActiveRecording;
+use crate::capture::recording::coordinator::RecordingCoordinator;
+use crate::capture::recording::replay::ReplayBuffer;
+use crate::services::export::ExportJobs;
 
 /// Global application state managed by Tauri
 pub struct AppState {
     pub storage: Mutex<StorageManager>,
-    pub recording_state: Mutex<RecordingSessionState>,
+    pub recording_coordinator: Mutex<RecordingCoordinator>,
+    pub recording: Mutex<Option<ActiveRecording>>,
+    /// Background replay buffer, independent of the recording session
+    pub replay: Mutex<Option<ReplayBuffer>>,
+    pub exports: ExportJobs,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             storage: Mutex::new(StorageManager::new()),
-            recording_state: Mutex::new(RecordingSessionState::Idle),
+            recording_coordinator: Mutex::new(RecordingCoordinator::new()),
+            recording: Mutex::new(None),
+            replay: Mutex::new(None),
+            exports: ExportJobs::default(),
         }
     }
 }
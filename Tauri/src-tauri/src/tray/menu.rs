@@ -2,6 +2,8 @@ use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
 use tauri::tray::TrayIconBuilder;
 use tauri::{AppHandle, Emitter, Manager};
 
+const TRAY_ID: &str = "main";
+
 /// Build and configure the system tray icon with menu
 pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     // Menu items — Capture
@@ -14,6 +16,12 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
 
     let sep1 = PredefinedMenuItem::separator(app)?;
 
+    // Replay buffer
+    let save_replay =
+        MenuItem::with_id(app, "save_replay", "Save Replay              ⌃⇧6", true, None::<&str>)?;
+
+    let sep_replay = PredefinedMenuItem::separator(app)?;
+
     // Utilities
     let open_folder =
         MenuItem::with_id(app, "open_folder", "Open Screenshots Folder", true, None::<&str>)?;
@@ -29,6 +37,8 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
         &capture_area,
         &capture_window,
         &sep1,
+        &save_replay,
+        &sep_replay,
         &open_folder,
         &preferences,
         &sep2,
@@ -39,7 +49,7 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let icon_bytes = include_bytes!("../../icons/tray-icon.png");
     let icon = tauri::image::Image::from_bytes(icon_bytes)?;
 
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(icon)
         .icon_as_template(true)
         .menu(&menu)
@@ -67,6 +77,14 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
                         do_tray_capture_fullscreen(&app_handle).await;
                     });
                 }
+                "save_replay" => {
+                    let app_handle = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = crate::capture::recording::replay::save(&app_handle).await {
+                            log::error!("Tray replay save failed: {}", e);
+                        }
+                    });
+                }
                 "capture_area" => {
                     open_selection_window(app, "selection", "/selection");
                 }
@@ -160,3 +178,16 @@ fn open_selection_window(app: &AppHandle, label: &str, path: &str) {
 pub fn set_recording_icon(_app: &AppHandle, _is_recording: bool) {
     // TODO: Swap between normal icon and red recording indicator
 }
+
+/// Show the running recording time in the tray tooltip, or restore the default
+pub fn set_recording_tooltip(app: &AppHandle, elapsed_seconds: Option<f64>) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else { return };
+    let tooltip = match elapsed_seconds {
+        Some(secs) => {
+            let secs = secs as u64;
+            format!("ScreenCapture — Recording {:02}:{:02}", secs / 60, secs % 60)
+        }
+        None => "ScreenCapture".to_string(),
+    };
+    let _ = tray.set_tooltip(Some(tooltip));
+}
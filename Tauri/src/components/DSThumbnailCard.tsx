@@ -1,7 +1,16 @@
-import React from "react";
+import React, { useState } from "react";
 import clsx from "clsx";
 import styles from "./DSThumbnailCard.module.css";
 
+/** Sprite sheet of evenly spaced frames, tiled row by row */
+export interface ThumbnailScrub {
+  sprite: string;
+  frameCount: number;
+  columns: number;
+  /** Chapter markers as fractions of the duration; hovering one shows its frame */
+  markers?: { position: number; label: string }[];
+}
+
 interface DSThumbnailCardProps {
   src: string;
   title: string;
@@ -9,6 +18,8 @@ interface DSThumbnailCardProps {
   onClick?: () => void;
   isFavorite?: boolean;
   onFavoriteToggle?: () => void;
+  /** Show the frame under the pointer while hovering */
+  scrub?: ThumbnailScrub;
   className?: string;
 }
 
@@ -19,28 +30,77 @@ export const DSThumbnailCard: React.FC<DSThumbnailCardProps> = ({
   onClick,
   isFavorite,
   onFavoriteToggle,
+  scrub,
   className,
-}) => (
-  <div className={clsx(styles.card, className)} onClick={onClick}>
-    <div className={styles.imageWrapper}>
-      <img src={src} alt={title} className={styles.image} />
-      <div className={styles.overlay}>
-        {onFavoriteToggle && (
-          <button
-            className={clsx(styles.favoriteBtn, isFavorite && styles.favorited)}
-            onClick={(e) => {
+}) => {
+  const [frame, setFrame] = useState<number | null>(null);
+
+  const handleMouseMove = (e: React.MouseEvent<HTMLDivElement>) => {
+    if (!scrub) return;
+    const rect = e.currentTarget.getBoundingClientRect();
+    const fraction = Math.min(Math.max((e.clientX - rect.left) / rect.width, 0), 0.999);
+    setFrame(Math.floor(fraction * scrub.frameCount));
+  };
+
+  let scrubStyle: React.CSSProperties | undefined;
+  if (scrub && frame !== null) {
+    const rows = Math.ceil(scrub.frameCount / scrub.columns);
+    const col = frame % scrub.columns;
+    const row = Math.floor(frame / scrub.columns);
+    scrubStyle = {
+      backgroundImage: `url("${scrub.sprite}")`,
+      backgroundSize: `${scrub.columns * 100}% ${rows * 100}%`,
+      backgroundPosition: `${scrub.columns > 1 ? (col / (scrub.columns - 1)) * 100 : 0}% ${
+        rows > 1 ? (row / (rows - 1)) * 100 : 0
+      }%`,
+    };
+  }
+
+  return (
+    <div className={clsx(styles.card, className)} onClick={onClick}>
+      <div
+        className={styles.imageWrapper}
+        onMouseMove={handleMouseMove}
+        onMouseLeave={() => setFrame(null)}
+      >
+        <img src={src} alt={title} className={styles.image} />
+        {scrubStyle && <div className={styles.scrub} style={scrubStyle} />}
+        {scrub && frame !== null && (
+          <div
+            className={styles.scrubProgress}
+            style={{ width: `${((frame + 1) / scrub.frameCount) * 100}%` }}
+          />
+        )}
+        {scrub?.markers?.map((marker, i) => (
+          <div
+            key={i}
+            className={styles.scrubMarker}
+            style={{ left: `${marker.position * 100}%` }}
+            title={marker.label}
+            onMouseMove={(e) => {
               e.stopPropagation();
-              onFavoriteToggle();
+              setFrame(Math.min(Math.floor(marker.position * scrub.frameCount), scrub.frameCount - 1));
             }}
-          >
-            {isFavorite ? "★" : "☆"}
-          </button>
-        )}
+          />
+        ))}
+        <div className={styles.overlay}>
+          {onFavoriteToggle && (
+            <button
+              className={clsx(styles.favoriteBtn, isFavorite && styles.favorited)}
+              onClick={(e) => {
+                e.stopPropagation();
+                onFavoriteToggle();
+              }}
+            >
+              {isFavorite ? "★" : "☆"}
+            </button>
+          )}
+        </div>
+      </div>
+      <div className={styles.info}>
+        <span className={styles.title}>{title}</span>
+        {subtitle && <span className={styles.subtitle}>{subtitle}</span>}
       </div>
     </div>
-    <div className={styles.info}>
-      <span className={styles.title}>{title}</span>
-      {subtitle && <span className={styles.subtitle}>{subtitle}</span>}
-    </div>
-  </div>
-);
+  );
+};
@@ -9,9 +9,11 @@ import {
 } from "@/components";
 import { useHistoryStore } from "@/stores/historyStore";
 import { getHistory, getStorageInfo } from "@/lib/ipc";
+import { onCapturePreviewReady } from "@/lib/events";
 import type { CaptureItem, StorageInfo } from "@/lib/ipc";
 import { HistoryGrid } from "./HistoryGrid";
 import { HistoryList } from "./HistoryList";
+import { RecoveryBanner } from "./RecoveryBanner";
 import styles from "./History.module.css";
 
 function formatBytes(bytes: number): string {
@@ -38,6 +40,8 @@ export const CaptureHistoryView: React.FC = () => {
     sortBy,
     viewMode,
     setItems,
+    addItem,
+    updateItem,
     setSearchQuery,
     setFilterType,
     setSortBy,
@@ -70,6 +74,12 @@ export const CaptureHistoryView: React.FC = () => {
     return () => { cancelled = true; };
   }, [setItems, setLoading]);
 
+  // Recording previews are generated in the background after the item is saved
+  useEffect(() => {
+    const unlisten = onCapturePreviewReady(updateItem);
+    return () => { unlisten.then((fn) => fn()); };
+  }, [updateItem]);
+
   // Derived: filtered + sorted items
   const filteredItems = useMemo(() => {
     let result = items;
@@ -181,6 +191,9 @@ export const CaptureHistoryView: React.FC = () => {
 
       <DSDivider />
 
+      {/* ── Interrupted recordings ── */}
+      <RecoveryBanner onRecovered={addItem} formatBytes={formatBytes} />
+
       {/* ── Content ── */}
       {filteredItems.length === 0 && !isLoading ? (
         <EmptyState hasItems={items.length > 0} />
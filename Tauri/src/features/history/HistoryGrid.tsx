@@ -1,4 +1,5 @@
 import React, { useCallback, useRef, useState } from "react";
+import { convertFileSrc } from "@tauri-apps/api/core";
 import { DSThumbnailCard, DSBadge } from "@/components";
 import { useHistoryStore } from "@/stores/historyStore";
 import { deleteCapture, toggleFavorite } from "@/lib/ipc";
@@ -122,12 +123,23 @@ export const HistoryGrid: React.FC<HistoryGridProps> = ({ items }) => {
             onContextMenu={(e) => handleContextMenu(e, item)}
           >
             <DSThumbnailCard
-              src={`asset://localhost/${item.filename}`}
+              src={item.preview ? convertFileSrc(item.preview.poster) : `asset://localhost/${item.filename}`}
               title={item.filename}
               subtitle={formatRelativeDate(item.created_at)}
               onClick={() => handleOpen(item)}
               isFavorite={item.is_favorite}
               onFavoriteToggle={() => handleFavorite(item)}
+              scrub={
+                item.preview && {
+                  sprite: convertFileSrc(item.preview.sprite),
+                  frameCount: item.preview.frame_count,
+                  columns: item.preview.columns,
+                  markers: item.markers?.map((m) => ({
+                    position: Math.min(m.time / (item.preview!.interval * item.preview!.frame_count), 1),
+                    label: m.name,
+                  })),
+                }
+              }
             />
             <div className={styles.gridBadge}>
               <DSBadge label={captureTypeLabel(item.capture_type)} variant={TYPE_BADGE_VARIANT[item.capture_type]} />
@@ -0,0 +1,93 @@
+import React, { useEffect, useState } from "react";
+import { DSPrimaryButton, DSSecondaryButton } from "@/components";
+import {
+  listOrphanedRecordings,
+  recoverOrphanedRecording,
+  discardOrphanedRecording,
+} from "@/lib/ipc";
+import type { CaptureItem, OrphanedRecording } from "@/lib/ipc";
+import styles from "./History.module.css";
+
+interface RecoveryBannerProps {
+  /** Called with the history item of each recording that was recovered */
+  onRecovered: (item: CaptureItem) => void;
+  formatBytes: (bytes: number) => string;
+}
+
+/** Recordings interrupted by a crash or power loss, offered for recovery */
+export const RecoveryBanner: React.FC<RecoveryBannerProps> = ({ onRecovered, formatBytes }) => {
+  const [orphans, setOrphans] = useState<OrphanedRecording[]>([]);
+  const [busy, setBusy] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  // Pulled on mount: the app finds them at launch, before any window is listening
+  useEffect(() => {
+    let cancelled = false;
+    listOrphanedRecordings()
+      .then((found) => {
+        if (!cancelled) setOrphans(found);
+      })
+      .catch((err: unknown) => console.error("Failed to list interrupted recordings:", err));
+    return () => { cancelled = true; };
+  }, []);
+
+  const resolve = async (filename: string, action: () => Promise<void>) => {
+    setBusy(filename);
+    setError(null);
+    try {
+      await action();
+      setOrphans((current) => current.filter((o) => o.filename !== filename));
+    } catch (err: unknown) {
+      setError(String(err));
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  const handleRecover = (filename: string) =>
+    resolve(filename, async () => onRecovered(await recoverOrphanedRecording(filename)));
+
+  const handleDiscard = (filename: string) =>
+    resolve(filename, () => discardOrphanedRecording(filename));
+
+  if (orphans.length === 0) return null;
+
+  return (
+    <div className={styles.recovery}>
+      <div className={styles.recoveryHeader}>
+        <span className={styles.recoveryTitle}>
+          {orphans.length === 1
+            ? "A recording was interrupted"
+            : `${orphans.length} recordings were interrupted`}
+        </span>
+        <span className={styles.recoveryText}>
+          Recover to keep everything captured before the app closed, or discard to delete the file.
+        </span>
+        {error && <span className={styles.recoveryError}>{error}</span>}
+      </div>
+      {orphans.map((orphan) => (
+        <div key={orphan.filename} className={styles.recoveryRow}>
+          <span className={styles.recoveryName}>{orphan.filename}</span>
+          <span className={styles.recoveryMeta}>
+            {formatBytes(orphan.size_bytes)}
+            {orphan.modified_at && ` · ${new Date(orphan.modified_at).toLocaleString()}`}
+          </span>
+          <DSSecondaryButton
+            danger
+            disabled={busy !== null}
+            onClick={() => handleDiscard(orphan.filename)}
+          >
+            Discard
+          </DSSecondaryButton>
+          <DSPrimaryButton
+            size="sm"
+            disabled={busy !== null}
+            onClick={() => handleRecover(orphan.filename)}
+          >
+            Recover
+          </DSPrimaryButton>
+        </div>
+      ))}
+    </div>
+  );
+};
@@ -53,10 +53,12 @@ export const RecordingSelectionView: React.FC<RecordingSelectionViewProps> = ({
   // Recording options
   const recordingQuality = useSettingsStore((s) => s.recordingQuality);
   const recordingFPS = useSettingsStore((s) => s.recordingFPS);
+  const recordingFormat = useSettingsStore((s) => s.recordingFormat);
   const recordShowCursor = useSettingsStore((s) => s.recordShowCursor);
   const recordMicrophone = useSettingsStore((s) => s.recordMicrophone);
   const recordSystemAudio = useSettingsStore((s) => s.recordSystemAudio);
   const showMouseClicks = useSettingsStore((s) => s.showMouseClicks);
+  const showKeystrokes = useSettingsStore((s) => s.showKeystrokes);
   const setSetting = useSettingsStore((s) => s.setSetting);
   const setRecordingState = useCaptureStore((s) => s.setRecordingState);
 
@@ -105,9 +107,11 @@ export const RecordingSelectionView: React.FC<RecordingSelectionViewProps> = ({
       fps: recordingFPS,
       include_cursor: recordShowCursor,
       show_mouse_clicks: showMouseClicks,
+      show_keystrokes: showKeystrokes,
       include_microphone: audioEnabled,
       include_system_audio: recordSystemAudio,
       exclude_app_audio: false,
+      format: recordingFormat,
     };
 
     const target: ipc.RecordingTarget = {
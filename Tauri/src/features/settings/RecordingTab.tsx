@@ -1,7 +1,10 @@
-import React from "react";
+import React, { useEffect, useState } from "react";
 import clsx from "clsx";
 import { DSChip, DSDivider } from "@/components";
 import { useSettingsStore } from "@/stores/settingsStore";
+import { getReplayStatus, listOutputFormats, startReplayBuffer, stopReplayBuffer } from "@/lib/ipc";
+import type { FormatSupport } from "@/lib/ipc";
+import { onReplayStateChanged } from "@/lib/events";
 import styles from "./Settings.module.css";
 
 const QUALITY_OPTIONS = [
@@ -10,6 +13,13 @@ const QUALITY_OPTIONS = [
   { value: "high" as const, label: "High (Native)" },
 ];
 
+const REPLAY_DURATION_OPTIONS = [
+  { value: 15 as const, label: "15 s" },
+  { value: 30 as const, label: "30 s" },
+  { value: 60 as const, label: "1 min" },
+  { value: 120 as const, label: "2 min" },
+];
+
 const FPS_OPTIONS = [
   { value: 30 as const, label: "30 FPS" },
   { value: 60 as const, label: "60 FPS" },
@@ -19,12 +29,43 @@ export const RecordingTab: React.FC = () => {
   const {
     recordingQuality,
     recordingFPS,
+    recordingFormat,
     recordShowCursor,
     recordMicrophone,
     recordSystemAudio,
     showMouseClicks,
+    showKeystrokes,
+    replayDuration,
     setSetting,
   } = useSettingsStore();
+  const [formats, setFormats] = useState<FormatSupport[]>([]);
+  const [replayActive, setReplayActive] = useState(false);
+
+  useEffect(() => {
+    getReplayStatus()
+      .then((status) => setReplayActive(status?.active ?? false))
+      .catch(() => setReplayActive(false));
+    const unlisten = onReplayStateChanged((status) => setReplayActive(status.active));
+    return () => { unlisten.then((fn) => fn()); };
+  }, []);
+
+  const toggleReplay = async () => {
+    try {
+      if (replayActive) {
+        await stopReplayBuffer();
+      } else {
+        await startReplayBuffer({ duration_seconds: replayDuration });
+      }
+    } catch (err: unknown) {
+      console.error("Failed to toggle replay buffer:", err);
+    }
+  };
+
+  useEffect(() => {
+    listOutputFormats()
+      .then(setFormats)
+      .catch((err: unknown) => console.error("Failed to list output formats:", err));
+  }, []);
 
   return (
     <>
@@ -59,6 +100,20 @@ export const RecordingTab: React.FC = () => {
           </div>
         </div>
 
+        <div className={styles.row}>
+          <span className={styles.label}>Format</span>
+          <div className={styles.chipGroup}>
+            {formats.filter((f) => f.available).map((f) => (
+              <DSChip
+                key={f.format}
+                label={f.label}
+                selected={recordingFormat === f.format}
+                onClick={() => setSetting("recordingFormat", f.format)}
+              />
+            ))}
+          </div>
+        </div>
+
         <div className={styles.row}>
           <span className={styles.label}>Show cursor</span>
           <button
@@ -105,7 +160,38 @@ export const RecordingTab: React.FC = () => {
 
         <div className={styles.row}>
           <span className={styles.label}>Keystroke overlay</span>
-          <span className={styles.description} style={{ marginTop: 0 }}>Unavailable</span>
+          <button
+            className={clsx(styles.toggle, showKeystrokes && styles.toggleOn)}
+            onClick={() => setSetting("showKeystrokes", !showKeystrokes)}
+          />
+        </div>
+      </section>
+
+      <DSDivider />
+
+      <section className={styles.section} style={{ marginTop: "var(--ds-spacing-xl)" }}>
+        <h3 className={styles.sectionTitle}>Instant Replay</h3>
+
+        <div className={styles.row}>
+          <span className={styles.label}>Keep recent screen activity</span>
+          <button
+            className={clsx(styles.toggle, replayActive && styles.toggleOn)}
+            onClick={toggleReplay}
+          />
+        </div>
+
+        <div className={styles.row}>
+          <span className={styles.label}>Replay length</span>
+          <div className={styles.chipGroup}>
+            {REPLAY_DURATION_OPTIONS.map((opt) => (
+              <DSChip
+                key={opt.value}
+                label={opt.label}
+                selected={replayDuration === opt.value}
+                onClick={() => setSetting("replayDuration", opt.value)}
+              />
+            ))}
+          </div>
         </div>
       </section>
     </>
@@ -48,6 +48,12 @@ export function useGlobalShortcuts() {
             alwaysOnTop: true,
           });
           break;
+        case "save_replay":
+          try { await ipc.saveReplay(); } catch (err) { console.error("Replay save failed:", err); }
+          break;
+        case "add_recording_marker":
+          try { await ipc.addRecordingMarker(); } catch { /* not recording */ }
+          break;
       }
     }).then((fn) => {
       unlisten = fn;
@@ -2,6 +2,7 @@
  * Typed Tauri event listeners for backend -> frontend communication
  */
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { CaptureItem, RecordingMarker, RecordingStats, ReplayStatus } from "./ipc";
 
 // Event payload types
 export interface RecordingStatePayload {
@@ -17,6 +18,28 @@ export interface CaptureCompletedPayload {
   is_favorite: boolean;
 }
 
+export interface AudioLevelPayload {
+  source: "microphone" | "system";
+  peak: number;
+  rms: number;
+}
+
+export interface ExportProgressPayload {
+  job_id: string;
+  source_id: string;
+  kind: string;
+  progress: number;
+}
+
+export interface RecordingCompletedPayload extends CaptureCompletedPayload {
+  stop_reason: "requested" | "maxDuration" | "maxFileSize" | "lowDiskSpace" | "windowClosed";
+}
+
+/** "hidden" when the recorded window is minimised and the recording paused itself */
+export interface SourceStatusPayload {
+  status: "live" | "hidden" | "closed";
+}
+
 export interface ShortcutPayload {
   action: string;
 }
@@ -31,12 +54,33 @@ export const onRecordingDuration = (handler: (seconds: number) => void): Promise
 export const onCaptureCompleted = (handler: (payload: CaptureCompletedPayload) => void): Promise<UnlistenFn> =>
   listen<CaptureCompletedPayload>("capture:completed", (e) => handler(e.payload));
 
-export const onRecordingCompleted = (handler: (payload: CaptureCompletedPayload) => void): Promise<UnlistenFn> =>
-  listen<CaptureCompletedPayload>("recording:completed", (e) => handler(e.payload));
+export const onCapturePreviewReady = (handler: (item: CaptureItem) => void): Promise<UnlistenFn> =>
+  listen<CaptureItem>("capture:preview-ready", (e) => handler(e.payload));
+
+export const onRecordingCompleted = (handler: (payload: RecordingCompletedPayload) => void): Promise<UnlistenFn> =>
+  listen<RecordingCompletedPayload>("recording:completed", (e) => handler(e.payload));
 
 export const onRecordingFailed = (handler: (message: string) => void): Promise<UnlistenFn> =>
   listen<{ message: string }>("recording:failed", (e) => handler(e.payload.message));
 
+export const onRecordingSourceStatus = (handler: (payload: SourceStatusPayload) => void): Promise<UnlistenFn> =>
+  listen<SourceStatusPayload>("recording:source-status", (e) => handler(e.payload));
+
+export const onRecordingMarkerAdded = (handler: (marker: RecordingMarker) => void): Promise<UnlistenFn> =>
+  listen<RecordingMarker>("recording:marker-added", (e) => handler(e.payload));
+
+export const onRecordingStats = (handler: (stats: RecordingStats) => void): Promise<UnlistenFn> =>
+  listen<RecordingStats>("recording:stats", (e) => handler(e.payload));
+
+export const onRecordingAudioLevel = (handler: (payload: AudioLevelPayload) => void): Promise<UnlistenFn> =>
+  listen<AudioLevelPayload>("recording:audio-level", (e) => handler(e.payload));
+
+export const onExportProgress = (handler: (payload: ExportProgressPayload) => void): Promise<UnlistenFn> =>
+  listen<ExportProgressPayload>("export:progress", (e) => handler(e.payload));
+
+export const onReplayStateChanged = (handler: (status: ReplayStatus) => void): Promise<UnlistenFn> =>
+  listen<ReplayStatus>("replay:state-changed", (e) => handler(e.payload));
+
 export const onShortcutTriggered = (handler: (action: string) => void): Promise<UnlistenFn> =>
   listen<ShortcutPayload>("shortcut:triggered", (e) => handler(e.payload.action));
 
@@ -12,8 +12,68 @@ export interface CaptureItem {
   filename: string;
   created_at: string;
   is_favorite: boolean;
+  edit?: RecordingEdit;
+  preview?: RecordingPreview;
+  markers?: RecordingMarker[];
+  stats?: RecordingStats;
 }
 
+/** Health of a recording session; live via `recording:stats`, final on the item */
+export interface RecordingStats {
+  elapsed_seconds: number;
+  frames_captured: number;
+  frames_written: number;
+  frames_encoded: number;
+  /** Output frames repeated because capture fell behind */
+  frames_dropped: number;
+  dropped_percent: number;
+  encoder_queue_depth: number;
+  max_encoder_queue_depth: number;
+  /** Audio minus video, null without audio */
+  av_drift_ms: number | null;
+  max_av_drift_ms: number | null;
+  output_bytes: number;
+  /** Bits per second */
+  output_bitrate: number;
+}
+
+/** Seconds on the item's timeline */
+export interface RecordingMarker {
+  time: number;
+  name: string;
+}
+
+/** Cached poster frame and scrub sprite sheet of a recording (absolute paths) */
+export interface RecordingPreview {
+  poster: string;
+  sprite: string;
+  frame_count: number;
+  columns: number;
+  tile_width: number;
+  tile_height: number;
+  interval: number;
+}
+
+export interface TimeRange {
+  start: number;
+  end: number;
+}
+
+/** Trim points in seconds on the unedited recording's timeline */
+export interface TrimEdit {
+  start?: number;
+  end?: number | null;
+  cuts?: TimeRange[];
+}
+
+export interface RecordingEdit {
+  source: string;
+  trim: TrimEdit;
+  source_markers?: RecordingMarker[];
+}
+
+export type EditMode = "newItem" | "replace";
+
 export interface CaptureHistory {
   items: CaptureItem[];
 }
@@ -41,14 +101,73 @@ export interface WindowInfo {
   height: number;
 }
 
+export interface OverlayStyle {
+  keystroke_position: "topLeft" | "topCenter" | "topRight" | "bottomLeft" | "bottomCenter" | "bottomRight";
+  font_size: number;
+  /** RGBA, 0-255 */
+  text_color: [number, number, number, number];
+  background_color: [number, number, number, number];
+  keystroke_duration_ms: number;
+  click_color: [number, number, number, number];
+  ripple_radius: number;
+}
+
+export type OutputFormat = "mp4" | "hevc" | "webm" | "av1" | "webp" | "apng";
+
+export interface FormatSupport {
+  format: OutputFormat;
+  label: string;
+  extension: string;
+  supports_audio: boolean;
+  available: boolean;
+}
+
+/** Explicit encoder settings; output is scaled to fit within width x height */
+export interface CustomQuality {
+  width: number;
+  height: number;
+  /** Bits per second */
+  bitrate: number;
+  /** 0-51, lower is better */
+  crf: number;
+  keyframe_interval_seconds: number;
+}
+
+export type QualityPreset = "low" | "medium" | "high" | { custom: CustomQuality };
+
 export interface RecordingConfig {
-  quality: "low" | "medium" | "high";
+  quality: QualityPreset;
   fps: number;
   include_cursor: boolean;
   show_mouse_clicks: boolean;
+  show_keystrokes?: boolean;
+  overlay?: Partial<OverlayStyle>;
   include_microphone: boolean;
   include_system_audio: boolean;
   exclude_app_audio: boolean;
+  format?: OutputFormat;
+  audio_mode?: "mix" | "separateTracks";
+  /** Window targets: fit resized windows into the initial size with bars, or stretch */
+  window_scaling?: "letterbox" | "stretch";
+  microphone_device?: string;
+  system_audio_device?: string;
+  max_duration_seconds?: number;
+  max_file_size_bytes?: number;
+  min_free_disk_bytes?: number;
+}
+
+export interface ReplayConfig {
+  display_id?: number;
+  duration_seconds?: number;
+  fps?: number;
+  quality?: QualityPreset;
+  include_cursor?: boolean;
+}
+
+export interface ReplayStatus {
+  active: boolean;
+  duration_seconds: number;
+  buffered_seconds: number;
 }
 
 export type RecordingTarget =
@@ -63,6 +182,135 @@ export interface StorageInfo {
   total_size_bytes: number;
 }
 
+export interface AudioDevice {
+  id: string;
+  name: string;
+  kind: "microphone" | "system";
+  is_default: boolean;
+}
+
+export interface OrphanedRecording {
+  filename: string;
+  size_bytes: number;
+  modified_at: string;
+}
+
+export interface GifExportOptions {
+  quality?: "low" | "medium" | "high" | "original";
+  fps?: number;
+  max_width?: number;
+  dither?:
+    | { type: "none" }
+    | { type: "bayer"; scale: number }
+    | { type: "floyd_steinberg" }
+    | { type: "sierra2_4a" };
+  remove_duplicates?: boolean;
+  per_scene_palette?: boolean;
+}
+
+export interface ZoomSegment {
+  start: number;
+  end: number;
+  zoom: number;
+  /** Fixed point in video pixels; follows the cursor when omitted */
+  center?: [number, number];
+}
+
+export interface ZoomExportOptions {
+  /** Derived from the recording's clicks when omitted */
+  segments?: ZoomSegment[];
+  zoom?: number;
+  smooth_cursor?: boolean;
+  cursor_scale?: number;
+}
+
+export interface SpeedSegment {
+  start: number;
+  end: number;
+  speed: number;
+}
+
+export interface SpeedExportOptions {
+  /** Speed outside idle stretches and segments (default 1) */
+  speed?: number;
+  segments?: SpeedSegment[];
+  /** Speed up stretches where the screen doesn't change */
+  idle?: { speed?: number; min_seconds?: number };
+  keep_audio?: boolean;
+}
+
+export interface CompressOptions {
+  /** Largest allowed size in MB (1 MB = 1,000,000 bytes), e.g. 10 or 25 */
+  target_mb: number;
+  keep_audio?: boolean;
+}
+
+export type ConcatTransition = { type: "cut" } | { type: "crossfade"; seconds: number };
+
+export interface ConcatOptions {
+  /** Output size; defaults to the first clip's, others are letterboxed */
+  width?: number;
+  height?: number;
+  /** Defaults to the highest frame rate among the clips */
+  fps?: number;
+  transition?: ConcatTransition;
+  /** Title card shown before each clip, by index; empty strings get none */
+  titles?: string[];
+  title_seconds?: number;
+  title_background?: [number, number, number, number];
+  title_color?: [number, number, number, number];
+}
+
+export type AspectRatio = "16:9" | "1:1" | "9:16" | "4:5";
+
+/** Colours are RGBA */
+export type FramingBackground =
+  | { type: "solid"; color: [number, number, number, number] }
+  | {
+      type: "gradient";
+      from: [number, number, number, number];
+      to: [number, number, number, number];
+      /** Degrees; 0 runs left to right */
+      angle: number;
+    }
+  | { type: "blur"; radius: number };
+
+export interface FramingOptions {
+  aspect?: AspectRatio;
+  /** Length of the longer side in pixels */
+  size?: number;
+  background?: FramingBackground;
+  padding?: number;
+  corner_radius?: number;
+  /** `null` for no shadow */
+  shadow?: { blur: number; opacity: number; offset: number } | null;
+}
+
+export interface FramingPreset {
+  name: string;
+  options: FramingOptions;
+}
+
+type Rgba = [number, number, number, number];
+
+/** Coordinates are video pixels; later overlays draw on top */
+export type OverlayShape =
+  | { type: "text"; x: number; y: number; text: string; font_size: number; color: Rgba; background?: Rgba | null }
+  | { type: "arrow"; from: { x: number; y: number }; to: { x: number; y: number }; color: Rgba; width: number }
+  | {
+      type: "rectangle";
+      x: number;
+      y: number;
+      width: number;
+      height: number;
+      color: Rgba;
+      stroke_width: number;
+      fill?: Rgba | null;
+    }
+  | { type: "blur"; x: number; y: number; width: number; height: number; radius: number };
+
+export type TimedOverlay = OverlayShape & { start: number; end: number };
+
 export interface TextBlock {
   text: string;
   confidence: number;
@@ -97,6 +345,7 @@ export const captureArea = (
     format: format === "jpeg" ? { jpeg: { quality: 0.9 } } : format,
   });
 
+/** On Wayland only `windowId` 0 works; it lets the user pick in the system dialog */
 export const captureWindow = (
   windowId: number,
   includeCursor = false,
@@ -115,22 +364,44 @@ export const startRecording = (target: RecordingTarget, config: RecordingConfig)
 
 export const stopRecording = () => invoke<CaptureItem>("stop_recording");
 
+export const pauseRecording = () => invoke<void>("pause_recording");
+
+export const resumeRecording = () => invoke<void>("resume_recording");
+
 export const cancelRecording = () => invoke<void>("cancel_recording");
 
 export interface RecordingState {
-  state: "idle" | "selecting" | "starting" | "recording" | "stopping" | "completed" | "failed" | "cancelled";
+  state: "idle" | "selecting" | "starting" | "recording" | "paused" | "stopping" | "exportingGif" | "completed" | "failed" | "cancelled";
   elapsed_seconds?: number;
   message?: string;
 }
 
+export const addRecordingMarker = (name?: string) =>
+  invoke<RecordingMarker>("add_recording_marker", { name });
+
 export const getRecordingState = () => invoke<RecordingState>("get_recording_state");
 
+// === Instant Replay ===
+
+export const startReplayBuffer = (config?: ReplayConfig) =>
+  invoke<ReplayStatus>("start_replay_buffer", { config });
+
+export const stopReplayBuffer = () => invoke<boolean>("stop_replay_buffer");
+
+export const saveReplay = () => invoke<CaptureItem>("save_replay");
+
+export const getReplayStatus = () => invoke<ReplayStatus | null>("get_replay_status");
+
 // === Content Discovery ===
 
 export const listDisplays = () => invoke<DisplayInfo[]>("list_displays");
 
 export const listWindows = () => invoke<WindowInfo[]>("list_windows");
 
+export const listAudioDevices = () => invoke<AudioDevice[]>("list_audio_devices");
+
+export const listOutputFormats = () => invoke<FormatSupport[]>("list_output_formats");
+
 // === OCR ===
 
 export const recognizeText = (imagePath: string, languages?: string[]) =>
@@ -149,6 +420,65 @@ export const getStorageInfo = () => invoke<StorageInfo>("get_storage_info");
 export const setStorageLocation = (location: "default" | "desktop" | { custom: { path: string } }) =>
   invoke<void>("set_storage_location", { location });
 
+export const listOrphanedRecordings = () => invoke<OrphanedRecording[]>("list_orphaned_recordings");
+
+export const recoverOrphanedRecording = (filename: string) =>
+  invoke<CaptureItem>("recover_orphaned_recording", { filename });
+
+export const discardOrphanedRecording = (filename: string) =>
+  invoke<void>("discard_orphaned_recording", { filename });
+
+// === Export ===
+
+export const exportGif = (id: string, options?: GifExportOptions, jobId?: string) =>
+  invoke<CaptureItem>("export_gif", { id, options, jobId });
+
+export const exportAnimatedImage = (
+  id: string,
+  format: "webp" | "apng",
+  options?: GifExportOptions,
+  jobId?: string,
+) => invoke<CaptureItem>("export_animated_image", { id, format, options, jobId });
+
+export const suggestZoomSegments = (id: string, zoom?: number) =>
+  invoke<ZoomSegment[]>("suggest_zoom_segments", { id, zoom });
+
+export const exportAutoZoom = (id: string, options?: ZoomExportOptions, jobId?: string) =>
+  invoke<CaptureItem>("export_auto_zoom", { id, options, jobId });
+
+export const exportSpeed = (id: string, options?: SpeedExportOptions, jobId?: string) =>
+  invoke<CaptureItem>("export_speed", { id, options, jobId });
+
+export const concatRecordings = (ids: string[], options?: ConcatOptions, jobId?: string) =>
+  invoke<CaptureItem>("concat_recordings", { ids, options, jobId });
+
+export const exportCompressed = (id: string, options: CompressOptions, jobId?: string) =>
+  invoke<CaptureItem>("export_compressed", { id, options, jobId });
+
+export const getFramingPresets = () => invoke<FramingPreset[]>("get_framing_presets");
+
+export const saveFramingPreset = (preset: FramingPreset) =>
+  invoke<FramingPreset[]>("save_framing_preset", { preset });
+
+export const deleteFramingPreset = (name: string) => invoke<boolean>("delete_framing_preset", { name });
+
+export const getRecordingOverlays = (id: string) => invoke<TimedOverlay[]>("get_recording_overlays", { id });
+
+export const setRecordingOverlays = (id: string, overlays: TimedOverlay[]) =>
+  invoke<void>("set_recording_overlays", { id, overlays });
+
+export const exportRecordingOverlays = (id: string, jobId?: string) =>
+  invoke<CaptureItem>("export_recording_overlays", { id, jobId });
+
+/** Pass `options`, or the name of a saved `preset` */
+export const exportFramed = (id: string, options?: FramingOptions, preset?: string, jobId?: string) =>
+  invoke<CaptureItem>("export_framed", { id, options, preset, jobId });
+
+export const cancelExport = (jobId: string) => invoke<boolean>("cancel_export", { jobId });
+
+export const trimRecording = (id: string, edit: TrimEdit, mode?: EditMode, jobId?: string) =>
+  invoke<CaptureItem>("trim_recording", { id, edit, mode, jobId });
+
 // === Permissions ===
 
 export const checkScreenRecordingPermission = () =>
@@ -12,6 +12,7 @@ interface HistoryState {
   setItems: (items: CaptureItem[]) => void;
   addItem: (item: CaptureItem) => void;
   removeItem: (id: string) => void;
+  updateItem: (item: CaptureItem) => void;
   toggleFavorite: (id: string) => void;
   setSearchQuery: (q: string) => void;
   setFilterType: (t: HistoryState["filterType"]) => void;
@@ -31,6 +32,7 @@ export const useHistoryStore = create<HistoryState>((set) => ({
   setItems: (items) => set({ items }),
   addItem: (item) => set((s) => ({ items: [item, ...s.items] })),
   removeItem: (id) => set((s) => ({ items: s.items.filter((i) => i.id !== id) })),
+  updateItem: (item) => set((s) => ({ items: s.items.map((i) => (i.id === item.id ? item : i)) })),
   toggleFavorite: (id) =>
     set((s) => ({
       items: s.items.map((i) => (i.id === id ? { ...i, is_favorite: !i.is_favorite } : i)),
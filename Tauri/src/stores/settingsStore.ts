@@ -1,5 +1,6 @@
 import { create } from "zustand";
 import type { AfterCaptureAction, ScreenCorner } from "@/lib/constants";
+import type { OutputFormat } from "@/lib/ipc";
 
 interface SettingsState {
   // General
@@ -22,10 +23,13 @@ interface SettingsState {
   // Recording
   recordingQuality: "low" | "medium" | "high";
   recordingFPS: 30 | 60;
+  recordingFormat: OutputFormat;
   recordShowCursor: boolean;
   recordMicrophone: boolean;
   recordSystemAudio: boolean;
   showMouseClicks: boolean;
+  showKeystrokes: boolean;
+  replayDuration: 15 | 30 | 60 | 120;
 
   // Storage
   storageLocation: "default" | "desktop" | "custom";
@@ -50,10 +54,13 @@ export const useSettingsStore = create<SettingsState>((set) => ({
   jpegQuality: 0.9,
   recordingQuality: "high",
   recordingFPS: 60,
+  recordingFormat: "mp4",
   recordShowCursor: true,
   recordMicrophone: false,
   recordSystemAudio: true,
   showMouseClicks: true,
+  showKeystrokes: false,
+  replayDuration: 30,
   storageLocation: "default",
   autoCleanup: true,
   cleanupDays: 30,